@@ -9,6 +9,7 @@ fn main() {
 	match Command::new("solc")
 		.arg("--abi")
 		.arg("--bin")
+		.arg("--bin-runtime")
 		.arg("--optimize")
 		.arg("--output-dir").arg("../compiled_contracts")
 		.arg("--overwrite")
@@ -1,13 +1,21 @@
-use std::time::Duration;
+use std::cmp;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
-use futures::{Future, Stream, Poll};
-use tokio_timer::{Timer, Interval, Timeout};
-use web3::{self, api, Transport};
-use web3::api::Namespace;
-use web3::types::{Log, Filter, H256, H520, U256, FilterBuilder, TransactionRequest, Bytes, Address, CallRequest};
-use web3::helpers::CallResult;
+use futures::{Future, Stream, Poll, Async};
+use tokio_timer::{Timer, Interval, Timeout, Sleep};
+use web3::{self, Transport};
+use web3::types::{Log, Filter, H256, H520, U256, FilterBuilder, TransactionRequest, Bytes, Address, CallRequest, Block, BlockNumber, Transaction};
+use web3::helpers::{self, CallResult};
+use config::{Retry, RetryPolicy};
 use error::{Error, ErrorKind};
+use journal::RelayKind;
+use metrics;
+use pubsub::NewHeads;
+use status::{self, Status};
+use transport::is_connection_error;
 
 /// Imperative alias for web3 function.
 pub use web3::confirm::send_transaction_with_confirmation;
@@ -16,6 +24,10 @@ pub use web3::confirm::send_transaction_with_confirmation;
 pub struct ApiCall<T, F> {
 	future: CallResult<T, F>,
 	message: &'static str,
+	/// Parameters of the request, so it can be sent again.
+	params: Vec<Value>,
+	/// When the call has been polled for the first time.
+	started: Option<Instant>,
 }
 
 impl<T, F> ApiCall<T, F> {
@@ -30,55 +42,241 @@ impl<T: DeserializeOwned, F: Future<Item = Value, Error = web3::Error>>Future fo
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
 		trace!(target: "bridge", "{}", self.message);
-		self.future.poll().map_err(ErrorKind::Web3).map_err(Into::into)
+		let started = *self.started.get_or_insert_with(Instant::now);
+		let result = self.future.poll();
+		match result {
+			Ok(Async::NotReady) => {},
+			Ok(Async::Ready(_)) => metrics::rpc_call(self.message, started.elapsed()),
+			Err(_) => {
+				metrics::rpc_call(self.message, started.elapsed());
+				metrics::rpc_error(self.message);
+			},
+		}
+		result.map_err(ErrorKind::Web3).map_err(Into::into)
 	}
 }
 
-/// Imperative wrapper for web3 function.
-pub fn logs<T: Transport>(transport: T, filter: &Filter) -> ApiCall<Vec<Log>, T::Out> {
+/// Returns true if the request timed out or failed because the connection to the node has been lost.
+pub fn is_connection_lost(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Timeout(_) => true,
+		ErrorKind::Web3(ref err) => is_connection_error(err),
+		_ => false,
+	}
+}
+
+fn execute<T: Transport, R>(transport: T, method: &'static str, params: Vec<Value>) -> ApiCall<R, T::Out> {
 	ApiCall {
-		future: api::Eth::new(transport).logs(filter),
-		message: "eth_getLogs",
+		future: CallResult::new(transport.execute(method, params.clone())),
+		message: method,
+		params,
+		started: None,
 	}
 }
 
+/// Imperative wrapper for web3 function.
+pub fn logs<T: Transport>(transport: T, filter: &Filter) -> ApiCall<Vec<Log>, T::Out> {
+	execute(transport, "eth_getLogs", vec![helpers::serialize(filter)])
+}
+
 /// Imperative wrapper for web3 function.
 pub fn block_number<T: Transport>(transport: T) -> ApiCall<U256, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).block_number(),
-		message: "eth_blockNumber",
-	}
+	execute(transport, "eth_blockNumber", vec![])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn gas_price<T: Transport>(transport: T) -> ApiCall<U256, T::Out> {
+	execute(transport, "eth_gasPrice", vec![])
+}
+
+/// Imperative wrapper for web3 function. Returns the block with full transactions.
+pub fn block_with_txs<T: Transport>(transport: T, number: u64) -> ApiCall<Option<Block<Transaction>>, T::Out> {
+	execute(transport, "eth_getBlockByNumber", vec![helpers::serialize(&BlockNumber::Number(number)), helpers::serialize(&true)])
 }
 
 /// Imperative wrapper for web3 function.
 pub fn send_transaction<T: Transport>(transport: T, tx: TransactionRequest) -> ApiCall<H256, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).send_transaction(tx),
-		message: "eth_sendTransaction",
-	}
+	execute(transport, "eth_sendTransaction", vec![helpers::serialize(&tx)])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn send_raw_transaction<T: Transport>(transport: T, rlp: Bytes) -> ApiCall<H256, T::Out> {
+	execute(transport, "eth_sendRawTransaction", vec![helpers::serialize(&rlp)])
 }
 
 /// Imperative wrapper for web3 function.
 pub fn call<T: Transport>(transport: T, address: Address, payload: Bytes) -> ApiCall<Bytes, T::Out> {
-	let future = api::Eth::new(transport).call(CallRequest {
+	call_at(transport, address, payload, BlockNumber::Latest)
+}
+
+/// Imperative wrapper for web3 function, executing the call in the state of given block.
+pub fn call_at<T: Transport>(transport: T, address: Address, payload: Bytes, block: BlockNumber) -> ApiCall<Bytes, T::Out> {
+	let request = CallRequest {
 		from: None,
 		to: address,
 		gas: None,
 		gas_price: None,
 		value: None,
 		data: Some(payload),
-	}, None);
+	};
 
-	ApiCall {
-		future,
-		message: "eth_call",
-	}
+	execute(transport, "eth_call", vec![helpers::serialize(&request), helpers::serialize(&block)])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn code<T: Transport>(transport: T, address: Address) -> ApiCall<Bytes, T::Out> {
+	execute(transport, "eth_getCode", vec![helpers::serialize(&address), helpers::serialize(&BlockNumber::Latest)])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn storage<T: Transport>(transport: T, address: Address, position: U256) -> ApiCall<H256, T::Out> {
+	execute(transport, "eth_getStorageAt", vec![helpers::serialize(&address), helpers::serialize(&position), helpers::serialize(&BlockNumber::Latest)])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn estimate_gas<T: Transport>(transport: T, request: CallRequest) -> ApiCall<U256, T::Out> {
+	execute(transport, "eth_estimateGas", vec![helpers::serialize(&request)])
+}
+
+/// Estimates gas of the transaction `request`, e.g. of a contract creation which `CallRequest` can't describe.
+pub fn estimate_transaction_gas<T: Transport>(transport: T, request: TransactionRequest) -> ApiCall<U256, T::Out> {
+	execute(transport, "eth_estimateGas", vec![helpers::serialize(&request)])
 }
 
 pub fn sign<T: Transport>(transport: T, address: Address, data: Bytes) -> ApiCall<H520, T::Out> {
-	ApiCall {
-		future: api::Eth::new(transport).sign(address, data),
-		message: "eth_sign",
+	execute(transport, "eth_sign", vec![helpers::serialize(&address), helpers::serialize(&data)])
+}
+
+/// Imperative wrapper for web3 function. Includes transactions pending in the queue.
+pub fn transaction_count<T: Transport>(transport: T, address: Address) -> ApiCall<U256, T::Out> {
+	execute(transport, "eth_getTransactionCount", vec![helpers::serialize(&address), helpers::serialize(&BlockNumber::Pending)])
+}
+
+/// Signs data with the account managed by a remote signer.
+/// Data with `text/plain` content type is signed the same way `eth_sign` does.
+pub fn account_sign_data<T: Transport>(transport: T, content_type: &str, address: Address, data: Bytes) -> ApiCall<H520, T::Out> {
+	let params = vec![helpers::serialize(&content_type), helpers::serialize(&address), helpers::serialize(&data)];
+	execute(transport, "account_signData", params)
+}
+
+/// Subset of the `account_signTransaction` response.
+#[derive(Debug, Deserialize)]
+pub struct SignedTransactionResponse {
+	pub raw: Bytes,
+}
+
+/// Signs transaction with the account managed by a remote signer.
+pub fn account_sign_transaction<T: Transport>(transport: T, tx: &TransactionRequest) -> ApiCall<SignedTransactionResponse, T::Out> {
+	execute(transport, "account_signTransaction", vec![helpers::serialize(tx)])
+}
+
+/// Subset of the `eth_getTransactionReceipt` response.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+	pub transaction_hash: H256,
+	pub block_number: Option<U256>,
+	pub contract_address: Option<Address>,
+	/// `1` if the transaction succeeded, `0` if it failed. Missing before byzantium.
+	pub status: Option<U256>,
+}
+
+/// Fetches receipt of the transaction. Resolves to `None` if the transaction is not mined yet.
+pub fn transaction_receipt<T: Transport>(transport: T, hash: H256) -> ApiCall<Option<Receipt>, T::Out> {
+	execute(transport, "eth_getTransactionReceipt", vec![helpers::serialize(&hash)])
+}
+
+/// Imperative wrapper for web3 function.
+pub fn transaction<T: Transport>(transport: T, hash: H256) -> ApiCall<Option<Transaction>, T::Out> {
+	execute(transport, "eth_getTransactionByHash", vec![helpers::serialize(&hash)])
+}
+
+/// Subset of the `eth_getBlockByNumber` response needed to detect chain reorganizations.
+#[derive(Debug, Deserialize)]
+pub struct BlockHeader {
+	pub number: Option<U256>,
+	pub hash: Option<H256>,
+}
+
+/// Fetches header of the block with given number. Resolves to `None` if the block is unknown.
+pub fn block_header<T: Transport>(transport: T, number: u64) -> ApiCall<Option<BlockHeader>, T::Out> {
+	let params = vec![helpers::serialize(&BlockNumber::Number(number)), helpers::serialize(&false)];
+	execute(transport, "eth_getBlockByNumber", params)
+}
+
+/// Subscribes to notifications about new blocks. Resolves to the id of the subscription.
+pub fn subscribe_new_heads<T: Transport>(transport: T) -> ApiCall<String, T::Out> {
+	execute(transport, "eth_subscribe", vec![helpers::serialize(&"newHeads")])
+}
+
+enum RetryCallState<R, F> {
+	/// Waiting for the response.
+	Call(Timeout<ApiCall<R, F>>),
+	/// Waiting before the request is sent again.
+	Backoff(Sleep),
+}
+
+/// Request which is sent again with exponential backoff if it times out or the connection to the node is lost.
+///
+/// Deterministic failures, e.g. reverted calls or invalid responses, are never retried.
+/// Only idempotent requests should be retried.
+pub struct RetryCall<T: Transport, R> {
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	method: &'static str,
+	params: Vec<Value>,
+	policy: RetryPolicy,
+	/// Number of times the request has been sent again.
+	retries: u32,
+	state: RetryCallState<R, T::Out>,
+}
+
+/// Sends `call` and retries it over `transport` following the `retry` policy of its method.
+/// Every attempt times out after `request_timeout`.
+pub fn retry_call<T: Transport, R>(transport: T, timer: Timer, request_timeout: Duration, retry: &Retry, call: ApiCall<R, T::Out>) -> RetryCall<T, R> {
+	RetryCall {
+		method: call.message,
+		params: call.params.clone(),
+		policy: retry.policy(call.message).clone(),
+		retries: 0,
+		state: RetryCallState::Call(timer.timeout(call, request_timeout)),
+		transport,
+		timer,
+		request_timeout,
+	}
+}
+
+impl<T: Transport, R: DeserializeOwned> Future for RetryCall<T, R> {
+	type Item = R;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				RetryCallState::Call(ref mut future) => match future.poll() {
+					Ok(result) => return Ok(result),
+					Err(err) => {
+						let err = Error::from(err);
+						if self.retries >= self.policy.attempts || !is_connection_lost(&err) {
+							return Err(err);
+						}
+						self.retries += 1;
+						let delay = self.policy.delay(self.retries);
+						warn!("{} failed: {}, retrying in {:?} ({}/{})", self.method, err, delay, self.retries, self.policy.attempts);
+						status::record_error(format!("{} failed: {}", self.method, err));
+						RetryCallState::Backoff(self.timer.sleep(delay))
+					},
+				},
+				RetryCallState::Backoff(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					let call = execute(&self.transport, self.method, self.params.clone());
+					RetryCallState::Call(self.timer.timeout(call, self.request_timeout))
+				},
+			};
+
+			self.state = next_state;
+		}
 	}
 }
 
@@ -89,6 +287,12 @@ pub struct LogStreamInit {
 	pub request_timeout: Duration,
 	pub poll_interval: Duration,
 	pub confirmations: usize,
+	/// Number of blocks below the last yielded block which are checked for reorganizations.
+	/// `0` disables reorganization tracking.
+	pub reorg_depth: usize,
+	/// Maximum number of blocks whose logs are fetched with a single request.
+	/// Larger ranges are fetched and yielded in chunks. `None` means no limit.
+	pub max_block_range: Option<u64>,
 }
 
 /// Contains all logs matching `LogStream` filter in inclusive range `[from, to]`.
@@ -99,20 +303,57 @@ pub struct LogStreamItem {
 	pub logs: Vec<Log>,
 }
 
+/// Item yielded by `LogStream`.
+#[derive(Debug, PartialEq)]
+pub enum LogStreamEvent {
+	/// New confirmed logs.
+	Logs(LogStreamItem),
+	/// Blocks in inclusive range `[from, to]` have been yielded before, but are no longer
+	/// part of the canonical chain. Log stream rewinds and yields this range again.
+	Reorg {
+		from: u64,
+		to: u64,
+	},
+}
+
+/// Range of blocks which has been yielded together with hash of its last block.
+struct YieldedRange {
+	from: u64,
+	to: u64,
+	hash: H256,
+}
+
 /// Log Stream state.
 enum LogStreamState<T: Transport> {
-	/// Log Stream is waiting for timer to poll.
+	/// Log Stream is waiting for a new head or for timer to poll.
 	Wait,
 	/// Fetching best block number.
 	FetchBlockNumber(Timeout<ApiCall<U256, T::Out>>),
+	/// Checking if the last yielded block is still part of the canonical chain.
+	CheckReorg {
+		last_confirmed_block: u64,
+		/// First block of the ranges which turned out to be orphaned so far.
+		orphaned_from: Option<u64>,
+		future: RetryCall<T, Option<BlockHeader>>,
+	},
+	/// Fetching hash of the last block in range, before fetching its logs.
+	FetchBlockHash {
+		from: u64,
+		to: u64,
+		/// Last confirmed block, greater than `to` if the range is fetched in chunks.
+		last: u64,
+		future: RetryCall<T, Option<BlockHeader>>,
+	},
 	/// Fetching logs for new best block.
 	FetchLogs {
 		from: u64,
 		to: u64,
-		future: Timeout<ApiCall<Vec<Log>, T::Out>>,
+		last: u64,
+		hash: Option<H256>,
+		future: RetryCall<T, Vec<Log>>,
 	},
 	/// All logs has been fetched.
-	NextItem(Option<LogStreamItem>),
+	NextItem(Option<LogStreamEvent>),
 }
 
 /// Creates new `LogStream`.
@@ -126,6 +367,14 @@ pub fn log_stream<T: Transport>(transport: T, timer: Timer, init: LogStreamInit)
 		filter: init.filter,
 		confirmations: init.confirmations,
 		request_timeout: init.request_timeout,
+		reorg_depth: init.reorg_depth as u64,
+		max_block_range: init.max_block_range,
+		catch_up: None,
+		yielded: VecDeque::new(),
+		status: None,
+		new_heads: None,
+		failed_new_heads: None,
+		retry: Retry::default(),
 	}
 }
 
@@ -139,38 +388,206 @@ pub struct LogStream<T: Transport> {
 	filter: FilterBuilder,
 	confirmations: usize,
 	request_timeout: Duration,
+	reorg_depth: u64,
+	max_block_range: Option<u64>,
+	/// Last confirmed block the stream is catching up to, once the current chunk is yielded.
+	catch_up: Option<u64>,
+	/// Recently yielded ranges, oldest first.
+	yielded: VecDeque<YieldedRange>,
+	/// Status and metrics updated with every fetched block number.
+	status: Option<(RelayKind, Arc<Status>)>,
+	/// Numbers of new blocks pushed by the node. Stream polls for them if it's missing.
+	new_heads: Option<NewHeads>,
+	/// Subscription which has failed, polled again after the next poll interval so it can subscribe again.
+	failed_new_heads: Option<NewHeads>,
+	/// Policies of retrying failed requests for logs and block hashes.
+	retry: Retry,
+}
+
+impl<T: Transport + Clone> LogStream<T> {
+	/// Reports every fetched block number of the chain scanned by bridge component `kind` to `status` and metrics.
+	pub fn with_status(mut self, kind: RelayKind, status: Arc<Status>) -> Self {
+		self.status = Some((kind, status));
+		self
+	}
+
+	/// Fetches logs when the node pushes a new block instead of polling for it every `poll_interval`.
+	/// Stream falls back to polling while the subscription fails and polls the subscription again
+	/// every `poll_interval`, so it can subscribe again, e.g. after a reconnect.
+	pub fn with_new_heads(mut self, new_heads: Option<NewHeads>) -> Self {
+		self.new_heads = new_heads;
+		self
+	}
+
+	/// Retries requests for logs and block hashes which timed out or lost the connection following `retry`,
+	/// instead of failing the stream.
+	pub fn with_retry(mut self, retry: Retry) -> Self {
+		self.retry = retry;
+		self
+	}
+
+	/// Sends `call`, retrying it if it fails with a transient error.
+	fn retry_call<R>(&self, call: ApiCall<R, T::Out>) -> RetryCall<T, R> {
+		retry_call(self.transport.clone(), self.timer.clone(), self.request_timeout, &self.retry, call)
+	}
+
+	/// Polls the subscription to new blocks, if any. Puts the subscription aside once it fails and drops it once it ends.
+	fn poll_new_heads(&mut self) -> Option<Async<u64>> {
+		let result = match self.new_heads {
+			Some(ref mut new_heads) => new_heads.poll(),
+			None => return None,
+		};
+
+		match result {
+			Ok(Async::Ready(Some(head))) => Some(Async::Ready(head)),
+			Ok(Async::NotReady) => Some(Async::NotReady),
+			Ok(Async::Ready(None)) => {
+				warn!("subscription to new blocks has been closed, falling back to polling");
+				self.new_heads = None;
+				None
+			},
+			Err(err) => {
+				warn!("subscription to new blocks failed with {}, polling until it's re-established", err);
+				self.failed_new_heads = self.new_heads.take();
+				None
+			},
+		}
+	}
+
+	fn on_head(&mut self, last_block: u64) -> LogStreamState<T> {
+		if let Some((kind, ref status)) = self.status {
+			status.head(kind, last_block);
+			metrics::head_block(kind, last_block);
+		}
+		let last_confirmed_block = last_block.saturating_sub(self.confirmations as u64);
+		if last_confirmed_block <= self.after {
+			LogStreamState::Wait
+		} else if self.yielded.is_empty() {
+			self.fetch_range(last_confirmed_block)
+		} else {
+			self.check_reorg(last_confirmed_block, None)
+		}
+	}
+
+	fn check_reorg(&self, last_confirmed_block: u64, orphaned_from: Option<u64>) -> LogStreamState<T> {
+		let last = self.yielded.back().expect("check_reorg is called only when there are yielded ranges; qed");
+		LogStreamState::CheckReorg {
+			last_confirmed_block,
+			orphaned_from,
+			future: self.retry_call(block_header(&self.transport, last.to)),
+		}
+	}
+
+	/// Fetches logs of blocks after the last yielded one, up to `last_confirmed_block` or `max_block_range` blocks.
+	fn fetch_range(&self, last_confirmed_block: u64) -> LogStreamState<T> {
+		let from = self.after + 1;
+		let to = match self.max_block_range {
+			Some(max) => cmp::min(last_confirmed_block, from + max - 1),
+			None => last_confirmed_block,
+		};
+
+		if self.reorg_depth == 0 {
+			self.fetch_logs(from, to, last_confirmed_block, None)
+		} else {
+			LogStreamState::FetchBlockHash {
+				from,
+				to,
+				last: last_confirmed_block,
+				future: self.retry_call(block_header(&self.transport, to)),
+			}
+		}
+	}
+
+	fn fetch_logs(&self, from: u64, to: u64, last: u64, hash: Option<H256>) -> LogStreamState<T> {
+		let filter = self.filter.clone()
+			.from_block(from.into())
+			.to_block(to.into())
+			.build();
+		LogStreamState::FetchLogs {
+			from,
+			to,
+			last,
+			hash,
+			future: self.retry_call(logs(&self.transport, &filter)),
+		}
+	}
+
+	/// Forgets all ranges starting at `from` and rewinds the stream so they are fetched again.
+	fn rewind(&mut self, from: u64) -> LogStreamEvent {
+		let to = self.after;
+		self.yielded.retain(|range| range.to < from);
+		self.after = from - 1;
+		LogStreamEvent::Reorg { from, to }
+	}
+
+	fn remember(&mut self, from: u64, to: u64, hash: H256) {
+		self.yielded.push_back(YieldedRange { from, to, hash });
+		while self.yielded.front().map_or(false, |range| range.to + self.reorg_depth < to) {
+			self.yielded.pop_front();
+		}
+	}
 }
 
-impl<T: Transport> Stream for LogStream<T> {
-	type Item = LogStreamItem;
+impl<T: Transport + Clone> Stream for LogStream<T> {
+	type Item = LogStreamEvent;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
 		loop {
 			let next_state = match self.state {
-				LogStreamState::Wait => {
-					let _ = try_stream!(self.interval.poll());
-					LogStreamState::FetchBlockNumber(self.timer.timeout(block_number(&self.transport), self.request_timeout))
+				LogStreamState::Wait => match self.poll_new_heads() {
+					Some(Async::Ready(last_block)) => self.on_head(last_block),
+					Some(Async::NotReady) => return Ok(Async::NotReady),
+					None => {
+						let _ = try_stream!(self.interval.poll());
+						// the failed subscription subscribes again once the block number is fetched
+						self.new_heads = self.failed_new_heads.take();
+						LogStreamState::FetchBlockNumber(self.timer.timeout(block_number(&self.transport), self.request_timeout))
+					},
 				},
-				LogStreamState::FetchBlockNumber(ref mut future) => {
-					let last_block = try_ready!(future.poll()).low_u64();
-					let last_confirmed_block = last_block.saturating_sub(self.confirmations as u64);
-					if last_confirmed_block > self.after {
-						let from = self.after + 1;
-						let filter = self.filter.clone()
-							.from_block(from.into())
-							.to_block(last_confirmed_block.into())
-							.build();
-						LogStreamState::FetchLogs {
-							from: from,
-							to: last_confirmed_block,
-							future: self.timer.timeout(logs(&self.transport, &filter), self.request_timeout),
+				LogStreamState::FetchBlockNumber(ref mut future) => match future.poll() {
+					Ok(Async::Ready(last_block)) => self.on_head(last_block.low_u64()),
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Err(err) => {
+						let err = Error::from(err);
+						if !is_connection_lost(&err) {
+							return Err(err);
 						}
-					} else {
+						// nothing has been fetched yet, so the stream simply tries again on the next tick
+						warn!("cannot fetch the latest block number: {}, retrying", err);
 						LogStreamState::Wait
+					},
+				},
+				LogStreamState::CheckReorg { ref mut future, last_confirmed_block, orphaned_from } => {
+					let header = try_ready!(future.poll());
+					let canonical = {
+						let last = self.yielded.back().expect("CheckReorg state is entered only when there are yielded ranges; qed");
+						header.and_then(|header| header.hash) == Some(last.hash)
+					};
+
+					if canonical {
+						match orphaned_from {
+							Some(from) => LogStreamState::NextItem(Some(self.rewind(from))),
+							None => self.fetch_range(last_confirmed_block),
+						}
+					} else {
+						let orphaned = self.yielded.pop_back().expect("CheckReorg state is entered only when there are yielded ranges; qed");
+						warn!(target: "bridge", "block {} is no longer part of the canonical chain", orphaned.to);
+						if self.yielded.is_empty() {
+							LogStreamState::NextItem(Some(self.rewind(orphaned.from)))
+						} else {
+							self.check_reorg(last_confirmed_block, Some(orphaned.from))
+						}
 					}
 				},
-				LogStreamState::FetchLogs { ref mut future, from, to } => {
+				LogStreamState::FetchBlockHash { ref mut future, from, to, last } => {
+					match try_ready!(future.poll()).and_then(|header| header.hash) {
+						Some(hash) => self.fetch_logs(from, to, last, Some(hash)),
+						// the node does not know the block yet, try again later
+						None => LogStreamState::Wait,
+					}
+				},
+				LogStreamState::FetchLogs { ref mut future, from, to, last, hash } => {
 					let logs = try_ready!(future.poll());
 					let item = LogStreamItem {
 						from,
@@ -178,11 +595,22 @@ impl<T: Transport> Stream for LogStream<T> {
 						logs,
 					};
 
+					if let Some(hash) = hash {
+						self.remember(from, to, hash);
+					}
 					self.after = to;
-					LogStreamState::NextItem(Some(item))
+					if to < last {
+						info!("fetched logs of blocks {}..{}, catching up to block {}", from, to, last);
+						self.catch_up = Some(last);
+					}
+					LogStreamState::NextItem(Some(LogStreamEvent::Logs(item)))
 				},
 				LogStreamState::NextItem(ref mut item) => match item.take() {
-					None => LogStreamState::Wait,
+					// fetch the next chunk right away, the head is known to be far enough
+					None => match self.catch_up.take() {
+						Some(last) => self.fetch_range(last),
+						None => LogStreamState::Wait,
+					},
 					some => return Ok(some.into()),
 				},
 			};
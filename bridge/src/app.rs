@@ -2,11 +2,17 @@ use std::path::{Path, PathBuf};
 use tokio_core::reactor::{Handle};
 use tokio_timer::Timer;
 use web3::Transport;
-use web3::transports::ipc::Ipc;
-use error::{Error, ResultExt, ErrorKind};
-use config::Config;
+use error::Error;
+use config::{Config, Node, TransportConfig};
+use nonce::Nonces;
+use signer::Signers;
+use journal::Journal;
+use failover::FailoverTransport;
+use gas_limits::GasLimits;
+use gas_price::GasPrices;
+use pubsub::Subscriptions;
+use status::Status;
 use contracts::{home, foreign};
-use web3::transports::http::Http;
 
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
@@ -18,7 +24,21 @@ pub struct App<T> where T: Transport {
 	pub home_bridge: home::HomeBridge,
 	pub foreign_bridge: foreign::ForeignBridge,
 	pub timer: Timer,
-	pub running: Arc<AtomicBool>
+	pub running: Arc<AtomicBool>,
+	/// Nonces of transactions sent from authority accounts.
+	pub nonces: Nonces,
+	/// Signers of authority accounts.
+	pub signers: Signers,
+	/// Journal of relays and checkpoints. Kept only in memory until `open_journal` is called.
+	pub journal: Arc<Journal>,
+	/// Health and status reported over http.
+	pub status: Arc<Status>,
+	/// Nodes pushing new blocks, log streams poll the nodes missing here.
+	pub subscriptions: Subscriptions,
+	/// Gas limits of relay transactions.
+	pub gas_limits: Arc<GasLimits>,
+	/// Gas prices of transactions sent to home and foreign.
+	pub gas_prices: Arc<GasPrices>,
 }
 
 pub struct Connections<T> where T: Transport {
@@ -26,41 +46,13 @@ pub struct Connections<T> where T: Transport {
 	pub foreign: T,
 }
 
-impl Connections<Ipc> {
-	pub fn new_ipc<P: AsRef<Path>>(handle: &Handle, home: P, foreign: P) -> Result<Self, Error> {
-		let home = Ipc::with_event_loop(home, handle)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(|| "Cannot connect to home node ipc")?;
-		let foreign = Ipc::with_event_loop(foreign, handle)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(|| "Cannot connect to foreign node ipc")?;
-
+impl Connections<FailoverTransport> {
+	/// Opens connections to all endpoints of home and foreign nodes, reporting their connectivity to `status`.
+	/// Lost connections are re-established with exponential backoff.
+	pub fn from_config(handle: &Handle, timer: &Timer, config: &Config, status: &Arc<Status>) -> Result<Self, Error> {
 		let result = Connections {
-			home,
-			foreign,
-		};
-		Ok(result)
-	}
-}
-
-
-impl Connections<Http>  {
-	pub fn new_http(handle: &Handle, home: &str, foreign: &str) -> Result<Self, Error> {
-
-	    let home = Http::with_event_loop(home, handle,1)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(||"Cannot connect to home node rpc")?;
-		let foreign = Http::with_event_loop(foreign, handle, 1)
-			.map_err(ErrorKind::Web3)
-			.map_err(Error::from)
-			.chain_err(||"Cannot connect to foreign node rpc")?;
-
-		let result = Connections {
-			home,
-			foreign
+			home: FailoverTransport::new(handle, timer, &config.home, "home")?.with_status(status.clone(), timer, &config.home),
+			foreign: FailoverTransport::new(handle, timer, &config.foreign, "foreign")?.with_status(status.clone(), timer, &config.foreign),
 		};
 		Ok(result)
 	}
@@ -75,51 +67,54 @@ impl<T: Transport> Connections<T> {
 	}
 }
 
-impl App<Ipc> {
-	pub fn new_ipc<P: AsRef<Path>>(config: Config, database_path: P, handle: &Handle, running: Arc<AtomicBool>) -> Result<Self, Error> {
-		let connections = Connections::new_ipc(handle, &config.home.ipc, &config.foreign.ipc)?;
-		let result = App {
-			config,
-			database_path: database_path.as_ref().to_path_buf(),
-			connections,
-			home_bridge: home::HomeBridge::default(),
-			foreign_bridge: foreign::ForeignBridge::default(),
-			timer: Timer::default(),
-			running,
-		};
-		Ok(result)
-	}
+/// Returns true if any endpoint of the node is connected over websockets, so it can push new blocks.
+fn has_websocket(node: &Node) -> bool {
+	node.endpoints.iter().any(|endpoint| match *endpoint {
+		TransportConfig::Ws(_) => true,
+		TransportConfig::Ipc(_) | TransportConfig::Http(_) => false,
+	})
 }
 
-impl App<Http> {
-	pub fn new_http<P: AsRef<Path>>(config: Config, database_path: P, handle: &Handle, running: Arc<AtomicBool>) -> Result<Self, Error> {
-		//TODO [edwardmack] is this the best way to build a string?
-		let mut home_url:String = config.home.rpc_host.to_owned();
-		let c_string: &str =  ":";
-		let home_port_string = config.home.rpc_port.to_string();
-		home_url.push_str(c_string);
-		home_url.push_str(&home_port_string);
-
-		let mut foreign_url:String = config.foreign.rpc_host.to_owned();
-		let foreign_port_string = config.foreign.rpc_port.to_string();
-		foreign_url.push_str(c_string);
-		foreign_url.push_str(&foreign_port_string);
-
-		let connections = Connections::new_http(handle, home_url.as_ref(), foreign_url.as_ref())?;
+impl App<FailoverTransport> {
+	pub fn from_config<P: AsRef<Path>>(config: Config, database_path: P, handle: &Handle, running: Arc<AtomicBool>) -> Result<Self, Error> {
+		let timer = Timer::default();
+		let status = Arc::new(Status::new(&config));
+		Status::install(&status);
+		let connections = Connections::from_config(handle, &timer, &config, &status)?;
+		let subscriptions = Subscriptions {
+			home: if has_websocket(&config.home) { Some(connections.home.clone()) } else { None },
+			foreign: if has_websocket(&config.foreign) { Some(connections.foreign.clone()) } else { None },
+		};
+		let signers = Signers::from_config(&config, handle, &timer)?;
+		let gas_limits = GasLimits::new(&config.txs);
+		let gas_prices = GasPrices::from_config(&config, handle);
 		let result = App {
 			config,
 			database_path: database_path.as_ref().to_path_buf(),
 			connections,
 			home_bridge: home::HomeBridge::default(),
 			foreign_bridge: foreign::ForeignBridge::default(),
-			timer: Timer::default(),
+			timer,
 			running,
+			nonces: Nonces::default(),
+			signers,
+			journal: Default::default(),
+			status,
+			subscriptions,
+			gas_limits: Arc::new(gas_limits),
+			gas_prices: Arc::new(gas_prices),
 		};
 		Ok(result)
 	}
 }
 
 impl<T: Transport> App<T> {
+	/// Opens the journal kept next to the database, creating it if it doesn't exist yet.
+	pub fn open_journal(&mut self) -> Result<(), Error> {
+		self.journal = Arc::new(Journal::open(Journal::path(&self.database_path))?);
+		Ok(())
+	}
+
 	pub fn as_ref(&self) -> App<&T> {
 		App {
 			config: self.config.clone(),
@@ -129,6 +124,13 @@ impl<T: Transport> App<T> {
 			foreign_bridge: foreign::ForeignBridge::default(),
 			timer: self.timer.clone(),
 			running: self.running.clone(),
+			nonces: self.nonces.clone(),
+			signers: self.signers.clone(),
+			journal: self.journal.clone(),
+			status: self.status.clone(),
+			subscriptions: self.subscriptions.clone(),
+			gas_limits: self.gas_limits.clone(),
+			gas_prices: self.gas_prices.clone(),
 		}
 	}
 }
@@ -1,112 +1,154 @@
+use std::fmt;
 use std::sync::Arc;
 use futures::{Future, Poll, future};
+use rustc_hex::ToHex;
 use web3::Transport;
-use web3::confirm::SendTransactionWithConfirmation;
-use web3::types::{TransactionRequest};
+use web3::types::{Address, TransactionRequest, U256};
+use api::{self, RetryCall};
 use app::App;
 use database::Database;
-use error::{Error, ErrorKind};
-use api;
-
-pub enum Deployed {
-	/// No existing database found. Deployed new contracts.
-	New(Database),
-	/// Reusing existing contracts.
-	Existing(Database),
+use error::Error;
+use nonce::{self, SendTransaction};
+use transaction::{PendingTransaction, PendingTransactionInit, pending_transaction};
+
+/// Constructor transactions of home and foreign bridge contracts.
+fn deploy_requests<T: Transport>(app: &App<T>) -> (TransactionRequest, TransactionRequest) {
+	let main_data = app.home_bridge.constructor(
+		app.config.home.contract.bin.clone().0,
+		app.config.authorities.required_signatures,
+		app.config.authorities.accounts.clone(),
+		app.config.estimated_gas_cost_of_withdraw
+	);
+	let test_data = app.foreign_bridge.constructor(
+		app.config.foreign.contract.bin.clone().0,
+		app.config.authorities.required_signatures,
+		app.config.authorities.accounts.clone(),
+		app.config.estimated_gas_cost_of_withdraw
+	);
+
+	let main_tx_request = TransactionRequest {
+		from: app.config.home.account,
+		to: None,
+		gas: Some(app.config.txs.home_deploy.gas.into()),
+		gas_price: Some(app.gas_prices.home.price(app.config.txs.home_deploy.gas_price)),
+		value: None,
+		data: Some(main_data.into()),
+		nonce: None,
+		condition: None,
+	};
+
+	let test_tx_request = TransactionRequest {
+		from: app.config.foreign.account,
+		to: None,
+		gas: Some(app.config.txs.foreign_deploy.gas.into()),
+		gas_price: Some(app.gas_prices.foreign.price(app.config.txs.foreign_deploy.gas_price)),
+		value: None,
+		data: Some(test_data.into()),
+		nonce: None,
+		condition: None,
+	};
+
+	(main_tx_request, test_tx_request)
 }
 
 enum DeployState<T: Transport + Clone> {
-	CheckIfNeeded,
-	Deploying(future::Join<SendTransactionWithConfirmation<T>, SendTransactionWithConfirmation<T>>),
+	SendTransactions {
+		future: future::Join<SendTransaction<T>, SendTransaction<T>>,
+		main_tx_request: Option<TransactionRequest>,
+		test_tx_request: Option<TransactionRequest>,
+	},
+	Deploying(future::Join<PendingTransaction<T>, PendingTransaction<T>>),
 }
 
+/// Creates new `Deploy` sending constructor transactions of both bridge contracts.
 pub fn create_deploy<T: Transport + Clone>(app: Arc<App<T>>) -> Deploy<T> {
+	let (main_tx_request, test_tx_request) = deploy_requests(&app);
+
+	let main_future = nonce::send_transaction(
+		app.connections.home.clone(),
+		app.timer.clone(),
+		app.nonces.home.clone(),
+		app.signers.home.clone(),
+		main_tx_request.clone(),
+		app.config.home.request_timeout
+	);
+
+	let test_future = nonce::send_transaction(
+		app.connections.foreign.clone(),
+		app.timer.clone(),
+		app.nonces.foreign.clone(),
+		app.signers.foreign.clone(),
+		test_tx_request.clone(),
+		app.config.foreign.request_timeout
+	);
+
 	Deploy {
 		app,
-		state: DeployState::CheckIfNeeded,
+		state: DeployState::SendTransactions {
+			future: main_future.join(test_future),
+			main_tx_request: Some(main_tx_request),
+			test_tx_request: Some(test_tx_request),
+		},
 	}
 }
 
+/// Deploys new bridge contracts and returns the database of their deployment.
 pub struct Deploy<T: Transport + Clone> {
 	app: Arc<App<T>>,
 	state: DeployState<T>,
 }
 
 impl<T: Transport + Clone> Future for Deploy<T> {
-	type Item = Deployed;
+	type Item = Database;
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
 		loop {
 			let next_state = match self.state {
-				DeployState::CheckIfNeeded => match Database::load(&self.app.database_path).map_err(ErrorKind::from) {
-					Ok(database) => return Ok(Deployed::Existing(database).into()),
-					Err(ErrorKind::MissingFile(_)) => {
-						let main_data = self.app.home_bridge.constructor(
-							self.app.config.home.contract.bin.clone().0,
-							self.app.config.authorities.required_signatures,
-							self.app.config.authorities.accounts.clone(),
-							self.app.config.estimated_gas_cost_of_withdraw
-						);
-						let test_data = self.app.foreign_bridge.constructor(
-							self.app.config.foreign.contract.bin.clone().0,
-							self.app.config.authorities.required_signatures,
-							self.app.config.authorities.accounts.clone(),
-							self.app.config.estimated_gas_cost_of_withdraw
-						);
-
-						let main_tx_request = TransactionRequest {
-							from: self.app.config.home.account,
-							to: None,
-							gas: Some(self.app.config.txs.home_deploy.gas.into()),
-							gas_price: Some(self.app.config.txs.home_deploy.gas_price.into()),
-							value: None,
-							data: Some(main_data.into()),
-							nonce: None,
-							condition: None,
-						};
-
-						let test_tx_request = TransactionRequest {
-							from: self.app.config.foreign.account,
-							to: None,
-							gas: Some(self.app.config.txs.foreign_deploy.gas.into()),
-							gas_price: Some(self.app.config.txs.foreign_deploy.gas_price.into()),
-							value: None,
-							data: Some(test_data.into()),
-							nonce: None,
-							condition: None,
-						};
-
-						let main_future = api::send_transaction_with_confirmation(
-							self.app.connections.home.clone(),
-							main_tx_request,
-							self.app.config.home.poll_interval,
-							self.app.config.home.required_confirmations
-						);
-
-						let test_future = api::send_transaction_with_confirmation(
-							self.app.connections.foreign.clone(),
-							test_tx_request,
-							self.app.config.foreign.poll_interval,
-							self.app.config.foreign.required_confirmations
-						);
-
-						DeployState::Deploying(main_future.join(test_future))
-					},
-					Err(err) => return Err(err.into()),
+				DeployState::SendTransactions { ref mut future, ref mut main_tx_request, ref mut test_tx_request } => {
+					let ((main_hash, _), (test_hash, _)) = try_ready!(future.poll());
+					let app = &self.app;
+
+					let main_future = pending_transaction(app.connections.home.clone(), app.timer.clone(), PendingTransactionInit {
+						hash: main_hash,
+						request: main_tx_request.take().expect("requests are taken only once; qed"),
+						request_timeout: app.config.home.request_timeout,
+						poll_interval: app.config.home.poll_interval,
+						confirmations: app.config.home.required_confirmations,
+						resubmission: app.config.txs.resubmission.clone(),
+						signer: app.signers.home.clone(),
+						retry: app.config.retry.clone(),
+					});
+
+					let test_future = pending_transaction(app.connections.foreign.clone(), app.timer.clone(), PendingTransactionInit {
+						hash: test_hash,
+						request: test_tx_request.take().expect("requests are taken only once; qed"),
+						request_timeout: app.config.foreign.request_timeout,
+						poll_interval: app.config.foreign.poll_interval,
+						confirmations: app.config.foreign.required_confirmations,
+						resubmission: app.config.txs.resubmission.clone(),
+						signer: app.signers.foreign.clone(),
+						retry: app.config.retry.clone(),
+					});
+
+					DeployState::Deploying(main_future.join(test_future))
 				},
 				DeployState::Deploying(ref mut future) => {
-					let (main_receipt, test_receipt) = try_ready!(future.poll().map_err(ErrorKind::Web3));
+					let (main_outcome, test_outcome) = try_ready!(future.poll());
+					let main_receipt = main_outcome.receipt()?;
+					let test_receipt = test_outcome.receipt()?;
+					let main_block = main_receipt.block_number.expect("receipts are returned only for mined transactions; qed").low_u64();
+					let test_block = test_receipt.block_number.expect("receipts are returned only for mined transactions; qed").low_u64();
 					let database = Database {
 						home_contract_address: main_receipt.contract_address.expect("contract creation receipt must have an address; qed"),
 						foreign_contract_address: test_receipt.contract_address.expect("contract creation receipt must have an address; qed"),
-						home_deploy: main_receipt.block_number.low_u64(),
-						foreign_deploy: test_receipt.block_number.low_u64(),
-						checked_deposit_relay: main_receipt.block_number.low_u64(),
-						checked_withdraw_relay: test_receipt.block_number.low_u64(),
-						checked_withdraw_confirm: test_receipt.block_number.low_u64(),
+						home_deploy: main_block,
+						foreign_deploy: test_block,
+						checked_deposit_relay: main_block,
+						checked_withdraw_relay: test_block,
+						checked_withdraw_confirm: test_block,
 					};
-					return Ok(Deployed::New(database).into())
+					return Ok(database.into())
 				},
 			};
 
@@ -114,3 +156,136 @@ impl<T: Transport + Clone> Future for Deploy<T> {
 		}
 	}
 }
+
+/// Constructor transaction which `deploy` would send to one of the chains.
+#[derive(Debug, PartialEq)]
+pub struct PlannedDeployment {
+	pub contract: &'static str,
+	pub from: Address,
+	pub gas: U256,
+	pub gas_price: U256,
+	/// Gas estimated by the node.
+	pub estimated_gas: U256,
+	/// Contract bytecode followed by the encoded constructor arguments.
+	pub data: Vec<u8>,
+}
+
+impl fmt::Display for PlannedDeployment {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{}:", self.contract)?;
+		writeln!(f, "from = {:?}", self.from)?;
+		writeln!(f, "gas = {}", self.gas)?;
+		writeln!(f, "gas_price = {}", self.gas_price)?;
+		write!(f, "estimated_gas = {}", self.estimated_gas)?;
+		if self.estimated_gas > self.gas {
+			write!(f, " (exceeds configured gas)")?;
+		}
+		writeln!(f)?;
+		write!(f, "data = 0x{}", self.data.to_hex())
+	}
+}
+
+/// Transactions which `deploy` would send, together with the authorities passed to the constructors.
+#[derive(Debug, PartialEq)]
+pub struct DeployPlan {
+	pub authorities: Vec<Address>,
+	pub required_signatures: u32,
+	pub home: PlannedDeployment,
+	pub foreign: PlannedDeployment,
+}
+
+impl fmt::Display for DeployPlan {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "authorities = {:?}", self.authorities)?;
+		writeln!(f, "required_signatures = {}", self.required_signatures)?;
+		writeln!(f)?;
+		writeln!(f, "{}", self.home)?;
+		writeln!(f)?;
+		write!(f, "{}", self.foreign)
+	}
+}
+
+/// Creates new `DeployDryRun` estimating constructor transactions of both bridge contracts.
+pub fn create_deploy_dry_run<T: Transport + Clone>(app: Arc<App<T>>) -> DeployDryRun<T> {
+	let (main_tx_request, test_tx_request) = deploy_requests(&app);
+
+	// the node estimates gas up to its block gas limit
+	let estimate = |transport: &T, request_timeout, request: &TransactionRequest| {
+		let call = api::estimate_transaction_gas(transport, TransactionRequest {
+			gas: None,
+			gas_price: None,
+			..request.clone()
+		});
+		api::retry_call(transport.clone(), app.timer.clone(), request_timeout, &app.config.retry, call)
+	};
+	let future = estimate(&app.connections.home, app.config.home.request_timeout, &main_tx_request)
+		.join(estimate(&app.connections.foreign, app.config.foreign.request_timeout, &test_tx_request));
+
+	DeployDryRun {
+		app,
+		main_tx_request,
+		test_tx_request,
+		future,
+	}
+}
+
+/// Estimates gas of deployment without sending any transactions.
+pub struct DeployDryRun<T: Transport> {
+	app: Arc<App<T>>,
+	main_tx_request: TransactionRequest,
+	test_tx_request: TransactionRequest,
+	future: future::Join<RetryCall<T, U256>, RetryCall<T, U256>>,
+}
+
+impl<T: Transport> Future for DeployDryRun<T> {
+	type Item = DeployPlan;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (main_gas, test_gas) = try_ready!(self.future.poll());
+		let planned = |contract, request: &TransactionRequest, estimated_gas| PlannedDeployment {
+			contract,
+			from: request.from,
+			gas: request.gas.expect("deploy requests have gas; qed"),
+			gas_price: request.gas_price.expect("deploy requests have gas price; qed"),
+			estimated_gas,
+			data: request.data.clone().expect("deploy requests have data; qed").0,
+		};
+
+		let plan = DeployPlan {
+			authorities: self.app.config.authorities.accounts.clone(),
+			required_signatures: self.app.config.authorities.required_signatures,
+			home: planned("HomeBridge", &self.main_tx_request, main_gas),
+			foreign: planned("ForeignBridge", &self.test_tx_request, test_gas),
+		};
+
+		Ok(plan.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::PlannedDeployment;
+
+	#[test]
+	fn test_planned_deployment_display() {
+		let mut planned = PlannedDeployment {
+			contract: "HomeBridge",
+			from: "0000000000000000000000000000000000000001".into(),
+			gas: 1000.into(),
+			gas_price: 20.into(),
+			estimated_gas: 900.into(),
+			data: vec![0x60, 0x60],
+		};
+		let expected = "HomeBridge:
+from = 0x0000000000000000000000000000000000000001
+gas = 1000
+gas_price = 20
+estimated_gas = 900
+data = 0x6060";
+		assert_eq!(expected, planned.to_string());
+
+		planned.estimated_gas = 1100.into();
+		assert!(planned.to_string().contains("estimated_gas = 1100 (exceeds configured gas)"));
+	}
+}
@@ -1,70 +1,273 @@
 use std::sync::Arc;
 use futures::{Future, Stream, Poll};
-use futures::future::{JoinAll, join_all};
-use tokio_timer::Timeout;
+use futures::future::{self, Either, FutureResult, JoinAll, Join, join_all};
 use web3::Transport;
-use web3::types::{TransactionRequest, H256, Address, Bytes, Log, FilterBuilder};
-use ethabi::RawLog;
-use api::{LogStream, self, ApiCall};
+use tokio_timer::Sleep;
+use web3::types::{TransactionRequest, Address, Bytes, Log, FilterBuilder, H256, U256};
+use ethabi::{self, RawLog};
+use api::{LogStream, LogStreamEvent, self};
 use error::{Error, Result};
 use database::Database;
-use contracts::{home, foreign};
+use contracts::{home, foreign, storage};
 use util::web3_filter;
 use app::App;
+use journal::{JournalEntry, Relay, RelayKind, RelayStatus};
+use metrics;
+use pubsub;
+use nonce;
+use preflight::{self, Processed, Simulate, DiagnoseDeposits, FetchTokenReserve, Revert};
+use transaction::{Outcome, PendingTransactionInit, pending_transaction};
+use super::relay::{SendRelay, ConfirmRelay, send_relay, confirm_relay};
 
 fn deposits_filter(home: &home::HomeBridge, address: Address) -> FilterBuilder {
 	let filter = home.events().deposit().create_filter();
 	web3_filter(filter, address)
 }
 
-fn deposit_relay_payload(home: &home::HomeBridge, foreign: &foreign::ForeignBridge, log: Log) -> Result<Bytes> {
+struct DepositPayload {
+	/// Payload of the relay transaction.
+	relay: Bytes,
+	/// Storage position of the flag set once the authority has relayed the deposit.
+	signed: U256,
+	value: ethabi::Uint,
+}
+
+fn deposit_relay_payload(home: &home::HomeBridge, foreign: &foreign::ForeignBridge, authority: Address, log: Log) -> Result<DepositPayload> {
 	let raw_log = RawLog {
 		topics: log.topics,
 		data: log.data.0,
 	};
 	let deposit_log = home.events().deposit().parse_log(raw_log)?;
 	let hash = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
-	let payload = foreign.functions().deposit().input(deposit_log.recipient, deposit_log.value, hash.0);
-	Ok(payload.into())
+	let relay = foreign.functions().deposit().input(deposit_log.recipient, deposit_log.value, hash.0);
+	let signed = storage::foreign_deposit_signed(authority, deposit_log.recipient, deposit_log.value, hash);
+	Ok(DepositPayload {
+		relay: relay.into(),
+		signed,
+		value: deposit_log.value,
+	})
+}
+
+/// Deposit which is about to be relayed.
+struct Deposit {
+	request: TransactionRequest,
+	/// Storage position of the flag set once the authority has relayed the deposit.
+	signed: U256,
+	/// Hash and block number of the deposit transaction.
+	source: (H256, u64),
+	value: ethabi::Uint,
+	/// Hash of the relay transaction once it has been sent.
+	sent: Option<H256>,
+	/// Nonce of the relay transaction once it has been sent, unknown for relays sent before restart.
+	nonce: Option<U256>,
+	/// Whether the relay has been sent before restart.
+	restored: bool,
+}
+
+impl Deposit {
+	fn relay(&self, destination: H256, status: RelayStatus) -> Relay {
+		Relay {
+			kind: RelayKind::DepositRelay,
+			source: self.source.0,
+			destination,
+			status,
+			block: self.source.1,
+		}
+	}
 }
 
 /// State of deposits relay.
-enum DepositRelayState<T: Transport> {
+enum DepositRelayState<T: Transport + Clone> {
 	/// Deposit relay is waiting for logs.
 	Wait,
-	/// Relaying deposits in progress.
+	/// Checking whether deposits which haven't been sent yet have already been relayed
+	/// by the authority and simulating their relay transactions.
+	SimulateDeposits {
+		future: JoinAll<Vec<Either<Join<Processed<T>, Simulate<T>>, FutureResult<(bool, Option<String>), Error>>>>,
+		deposits: Vec<Deposit>,
+		block: u64,
+	},
+	/// Checking whether `ForeignBridge` owns enough tokens to release deposits which haven't been sent yet.
+	CheckReserve {
+		future: FetchTokenReserve<T>,
+		deposits: Vec<Deposit>,
+		/// Deposits whose relay would revert.
+		reverted: Vec<Deposit>,
+		block: u64,
+	},
+	/// Deposits are queued until `ForeignBridge` owns enough tokens to release them
+	/// or, if their relay failed to be signed or sent, until the next poll.
+	WaitForReserve {
+		future: Sleep,
+		deposits: Vec<Deposit>,
+		block: u64,
+	},
+	/// Finding out why relay transactions of deposits would revert, so they can be reported and skipped.
+	/// Deposits which would revert because of the token reserve are queued, the others are skipped.
+	DiagnoseDeposits {
+		future: DiagnoseDeposits<T>,
+		/// Deposits whose relay would revert.
+		reverted: Vec<Deposit>,
+		/// Deposits which can be relayed.
+		deposits: Vec<Deposit>,
+		/// Deposits queued until the token reserve covers them.
+		queued: Vec<Deposit>,
+		block: u64,
+	},
+	/// Relaying deposits in progress. Deposits sent before restart resolve to the journaled hash.
 	RelayDeposits {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
+		future: JoinAll<Vec<Either<SendRelay<T>, FutureResult<Option<(H256, Option<U256>)>, Error>>>>,
+		deposits: Vec<Deposit>,
+		/// Deposits queued until the token reserve covers them.
+		queued: Vec<Deposit>,
+		block: u64,
+	},
+	/// Waiting for relay transactions to be mined and confirmed.
+	/// Deposits whose relay failed to be sent are queued and relayed again after the poll interval.
+	/// Failed relays are skipped and dropped ones are relayed again,
+	/// as are failed relays sent before restart.
+	ConfirmDeposits {
+		future: JoinAll<Vec<ConfirmRelay<T>>>,
+		deposits: Vec<Deposit>,
+		/// Deposits queued until the token reserve covers them.
+		queued: Vec<Deposit>,
 		block: u64,
 	},
 	/// All deposits till given block has been relayed.
 	Yield(Option<u64>),
 }
 
+impl<T: Transport + Clone> DepositRelayState<T> {
+	/// Name of the state reported by the status api.
+	fn name(&self) -> &'static str {
+		match *self {
+			DepositRelayState::Wait => "wait",
+			DepositRelayState::SimulateDeposits { .. } => "simulate_deposits",
+			DepositRelayState::CheckReserve { .. } => "check_reserve",
+			DepositRelayState::WaitForReserve { .. } => "wait_for_reserve",
+			DepositRelayState::DiagnoseDeposits { .. } => "diagnose_deposits",
+			DepositRelayState::RelayDeposits { .. } => "relay_deposits",
+			DepositRelayState::ConfirmDeposits { .. } => "confirm_deposits",
+			DepositRelayState::Yield(_) => "yield",
+		}
+	}
+}
+
 pub fn create_deposit_relay<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> DepositRelay<T> {
 	let logs_init = api::LogStreamInit {
 		after: init.checked_deposit_relay,
 		request_timeout: app.config.home.request_timeout,
 		poll_interval: app.config.home.poll_interval,
 		confirmations: app.config.home.required_confirmations,
+		reorg_depth: app.config.home.reorg_depth,
+		max_block_range: app.config.home.max_block_range,
 		filter: deposits_filter(&app.home_bridge, init.home_contract_address),
 	};
 	DepositRelay {
-		logs: api::log_stream(app.connections.home.clone(), app.timer.clone(), logs_init),
+		logs: api::log_stream(app.connections.home.clone(), app.timer.clone(), logs_init)
+			.with_status(RelayKind::DepositRelay, app.status.clone())
+			.with_new_heads(app.subscriptions.home.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.home.request_timeout)))
+			.with_retry(app.config.retry.clone()),
 		foreign_contract: init.foreign_contract_address,
 		state: DepositRelayState::Wait,
 		app,
 	}
 }
 
-pub struct DepositRelay<T: Transport> {
+pub struct DepositRelay<T: Transport + Clone> {
 	app: Arc<App<T>>,
 	logs: LogStream<T>,
 	state: DepositRelayState<T>,
 	foreign_contract: Address,
 }
 
-impl<T: Transport> Stream for DepositRelay<T> {
+/// Checks whether deposits which haven't been sent yet have already been relayed and simulates their relays.
+fn simulate_deposits<T: Transport + Clone>(app: &App<T>, foreign_contract: Address, deposits: Vec<Deposit>, block: u64) -> DepositRelayState<T> {
+	let simulations = deposits.iter()
+		.map(|deposit| match deposit.sent {
+			Some(_) => Either::B(future::ok((false, None))),
+			None => Either::A(preflight::processed(
+				app.connections.foreign.clone(),
+				app.timer.clone(),
+				app.config.foreign.request_timeout,
+				&app.config.retry,
+				foreign_contract,
+				deposit.signed,
+			).join(preflight::simulate(
+				app.connections.foreign.clone(),
+				app.timer.clone(),
+				app.config.foreign.request_timeout,
+				&app.config.retry,
+				&deposit.request,
+			))),
+		})
+		.collect();
+
+	DepositRelayState::SimulateDeposits {
+		future: join_all(simulations),
+		deposits,
+		block,
+	}
+}
+
+/// Sums values of deposits. `None` if the total overflows, such a total can never be covered by the token reserve.
+fn total_value<I: IntoIterator<Item = U256>>(values: I) -> Option<U256> {
+	values.into_iter().fold(Some(U256::zero()), |total, value| total.and_then(|total| total.checked_add(value)))
+}
+
+/// Number of leading deposits with `values` the token reserve covers.
+/// Deposits are released in order, so a large deposit is never starved by the following smaller ones.
+fn covered_deposits(balance: U256, values: &[U256]) -> usize {
+	let mut total = U256::zero();
+	values.iter()
+		.take_while(|value| match total.checked_add(**value) {
+			Some(sum) if sum <= balance => {
+				total = sum;
+				true
+			},
+			_ => false,
+		})
+		.count()
+}
+
+/// Sends deposits which haven't been sent before restart. Every relay is journaled as soon as it's sent.
+/// Queued deposits wait for the token reserve once the relays are confirmed.
+fn relay_deposits<T: Transport + Clone>(app: &App<T>, deposits: Vec<Deposit>, queued: Vec<Deposit>, block: u64) -> DepositRelayState<T> {
+	if deposits.is_empty() && !queued.is_empty() {
+		return DepositRelayState::WaitForReserve {
+			future: app.timer.sleep(app.config.foreign.poll_interval),
+			deposits: queued,
+			block,
+		};
+	}
+
+	let relays = deposits.iter()
+		.map(|deposit| match deposit.sent {
+			Some(hash) => {
+				info!("deposit {:?} has already been sent in {:?}, waiting for confirmation", deposit.source.0, hash);
+				Either::B(future::ok(Some((hash, None))))
+			},
+			None => Either::A(send_relay(app.journal.clone(), RelayKind::DepositRelay, deposit.source, nonce::send_transaction(
+				app.connections.foreign.clone(),
+				app.timer.clone(),
+				app.nonces.foreign.clone(),
+				app.signers.foreign.clone(),
+				deposit.request.clone(),
+				app.config.foreign.request_timeout,
+			))),
+		})
+		.collect::<Vec<_>>();
+
+	info!("relaying {} deposits", relays.len());
+	DepositRelayState::RelayDeposits {
+		future: join_all(relays),
+		deposits,
+		queued,
+		block,
+	}
+}
+
+impl<T: Transport + Clone> Stream for DepositRelay<T> {
 	type Item = u64;
 	type Error = Error;
 
@@ -72,40 +275,252 @@ impl<T: Transport> Stream for DepositRelay<T> {
 		loop {
 			let next_state = match self.state {
 				DepositRelayState::Wait => {
-					let item = try_stream!(self.logs.poll());
+					let item = match try_stream!(self.logs.poll()) {
+						LogStreamEvent::Logs(item) => item,
+						LogStreamEvent::Reorg { from, to } => {
+							warn!("home blocks {}..{} have been reorganized, deposits will be relayed again", from, to);
+							// move the checkpoint back so it's persisted before the range is rescanned
+							self.state = DepositRelayState::Yield(Some(from - 1));
+							continue;
+						},
+					};
 					info!("got {} new deposits to relay", item.logs.len());
-					let deposits = item.logs
-						.into_iter()
-						.map(|log| deposit_relay_payload(&self.app.home_bridge, &self.app.foreign_bridge, log))
-						.collect::<Result<Vec<_>>>()?
-						.into_iter()
-						.map(|payload| TransactionRequest {
-							from: self.app.config.foreign.account,
+					let app = &self.app;
+					let mut deposits = Vec::new();
+					for log in item.logs {
+						let source = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
+						let relayed = app.journal.relay(RelayKind::DepositRelay, source);
+						if let Some(Relay { status: RelayStatus::Mined, .. }) = relayed {
+							info!("deposit {:?} has already been relayed", source);
+							continue;
+						}
+
+						let block_number = log.block_number.map(|number| number.low_u64()).unwrap_or(item.to);
+						let payload = deposit_relay_payload(&app.home_bridge, &app.foreign_bridge, app.config.foreign.account, log)?;
+						let request = TransactionRequest {
+							from: app.config.foreign.account,
 							to: Some(self.foreign_contract.clone()),
-							gas: Some(self.app.config.txs.deposit_relay.gas.into()),
-							gas_price: Some(self.app.config.txs.deposit_relay.gas_price.into()),
+							gas: Some(app.gas_limits.deposit_relay()),
+							gas_price: Some(app.gas_prices.foreign.price(app.config.txs.deposit_relay.gas_price)),
 							value: None,
-							data: Some(payload),
+							data: Some(payload.relay),
 							nonce: None,
 							condition: None,
-						})
-						.map(|request| {
-							self.app.timer.timeout(
-								api::send_transaction(&self.app.connections.foreign, request),
-								self.app.config.foreign.request_timeout)
+						};
+
+						deposits.push(Deposit {
+							request,
+							signed: payload.signed,
+							source: (source, block_number),
+							value: payload.value,
+							sent: relayed.as_ref().map(|relay| relay.destination),
+							nonce: None,
+							restored: relayed.is_some(),
+						});
+					}
+
+					simulate_deposits(app, self.foreign_contract, deposits, item.to)
+				},
+				DepositRelayState::SimulateDeposits { ref mut future, ref mut deposits, block } => {
+					let results = try_ready!(future.poll());
+					let mut pending = Vec::new();
+					let mut reverted = Vec::new();
+					for (deposit, (processed, revert)) in deposits.drain(..).zip(results.into_iter()) {
+						if processed {
+							info!("deposit {:?} has already been relayed by this authority, skipping", deposit.source.0);
+							continue;
+						}
+						match revert {
+							Some(revert) => {
+								warn!("relay of deposit {:?} would revert: {}", deposit.source.0, revert);
+								reverted.push(deposit);
+							},
+							None => pending.push(deposit),
+						}
+					}
+
+					let app = &self.app;
+					if reverted.is_empty() && pending.iter().all(|deposit| deposit.sent.is_some()) {
+						relay_deposits(app, pending, Vec::new(), block)
+					} else {
+						DepositRelayState::CheckReserve {
+							future: preflight::token_reserve(
+								app.connections.foreign.clone(),
+								app.timer.clone(),
+								app.config.foreign.request_timeout,
+								&app.config.retry,
+								self.foreign_contract,
+							),
+							deposits: pending,
+							reverted,
+							block,
+						}
+					}
+				},
+				DepositRelayState::CheckReserve { ref mut future, ref mut deposits, ref mut reverted, block } => {
+					let reserve = try_ready!(future.poll());
+					// deposits whose relay would revert are diagnosed separately, they don't hold back the others
+					let (mut deposits, mut unsent): (Vec<_>, Vec<_>) = deposits.drain(..).partition(|deposit| deposit.sent.is_some());
+					let values = unsent.iter().map(|deposit| deposit.value).collect::<Vec<_>>();
+					let covered = if reserve.token.is_zero() { 0 } else { covered_deposits(reserve.balance, &values) };
+					let queued = unsent.split_off(covered);
+					deposits.extend(unsent);
+
+					metrics::token_reserve(reserve.balance, queued.len());
+					if reserve.token.is_zero() && !queued.is_empty() {
+						error!("token address of ForeignBridge is not set, {} deposits are queued until authorities vote for the token with `bridge set-token-address`", queued.len());
+					} else if !queued.is_empty() {
+						let required = total_value(values.iter().cloned()).map_or_else(|| "more than 2^256 - 1".to_owned(), |required| required.to_string());
+						error!(
+							"ForeignBridge owns {} tokens of {:?} but {} are required to relay {} deposits, {} deposits are queued until the reserve is topped up",
+							reserve.balance, reserve.token, required, values.len(), queued.len()
+						);
+					}
+
+					let app = &self.app;
+					if reverted.is_empty() {
+						relay_deposits(app, deposits, queued, block)
+					} else {
+						let values = reverted.iter().map(|deposit| deposit.value).collect();
+						DepositRelayState::DiagnoseDeposits {
+							future: preflight::diagnose_deposits(
+								app.connections.foreign.clone(),
+								app.timer.clone(),
+								app.config.foreign.request_timeout,
+								&app.config.retry,
+								self.foreign_contract,
+								app.config.foreign.account,
+								values,
+							),
+							reverted: reverted.drain(..).collect(),
+							deposits,
+							queued,
+							block,
+						}
+					}
+				},
+				DepositRelayState::WaitForReserve { ref mut future, ref mut deposits, block } => {
+					try_ready!(future.poll());
+					// the reserve changed, deposits may have been relayed by this authority or revert for other reasons meanwhile
+					let deposits = deposits.drain(..).collect();
+					simulate_deposits(&self.app, self.foreign_contract, deposits, block)
+				},
+				DepositRelayState::DiagnoseDeposits { ref mut future, ref mut reverted, ref mut deposits, ref mut queued, block } => {
+					let reverts = try_ready!(future.poll());
+					let mut queued = queued.drain(..).collect::<Vec<_>>();
+					let mut skipped = 0;
+					for (deposit, revert) in reverted.drain(..).zip(reverts.into_iter()) {
+						match revert {
+							Revert::TokenNotSet | Revert::InsufficientTokenBalance => {
+								warn!("relay of deposit {:?} would revert: {}, queueing it until the reserve is topped up", deposit.source.0, revert);
+								queued.push(deposit);
+							},
+							// skipped, so a single deposit can't stop the bridge
+							Revert::NotAuthority | Revert::Unknown => {
+								error!("relay of deposit {:?} would revert: {}, skipping", deposit.source.0, revert);
+								self.app.status.record_error(format!("relay of deposit {:?} would revert: {}", deposit.source.0, revert));
+								skipped += 1;
+							},
+						}
+					}
+					metrics::skipped(RelayKind::DepositRelay, skipped);
+					relay_deposits(&self.app, deposits.drain(..).collect(), queued, block)
+				},
+				DepositRelayState::RelayDeposits { ref mut future, ref mut deposits, ref mut queued, block } => {
+					let hashes = try_ready!(future.poll());
+					let mut queued = queued.drain(..).collect::<Vec<_>>();
+					let mut sent = Vec::new();
+					for (mut deposit, hash) in deposits.drain(..).zip(hashes.into_iter()) {
+						match hash {
+							Some((hash, nonce)) => {
+								deposit.sent = Some(hash);
+								deposit.nonce = nonce;
+								sent.push(deposit);
+							},
+							// a single relay which can't be sent doesn't stop the others
+							None => queued.push(deposit),
+						}
+					}
+
+					info!("waiting for {} deposit relays to be confirmed", sent.len());
+					let app = &self.app;
+					let pending = sent.iter()
+						.map(|deposit| {
+							let hash = deposit.sent.expect("only sent deposits are confirmed; qed");
+							confirm_relay(app.journal.clone(), deposit.relay(hash, RelayStatus::Sent), pending_transaction(app.connections.foreign.clone(), app.timer.clone(), PendingTransactionInit {
+								hash,
+								request: deposit.request.clone(),
+								request_timeout: app.config.foreign.request_timeout,
+								poll_interval: app.config.foreign.poll_interval,
+								confirmations: app.config.foreign.required_confirmations,
+								resubmission: app.config.txs.resubmission.clone(),
+								signer: app.signers.foreign.clone(),
+								retry: app.config.retry.clone(),
+							}))
 						})
 						.collect::<Vec<_>>();
 
-					info!("relaying {} deposits", deposits.len());
-					DepositRelayState::RelayDeposits {
-						future: join_all(deposits),
-						block: item.to,
+					DepositRelayState::ConfirmDeposits {
+						future: join_all(pending),
+						deposits: sent,
+						queued,
+						block,
 					}
 				},
-				DepositRelayState::RelayDeposits { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					info!("deposit relay completed");
-					DepositRelayState::Yield(Some(block))
+				DepositRelayState::ConfirmDeposits { ref mut future, ref mut deposits, ref mut queued, block } => {
+					let outcomes = try_ready!(future.poll());
+					let app = &self.app;
+					let mut mined = Vec::new();
+					let mut unsent = Vec::new();
+					for (deposit, outcome) in deposits.drain(..).zip(outcomes.into_iter()) {
+						match outcome {
+							Outcome::Mined(receipt) => mined.push(JournalEntry::Relay(deposit.relay(receipt.transaction_hash, RelayStatus::Mined))),
+							// transaction sent before restart may have failed for reasons which no longer hold
+							Outcome::Failed(hash) if deposit.restored => {
+								warn!("relay of deposit {:?} sent before restart in transaction {:?} failed, checking it again", deposit.source.0, hash);
+								unsent.push(Deposit {
+									sent: None,
+									restored: false,
+									..deposit
+								});
+							},
+							Outcome::Failed(hash) => {
+								error!("relay of deposit {:?} in transaction {:?} failed, skipping", deposit.source.0, hash);
+								app.status.record_error(format!("relay of deposit {:?} in transaction {:?} failed", deposit.source.0, hash));
+								metrics::skipped(RelayKind::DepositRelay, 1);
+							},
+							Outcome::Dropped(hash) => {
+								warn!("relay of deposit {:?} in transaction {:?} has been dropped, relaying it again", deposit.source.0, hash);
+								// nonce of a dropped transaction is free again
+								if let Some(nonce) = deposit.nonce {
+									app.nonces.foreign.release(nonce);
+								}
+								unsent.push(Deposit {
+									sent: None,
+									nonce: None,
+									restored: false,
+									..deposit
+								});
+							},
+						}
+					}
+
+					let relays_count = mined.len();
+					app.journal.record(mined)?;
+					metrics::relayed(RelayKind::DepositRelay, relays_count);
+					if !unsent.is_empty() {
+						unsent.extend(queued.drain(..));
+						simulate_deposits(app, self.foreign_contract, unsent, block)
+					} else if !queued.is_empty() {
+						DepositRelayState::WaitForReserve {
+							future: app.timer.sleep(app.config.foreign.poll_interval),
+							deposits: queued.drain(..).collect(),
+							block,
+						}
+					} else {
+						info!("deposit relay completed");
+						DepositRelayState::Yield(Some(block))
+					}
 				},
 				DepositRelayState::Yield(ref mut block) => match block.take() {
 					None => DepositRelayState::Wait,
@@ -113,6 +528,7 @@ impl<T: Transport> Stream for DepositRelay<T> {
 				}
 			};
 			self.state = next_state;
+			self.app.status.stream(RelayKind::DepositRelay, self.state.name());
 		}
 	}
 }
@@ -120,9 +536,9 @@ impl<T: Transport> Stream for DepositRelay<T> {
 #[cfg(test)]
 mod tests {
 	use rustc_hex::FromHex;
-	use web3::types::{Log, Bytes};
+	use web3::types::{Log, Bytes, U256};
 	use contracts::{home, foreign};
-	use super::deposit_relay_payload;
+	use super::{deposit_relay_payload, total_value, covered_deposits};
 
 	#[test]
 	fn test_deposit_relay_payload() {
@@ -137,8 +553,25 @@ mod tests {
 			..Default::default()
 		};
 
-		let payload = deposit_relay_payload(&home, &foreign, log).unwrap();
+		let payload = deposit_relay_payload(&home, &foreign, 1.into(), log).unwrap();
 		let expected: Bytes = "26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".from_hex().unwrap().into();
-		assert_eq!(expected, payload);
+		assert_eq!(expected, payload.relay);
+		assert_eq!(0xf0u64, payload.value.low_u64());
+	}
+	#[test]
+	fn test_total_value() {
+		assert_eq!(Some(U256::zero()), total_value(vec![]));
+		assert_eq!(Some(U256::from(0x30)), total_value(vec![0x10.into(), 0x20.into()]));
+		assert_eq!(Some(U256::max_value()), total_value(vec![U256::max_value() - 1, 1.into()]));
+		assert_eq!(None, total_value(vec![U256::max_value(), 1.into()]));
+	}
+
+	#[test]
+	fn test_covered_deposits() {
+		assert_eq!(0, covered_deposits(0x100.into(), &[]));
+		assert_eq!(2, covered_deposits(0x100.into(), &[0x80.into(), 0x80.into()]));
+		assert_eq!(1, covered_deposits(0x100.into(), &[0xf0.into(), 0xf0.into()]));
+		assert_eq!(0, covered_deposits(0x100.into(), &[0x101.into(), 0x10.into()]));
+		assert_eq!(1, covered_deposits(U256::max_value(), &[U256::max_value(), 1.into()]));
 	}
 }
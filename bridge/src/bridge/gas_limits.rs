@@ -0,0 +1,284 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::{Future, Stream, Poll};
+use futures::future::{Join, Join3, JoinAll, join_all};
+use ethabi;
+use web3::Transport;
+use web3::types::{Address, BlockNumber, Bytes, FilterBuilder, Log, U256};
+use api::{self, ApiCall, LogStream, LogStreamEvent, RetryCall};
+use app::App;
+use contracts::{home, foreign};
+use database::Database;
+use error::Error;
+use pubsub;
+use util::web3_filter;
+
+fn home_gas_limits_filter(home: &home::HomeBridge, address: Address) -> FilterBuilder {
+	let filter = home.events().gas_consumption_limits_updated().create_filter();
+	web3_filter(filter, address)
+}
+
+fn foreign_gas_limits_filter(foreign: &foreign::ForeignBridge, address: Address) -> FilterBuilder {
+	let filter = foreign.events().gas_consumption_limits_updated().create_filter();
+	web3_filter(filter, address)
+}
+
+/// Decodes limits of `GasConsumptionLimitsUpdated` event with `count` limits.
+fn gas_limits_updated(log: &Log, count: usize) -> Result<Vec<U256>, Error> {
+	let types = vec![ethabi::ParamType::Uint(256); count];
+	ethabi::decode(&types, &log.data.0)?
+		.into_iter()
+		.map(|token| token.to_uint().ok_or_else(|| "Invalid GasConsumptionLimitsUpdated event".into()))
+		.collect()
+}
+
+/// Numbers of blocks at which gas limits have been loaded from the contracts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GasLimitsLoaded {
+	pub home: u64,
+	pub foreign: u64,
+}
+
+fn retry_call<T: Transport + Clone, R>(app: &App<T>, transport: &T, request_timeout: Duration, call: ApiCall<R, T::Out>) -> RetryCall<T, R> {
+	api::retry_call(transport.clone(), app.timer.clone(), request_timeout, &app.config.retry, call)
+}
+
+/// Creates new `LoadGasLimits` of contracts in `init`.
+pub fn create_load_gas_limits<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> LoadGasLimits<T> {
+	let home = &app.connections.home;
+	let foreign = &app.connections.foreign;
+	let home_timeout = app.config.home.request_timeout;
+	let foreign_timeout = app.config.foreign.request_timeout;
+	let withdraw_relay = app.home_bridge.functions().gas_limit_withdraw_relay().input();
+	let deposit_relay = app.foreign_bridge.functions().gas_limit_deposit_relay().input();
+	let withdraw_confirm = app.foreign_bridge.functions().gas_limit_withdraw_confirm().input();
+
+	// block numbers are fetched first, so updates following the loaded limits are never older than them
+	let home_future = retry_call(&app, home, home_timeout, api::block_number(home)).join(
+		retry_call(&app, home, home_timeout, api::call(home, init.home_contract_address, withdraw_relay.into()))
+	);
+	let foreign_future = retry_call(&app, foreign, foreign_timeout, api::block_number(foreign)).join3(
+		retry_call(&app, foreign, foreign_timeout, api::call(foreign, init.foreign_contract_address, deposit_relay.into())),
+		retry_call(&app, foreign, foreign_timeout, api::call(foreign, init.foreign_contract_address, withdraw_confirm.into())),
+	);
+	let future = home_future.join(foreign_future);
+
+	LoadGasLimits {
+		future,
+		app,
+	}
+}
+
+/// Reads gas limits of relay transactions from `HomeBridge` and `ForeignBridge` into `App::gas_limits`.
+pub struct LoadGasLimits<T: Transport> {
+	app: Arc<App<T>>,
+	future: Join<
+		Join<RetryCall<T, U256>, RetryCall<T, Bytes>>,
+		Join3<RetryCall<T, U256>, RetryCall<T, Bytes>, RetryCall<T, Bytes>>
+	>,
+}
+
+impl<T: Transport> Future for LoadGasLimits<T> {
+	type Item = GasLimitsLoaded;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let ((home_block, withdraw_relay), (foreign_block, deposit_relay, withdraw_confirm)) = try_ready!(self.future.poll());
+		let app = &self.app;
+		app.gas_limits.set_home(app.home_bridge.functions().gas_limit_withdraw_relay().output(&withdraw_relay.0)?);
+		app.gas_limits.set_foreign(
+			app.foreign_bridge.functions().gas_limit_deposit_relay().output(&deposit_relay.0)?,
+			app.foreign_bridge.functions().gas_limit_withdraw_confirm().output(&withdraw_confirm.0)?,
+		);
+
+		let loaded = GasLimitsLoaded {
+			home: home_block.low_u64(),
+			foreign: foreign_block.low_u64(),
+		};
+		Ok(loaded.into())
+	}
+}
+
+/// Chain of the contract which emits `GasConsumptionLimitsUpdated`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chain {
+	Home,
+	Foreign,
+}
+
+impl Chain {
+	fn name(&self) -> &'static str {
+		match *self {
+			Chain::Home => "home",
+			Chain::Foreign => "foreign",
+		}
+	}
+}
+
+/// Creates new `GasLimitsUpdates` of `HomeBridge` following events after the block at which the limits have been loaded.
+pub fn create_home_gas_limits_updates<T: Transport + Clone>(app: Arc<App<T>>, init: &Database, loaded: &GasLimitsLoaded) -> GasLimitsUpdates<T> {
+	let logs_init = api::LogStreamInit {
+		after: loaded.home,
+		request_timeout: app.config.home.request_timeout,
+		poll_interval: app.config.home.poll_interval,
+		confirmations: app.config.home.required_confirmations,
+		reorg_depth: app.config.home.reorg_depth,
+		max_block_range: app.config.home.max_block_range,
+		filter: home_gas_limits_filter(&app.home_bridge, init.home_contract_address),
+	};
+
+	GasLimitsUpdates {
+		logs: api::log_stream(app.connections.home.clone(), app.timer.clone(), logs_init)
+			.with_new_heads(app.subscriptions.home.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.home.request_timeout)))
+			.with_retry(app.config.retry.clone()),
+		state: GasLimitsUpdatesState::Wait,
+		chain: Chain::Home,
+		contract: init.home_contract_address,
+		app,
+	}
+}
+
+/// Creates new `GasLimitsUpdates` of `ForeignBridge` following events after the block at which the limits have been loaded.
+pub fn create_foreign_gas_limits_updates<T: Transport + Clone>(app: Arc<App<T>>, init: &Database, loaded: &GasLimitsLoaded) -> GasLimitsUpdates<T> {
+	let logs_init = api::LogStreamInit {
+		after: loaded.foreign,
+		request_timeout: app.config.foreign.request_timeout,
+		poll_interval: app.config.foreign.poll_interval,
+		confirmations: app.config.foreign.required_confirmations,
+		reorg_depth: app.config.foreign.reorg_depth,
+		max_block_range: app.config.foreign.max_block_range,
+		filter: foreign_gas_limits_filter(&app.foreign_bridge, init.foreign_contract_address),
+	};
+
+	GasLimitsUpdates {
+		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init)
+			.with_new_heads(app.subscriptions.foreign.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.foreign.request_timeout)))
+			.with_retry(app.config.retry.clone()),
+		state: GasLimitsUpdatesState::Wait,
+		chain: Chain::Foreign,
+		contract: init.foreign_contract_address,
+		app,
+	}
+}
+
+/// Sets limits of `chain` read from the contract.
+fn set_limits<T: Transport>(app: &App<T>, chain: Chain, outputs: &[Bytes]) -> Result<(), Error> {
+	match chain {
+		Chain::Home => {
+			app.gas_limits.set_home(app.home_bridge.functions().gas_limit_withdraw_relay().output(&outputs[0].0)?);
+		},
+		Chain::Foreign => {
+			app.gas_limits.set_foreign(
+				app.foreign_bridge.functions().gas_limit_deposit_relay().output(&outputs[0].0)?,
+				app.foreign_bridge.functions().gas_limit_withdraw_confirm().output(&outputs[1].0)?,
+			);
+		},
+	}
+	Ok(())
+}
+
+enum GasLimitsUpdatesState<T: Transport> {
+	/// Waiting for the next range of logs.
+	Wait,
+	/// Reloading the limits from the contract after a reorg.
+	Reload(JoinAll<Vec<RetryCall<T, Bytes>>>),
+}
+
+/// Follows `GasConsumptionLimitsUpdated` events of a bridge contract and updates `App::gas_limits`.
+///
+/// Yields the number of the last block checked for updates.
+pub struct GasLimitsUpdates<T: Transport> {
+	app: Arc<App<T>>,
+	logs: LogStream<T>,
+	state: GasLimitsUpdatesState<T>,
+	chain: Chain,
+	contract: Address,
+}
+
+impl<T: Transport + Clone> GasLimitsUpdates<T> {
+	/// Reads the limits from the contract in the state of given block.
+	fn reload(&self, block: u64) -> GasLimitsUpdatesState<T> {
+		let app = &self.app;
+		let (transport, request_timeout, payloads) = match self.chain {
+			Chain::Home => (&app.connections.home, app.config.home.request_timeout, vec![
+				app.home_bridge.functions().gas_limit_withdraw_relay().input(),
+			]),
+			Chain::Foreign => (&app.connections.foreign, app.config.foreign.request_timeout, vec![
+				app.foreign_bridge.functions().gas_limit_deposit_relay().input(),
+				app.foreign_bridge.functions().gas_limit_withdraw_confirm().input(),
+			]),
+		};
+
+		let calls = payloads.into_iter()
+			.map(|payload| {
+				let call = api::call_at(transport, self.contract, payload.into(), BlockNumber::Number(block));
+				retry_call(app, transport, request_timeout, call)
+			})
+			.collect();
+		GasLimitsUpdatesState::Reload(join_all(calls))
+	}
+}
+
+impl<T: Transport + Clone> Stream for GasLimitsUpdates<T> {
+	type Item = u64;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				GasLimitsUpdatesState::Wait => match try_stream!(self.logs.poll()) {
+					LogStreamEvent::Logs(item) => {
+						// only the latest update of the range matters
+						if let Some(log) = item.logs.last() {
+							match self.chain {
+								Chain::Home => {
+									let limits = gas_limits_updated(log, 1)?;
+									self.app.gas_limits.set_home(limits[0]);
+								},
+								Chain::Foreign => {
+									let limits = gas_limits_updated(log, 2)?;
+									self.app.gas_limits.set_foreign(limits[0], limits[1]);
+								},
+							}
+						}
+
+						return Ok(Some(item.to).into());
+					},
+					LogStreamEvent::Reorg { from, to } => {
+						// updates from the orphaned blocks may be gone, so the limits are read from the last
+						// block before them and updates of the rescanned range are applied on top of them
+						warn!("{} blocks {}..{} have been reorganized, reloading gas limits at block {}", self.chain.name(), from, to, from - 1);
+						self.reload(from - 1)
+					},
+				},
+				GasLimitsUpdatesState::Reload(ref mut future) => {
+					let outputs = try_ready!(future.poll());
+					set_limits(&self.app, self.chain, &outputs)?;
+					GasLimitsUpdatesState::Wait
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_hex::FromHex;
+	use web3::types::Log;
+	use super::gas_limits_updated;
+
+	#[test]
+	fn test_gas_limits_updated() {
+		let data = "00000000000000000000000000000000000000000000000000000000000186a000000000000000000000000000000000000000000000000000000000000249f0".from_hex().unwrap();
+		let log = Log {
+			data: data.into(),
+			..Default::default()
+		};
+
+		let limits = gas_limits_updated(&log, 2).unwrap();
+		assert_eq!(vec![100_000.into(), 150_000.into()], limits);
+		assert!(gas_limits_updated(&Log::default(), 1).is_err());
+	}
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+use futures::{Future, Stream, Poll, Async};
+use futures::future::Join;
+use tokio_timer::Sleep;
+use web3::Transport;
+use app::App;
+use config::{GasPriceStrategy, Node};
+use error::Error;
+use gas_price::{fetch_gas_price, FetchGasPrice, GasPrice};
+
+/// Chain whose gas price is fetched.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Chain {
+	Home,
+	Foreign,
+}
+
+impl Chain {
+	fn name(&self) -> &'static str {
+		match *self {
+			Chain::Home => "home",
+			Chain::Foreign => "foreign",
+		}
+	}
+
+	fn config<'a, T: Transport>(&self, app: &'a App<T>) -> &'a Node {
+		match *self {
+			Chain::Home => &app.config.home,
+			Chain::Foreign => &app.config.foreign,
+		}
+	}
+
+	fn gas_price<'a, T: Transport>(&self, app: &'a App<T>) -> &'a GasPrice {
+		match *self {
+			Chain::Home => &app.gas_prices.home,
+			Chain::Foreign => &app.gas_prices.foreign,
+		}
+	}
+
+	fn fetch<T: Transport + Clone>(&self, app: &App<T>) -> FetchGasPrice<T> {
+		let transport = match *self {
+			Chain::Home => app.connections.home.clone(),
+			Chain::Foreign => app.connections.foreign.clone(),
+		};
+		let node = self.config(app);
+		fetch_gas_price(transport, app.timer.clone(), node.request_timeout, &app.config.retry, &node.gas_price, app.gas_prices.client())
+	}
+}
+
+/// Fetches the gas price of a chain and stores it in `App::gas_prices`.
+///
+/// Failures are only logged, transactions keep using the previous price.
+pub struct UpdateGasPrice<T: Transport> {
+	app: Arc<App<T>>,
+	chain: Chain,
+	future: FetchGasPrice<T>,
+}
+
+impl<T: Transport + Clone> UpdateGasPrice<T> {
+	fn new(app: Arc<App<T>>, chain: Chain) -> Self {
+		UpdateGasPrice {
+			future: chain.fetch(&app),
+			app,
+			chain,
+		}
+	}
+}
+
+impl<T: Transport + Clone> Future for UpdateGasPrice<T> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.future.poll() {
+			Ok(Async::NotReady) => return Ok(Async::NotReady),
+			Ok(Async::Ready(Some(price))) => {
+				let gas_price = self.chain.gas_price(&self.app);
+				gas_price.set(price);
+				info!("{} gas price is {}", self.chain.name(), gas_price.config().clamp(price));
+			},
+			Ok(Async::Ready(None)) => {},
+			Err(err) => warn!("cannot fetch {} gas price, previous price is used: {}", self.chain.name(), err),
+		}
+
+		Ok(Async::Ready(()))
+	}
+}
+
+/// Creates new `LoadGasPrices` fetching the gas prices before the first transaction is sent.
+pub fn create_load_gas_prices<T: Transport + Clone>(app: Arc<App<T>>) -> LoadGasPrices<T> {
+	LoadGasPrices {
+		future: UpdateGasPrice::new(app.clone(), Chain::Home).join(UpdateGasPrice::new(app, Chain::Foreign)),
+	}
+}
+
+/// Fetches gas prices of home and foreign.
+pub struct LoadGasPrices<T: Transport> {
+	future: Join<UpdateGasPrice<T>, UpdateGasPrice<T>>,
+}
+
+impl<T: Transport + Clone> Future for LoadGasPrices<T> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		try_ready!(self.future.poll());
+		Ok(Async::Ready(()))
+	}
+}
+
+/// Creates new `GasPriceUpdates` of home, following prices loaded by `LoadGasPrices`.
+pub fn create_home_gas_price_updates<T: Transport + Clone>(app: Arc<App<T>>) -> GasPriceUpdates<T> {
+	GasPriceUpdates::new(app, Chain::Home)
+}
+
+/// Creates new `GasPriceUpdates` of foreign, following prices loaded by `LoadGasPrices`.
+pub fn create_foreign_gas_price_updates<T: Transport + Clone>(app: Arc<App<T>>) -> GasPriceUpdates<T> {
+	GasPriceUpdates::new(app, Chain::Foreign)
+}
+
+enum GasPriceUpdatesState<T: Transport> {
+	/// The strategy has no updates.
+	Fixed,
+	Wait(Sleep),
+	Update(UpdateGasPrice<T>),
+}
+
+/// Fetches the gas price of a chain every `gas_price.update_interval`.
+pub struct GasPriceUpdates<T: Transport> {
+	app: Arc<App<T>>,
+	chain: Chain,
+	state: GasPriceUpdatesState<T>,
+}
+
+impl<T: Transport + Clone> GasPriceUpdates<T> {
+	fn new(app: Arc<App<T>>, chain: Chain) -> Self {
+		let state = match chain.config(&app).gas_price.strategy {
+			GasPriceStrategy::Fixed => GasPriceUpdatesState::Fixed,
+			_ => GasPriceUpdatesState::Wait(app.timer.sleep(chain.config(&app).gas_price.update_interval)),
+		};
+
+		GasPriceUpdates {
+			app,
+			chain,
+			state,
+		}
+	}
+}
+
+impl<T: Transport + Clone> Stream for GasPriceUpdates<T> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				GasPriceUpdatesState::Fixed => return Ok(Async::NotReady),
+				GasPriceUpdatesState::Wait(ref mut sleep) => {
+					try_ready!(sleep.poll());
+					GasPriceUpdatesState::Update(UpdateGasPrice::new(self.app.clone(), self.chain))
+				},
+				GasPriceUpdatesState::Update(ref mut future) => {
+					try_ready!(future.poll());
+					let interval = self.chain.config(&self.app).gas_price.update_interval;
+					self.state = GasPriceUpdatesState::Wait(self.app.timer.sleep(interval));
+					return Ok(Async::Ready(Some(())));
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
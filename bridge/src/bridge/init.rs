@@ -0,0 +1,157 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use futures::future::{Join4, JoinAll, join_all};
+use tokio_timer::Timer;
+use web3::Transport;
+use web3::types::{Address, Bytes, H256, U256};
+use api::{self, RetryCall};
+use app::App;
+use config::Retry;
+use contracts::storage;
+use database::Database;
+use error::{Error, ResultExt};
+
+enum HomeAuthoritiesState<T: Transport> {
+	/// Reading the length of the array.
+	Length(RetryCall<T, H256>),
+	/// Reading the elements of the array.
+	Elements(JoinAll<Vec<RetryCall<T, H256>>>),
+}
+
+/// Reads the accounts of the `authorities` array of `HomeBridge` from the storage of the contract.
+struct HomeAuthorities<T: Transport> {
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	retry: Retry,
+	contract: Address,
+	state: HomeAuthoritiesState<T>,
+}
+
+impl<T: Transport + Clone> HomeAuthorities<T> {
+	fn new(transport: T, timer: Timer, request_timeout: Duration, retry: &Retry, contract: Address) -> Self {
+		let length = api::storage(&transport, contract, storage::home_authorities_length());
+		HomeAuthorities {
+			state: HomeAuthoritiesState::Length(api::retry_call(transport.clone(), timer.clone(), request_timeout, retry, length)),
+			transport,
+			timer,
+			request_timeout,
+			retry: retry.clone(),
+			contract,
+		}
+	}
+}
+
+impl<T: Transport + Clone> Future for HomeAuthorities<T> {
+	type Item = Vec<Address>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				HomeAuthoritiesState::Length(ref mut future) => {
+					let length = try_ready!(future.poll());
+					let elements = (0..U256::from(&length.0[..]).low_u64() as usize)
+						.map(|index| {
+							let element = api::storage(&self.transport, self.contract, storage::home_authority(index));
+							api::retry_call(self.transport.clone(), self.timer.clone(), self.request_timeout, &self.retry, element)
+						})
+						.collect();
+					HomeAuthoritiesState::Elements(join_all(elements))
+				},
+				HomeAuthoritiesState::Elements(ref mut future) => {
+					let elements = try_ready!(future.poll());
+					// addresses take the lower 20 bytes of their slots
+					let authorities = elements.into_iter().map(|element| Address::from(&element.0[12..])).collect();
+					return Ok(Async::Ready(authorities));
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+/// Creates new `Init` of the bridge deployed at `home_contract` and `foreign_contract`.
+pub fn create_init<T: Transport + Clone>(app: Arc<App<T>>, home_contract: Address, foreign_contract: Address) -> Init<T> {
+	let future = {
+		let home = &app.connections.home;
+		let foreign = &app.connections.foreign;
+		let retry_home = |payload: Vec<u8>| {
+			let call = api::call(home, home_contract, payload.into());
+			api::retry_call(home.clone(), app.timer.clone(), app.config.home.request_timeout, &app.config.retry, call)
+		};
+		let retry_foreign = |payload: Vec<u8>| {
+			let call = api::call(foreign, foreign_contract, payload.into());
+			api::retry_call(foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, call)
+		};
+
+		// the contracts have no getters telling whether an account is an authority, the authorities are read from their storage
+		let home_authorities = HomeAuthorities::new(home.clone(), app.timer.clone(), app.config.home.request_timeout, &app.config.retry, home_contract);
+		let foreign_authority = api::storage(foreign, foreign_contract, storage::foreign_authority(app.config.foreign.account));
+		let foreign_authority = api::retry_call(foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, foreign_authority);
+
+		retry_home(app.home_bridge.functions().deployed_at_block().input()).join4(
+			home_authorities,
+			retry_foreign(app.foreign_bridge.functions().deployed_at_block().input()),
+			foreign_authority,
+		)
+	};
+
+	Init {
+		app,
+		home_contract,
+		foreign_contract,
+		future,
+	}
+}
+
+/// Creates a database of already deployed contracts, so authorities joining the bridge
+/// start relaying from the blocks at which the contracts have been deployed.
+///
+/// Fails if the account of a chain is not an authority of its contract.
+pub struct Init<T: Transport> {
+	app: Arc<App<T>>,
+	home_contract: Address,
+	foreign_contract: Address,
+	future: Join4<RetryCall<T, Bytes>, HomeAuthorities<T>, RetryCall<T, Bytes>, RetryCall<T, H256>>,
+}
+
+impl<T: Transport + Clone> Future for Init<T> {
+	type Item = Database;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (home_deploy, home_authorities, foreign_deploy, foreign_authority) = try_ready!(self.future.poll());
+		let home = self.app.home_bridge.functions();
+		let foreign = self.app.foreign_bridge.functions();
+
+		// calls to addresses without code return no data
+		let home_deploy = home.deployed_at_block().output(&home_deploy.0)
+			.chain_err(|| format!("No HomeBridge at {:?}", self.home_contract))?;
+		let foreign_deploy = foreign.deployed_at_block().output(&foreign_deploy.0)
+			.chain_err(|| format!("No ForeignBridge at {:?}", self.foreign_contract))?;
+
+		if !home_authorities.contains(&self.app.config.home.account) {
+			return Err(format!("{:?} is not an authority of HomeBridge at {:?}", self.app.config.home.account, self.home_contract).into());
+		}
+		if foreign_authority.is_zero() {
+			return Err(format!("{:?} is not an authority of ForeignBridge at {:?}", self.app.config.foreign.account, self.foreign_contract).into());
+		}
+
+		let home_deploy = home_deploy.low_u64();
+		let foreign_deploy = foreign_deploy.low_u64();
+		let database = Database {
+			home_contract_address: self.home_contract,
+			foreign_contract_address: self.foreign_contract,
+			home_deploy,
+			foreign_deploy,
+			checked_deposit_relay: home_deploy,
+			checked_withdraw_relay: foreign_deploy,
+			checked_withdraw_confirm: foreign_deploy,
+		};
+
+		Ok(database.into())
+	}
+}
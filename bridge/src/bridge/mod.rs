@@ -1,19 +1,33 @@
 mod deploy;
 mod deposit_relay;
+mod gas_limits;
+mod gas_price;
+mod init;
+mod nonce;
+mod relay;
+mod token_address;
+mod verify;
 mod withdraw_confirm;
 mod withdraw_relay;
 
-use std::fs;
 use std::sync::Arc;
-use std::path::PathBuf;
 use futures::{Stream, Poll, Async};
 use web3::Transport;
 use app::App;
 use database::Database;
 use error::{Error, ErrorKind, Result};
-
-pub use self::deploy::{Deploy, Deployed, create_deploy};
+use journal::{Journal, JournalEntry, RelayKind};
+use metrics;
+use status::Status;
+
+pub use self::deploy::{Deploy, DeployDryRun, DeployPlan, PlannedDeployment, create_deploy, create_deploy_dry_run};
+pub use self::init::{Init, create_init};
+pub use self::nonce::{SyncNonces, create_sync_nonces};
+pub use self::verify::{Verify, create_verify};
 pub use self::deposit_relay::{DepositRelay, create_deposit_relay};
+pub use self::gas_price::{LoadGasPrices, GasPriceUpdates, create_load_gas_prices, create_home_gas_price_updates, create_foreign_gas_price_updates};
+pub use self::gas_limits::{GasLimitsLoaded, LoadGasLimits, GasLimitsUpdates, create_load_gas_limits, create_home_gas_limits_updates, create_foreign_gas_limits_updates};
+pub use self::token_address::{TokenAddressVotes, LoadTokenAddressVotes, SetTokenAddress, TokenAddressEvents, create_load_token_address_votes, create_set_token_address, create_token_address_events};
 pub use self::withdraw_relay::{WithdrawRelay, create_withdraw_relay};
 pub use self::withdraw_confirm::{WithdrawConfirm, create_withdraw_confirm};
 
@@ -25,37 +39,37 @@ pub enum BridgeChecked {
 	WithdrawConfirm(u64),
 }
 
+impl BridgeChecked {
+	fn kind(&self) -> RelayKind {
+		match *self {
+			BridgeChecked::DepositRelay(_) => RelayKind::DepositRelay,
+			BridgeChecked::WithdrawRelay(_) => RelayKind::WithdrawRelay,
+			BridgeChecked::WithdrawConfirm(_) => RelayKind::WithdrawConfirm,
+		}
+	}
+
+	fn block(&self) -> u64 {
+		match *self {
+			BridgeChecked::DepositRelay(block) | BridgeChecked::WithdrawRelay(block) | BridgeChecked::WithdrawConfirm(block) => block,
+		}
+	}
+}
+
 pub trait BridgeBackend {
 	fn save(&mut self, checks: Vec<BridgeChecked>) -> Result<()>;
 }
 
-pub struct FileBackend {
-	path: PathBuf,
-	database: Database,
+/// Backend writing checkpoints to the journal of relays.
+pub struct JournalBackend {
+	journal: Arc<Journal>,
 }
 
-impl BridgeBackend for FileBackend {
+impl BridgeBackend for JournalBackend {
 	fn save(&mut self, checks: Vec<BridgeChecked>) -> Result<()> {
-		for check in checks {
-			match check {
-				BridgeChecked::DepositRelay(n) => {
-					self.database.checked_deposit_relay = n;
-				},
-				BridgeChecked::WithdrawRelay(n) => {
-					self.database.checked_withdraw_relay = n;
-				},
-				BridgeChecked::WithdrawConfirm(n) => {
-					self.database.checked_withdraw_confirm = n;
-				},
-			}
-		}
-
-		let file = fs::OpenOptions::new()
-			.write(true)
-			.create(true)
-			.open(&self.path)?;
-
-		self.database.save(file)
+		let entries = checks.into_iter()
+			.map(|check| JournalEntry::Checked { kind: check.kind(), block: check.block() })
+			.collect();
+		self.journal.record(entries)
 	}
 }
 
@@ -64,54 +78,65 @@ enum BridgeStatus {
 	NextItem(Option<()>),
 }
 
-//pub fn create_arc<T: Transport>(app: A) -> Arc<App<T>> {
-//	Arc::new(app)
-//}
-
-/// Creates new bridge.
-pub fn create_bridge<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> Bridge<T, FileBackend> {
-	let backend = FileBackend {
-		path: app.database_path.clone(),
-		database: init.clone(),
+/// Creates new bridge writing checkpoints to the journal.
+///
+/// `init` should have checkpoints restored from the journal with `Journal::restore`
+/// and `gas_limits` should come from `create_load_gas_limits`.
+pub fn create_bridge<T: Transport + Clone>(app: Arc<App<T>>, init: &Database, gas_limits: &GasLimitsLoaded) -> Bridge<T, JournalBackend> {
+	let backend = JournalBackend {
+		journal: app.journal.clone(),
 	};
 
-	create_bridge_backed_by(app, init, backend)
+	create_bridge_backed_by(app, init, gas_limits, backend)
 }
 
 /// Creates new bridge writing to custom backend.
-pub fn create_bridge_backed_by<T: Transport + Clone, F: BridgeBackend>(app: Arc<App<T>>, init: &Database, backend: F) -> Bridge<T, F> {
+pub fn create_bridge_backed_by<T: Transport + Clone, F: BridgeBackend>(app: Arc<App<T>>, init: &Database, gas_limits: &GasLimitsLoaded, backend: F) -> Bridge<T, F> {
+	app.status.database(init);
 	Bridge {
 		deposit_relay: create_deposit_relay(app.clone(), init),
 		withdraw_relay: create_withdraw_relay(app.clone(), init),
 		withdraw_confirm: create_withdraw_confirm(app.clone(), init),
+		home_gas_limits: create_home_gas_limits_updates(app.clone(), init, gas_limits),
+		foreign_gas_limits: create_foreign_gas_limits_updates(app.clone(), init, gas_limits),
+		home_gas_price: create_home_gas_price_updates(app.clone()),
+		foreign_gas_price: create_foreign_gas_price_updates(app.clone()),
 		state: BridgeStatus::Wait,
 		backend,
 		running: app.running.clone(),
+		status: app.status.clone(),
 	}
 }
 
-pub struct Bridge<T: Transport, F> {
+pub struct Bridge<T: Transport + Clone, F> {
 	deposit_relay: DepositRelay<T>,
 	withdraw_relay: WithdrawRelay<T>,
 	withdraw_confirm: WithdrawConfirm<T>,
+	home_gas_limits: GasLimitsUpdates<T>,
+	foreign_gas_limits: GasLimitsUpdates<T>,
+	home_gas_price: GasPriceUpdates<T>,
+	foreign_gas_price: GasPriceUpdates<T>,
 	state: BridgeStatus,
 	backend: F,
 	running: Arc<AtomicBool>,
+	status: Arc<Status>,
 }
 
 use std::sync::atomic::{AtomicBool, Ordering};
 
-impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
-	type Item = ();
-	type Error = Error;
-
-	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+impl<T: Transport + Clone, F: BridgeBackend> Bridge<T, F> {
+	fn poll_checks(&mut self) -> Poll<Option<()>, Error> {
 		loop {
 			let next_state = match self.state {
 				BridgeStatus::Wait => {
 					if !self.running.load(Ordering::SeqCst) {
 						return Err(ErrorKind::ShutdownRequested.into())
 					}
+					// gas limits and prices are applied to `App` before relays of the same poll are sent
+					while let Some(_) = try_bridge!(self.home_gas_limits.poll()) {}
+					while let Some(_) = try_bridge!(self.foreign_gas_limits.poll()) {}
+					while let Some(_) = try_bridge!(self.home_gas_price.poll()) {}
+					while let Some(_) = try_bridge!(self.foreign_gas_price.poll()) {}
 					let d_relay = try_bridge!(self.deposit_relay.poll()).map(BridgeChecked::DepositRelay);
 					let w_relay = try_bridge!(self.withdraw_relay.poll()).map(BridgeChecked::WithdrawRelay);
 					let w_confirm = try_bridge!(self.withdraw_confirm.poll()).map(BridgeChecked::WithdrawConfirm);
@@ -124,7 +149,13 @@ impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
 					if result.is_empty() {
 						return Ok(Async::NotReady);
 					} else {
-						self.backend.save(result)?;
+						for check in &result {
+							metrics::checked_block(check.kind(), check.block());
+						}
+						self.backend.save(result.clone())?;
+						for check in &result {
+							self.status.checked(check.kind(), check.block());
+						}
 						BridgeStatus::NextItem(Some(()))
 					}
 				},
@@ -139,33 +170,44 @@ impl<T: Transport, F: BridgeBackend> Stream for Bridge<T, F> {
 	}
 }
 
+impl<T: Transport + Clone, F: BridgeBackend> Stream for Bridge<T, F> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		let result = self.poll_checks();
+		match result {
+			Err(Error(ErrorKind::ShutdownRequested, _)) => (),
+			Err(ref err) => self.status.error(err),
+			Ok(_) => (),
+		}
+		result
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	extern crate tempdir;
 	use self::tempdir::TempDir;
-	use database::Database;
-	use super::{BridgeBackend, FileBackend, BridgeChecked};
+	use std::sync::Arc;
+	use journal::{Journal, RelayKind};
+	use super::{BridgeBackend, JournalBackend, BridgeChecked};
 
 	#[test]
-	fn test_file_backend() {
-		let tempdir = TempDir::new("test_file_backend").unwrap();
-		let mut path = tempdir.path().to_owned();
-		path.push("db");
-		let mut backend = FileBackend {
-			path: path.clone(),
-			database: Database::default(),
+	fn test_journal_backend() {
+		let tempdir = TempDir::new("test_journal_backend").unwrap();
+		let path = Journal::path(tempdir.path().join("db"));
+		let mut backend = JournalBackend {
+			journal: Arc::new(Journal::open(&path).unwrap()),
 		};
 
 		backend.save(vec![BridgeChecked::DepositRelay(1)]).unwrap();
-		assert_eq!(1, backend.database.checked_deposit_relay);
-		assert_eq!(0, backend.database.checked_withdraw_confirm);
-		assert_eq!(0, backend.database.checked_withdraw_relay);
-		backend.save(vec![BridgeChecked::DepositRelay(2), BridgeChecked::WithdrawConfirm(3), BridgeChecked::WithdrawRelay(2)]).unwrap();
-		assert_eq!(2, backend.database.checked_deposit_relay);
-		assert_eq!(3, backend.database.checked_withdraw_confirm);
-		assert_eq!(2, backend.database.checked_withdraw_relay);
-
-		let loaded = Database::load(path).unwrap();
-		assert_eq!(backend.database, loaded);
+		backend.save(vec![BridgeChecked::DepositRelay(2), BridgeChecked::WithdrawConfirm(3)]).unwrap();
+		assert_eq!(Some(2), backend.journal.checked(RelayKind::DepositRelay));
+
+		let loaded = Journal::open(&path).unwrap();
+		assert_eq!(Some(2), loaded.checked(RelayKind::DepositRelay));
+		assert_eq!(None, loaded.checked(RelayKind::WithdrawRelay));
+		assert_eq!(Some(3), loaded.checked(RelayKind::WithdrawConfirm));
 	}
 }
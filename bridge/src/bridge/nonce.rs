@@ -0,0 +1,42 @@
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::Join;
+use web3::Transport;
+use web3::types::U256;
+use api::{self, RetryCall, retry_call};
+use app::App;
+use error::Error;
+
+/// Creates new `SyncNonces` of the authority accounts on home and foreign.
+pub fn create_sync_nonces<T: Transport + Clone>(app: Arc<App<T>>) -> SyncNonces<T> {
+	let home = api::transaction_count(&app.connections.home, app.config.home.account);
+	let foreign = api::transaction_count(&app.connections.foreign, app.config.foreign.account);
+	let future = retry_call(app.connections.home.clone(), app.timer.clone(), app.config.home.request_timeout, &app.config.retry, home)
+		.join(retry_call(app.connections.foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, foreign));
+
+	SyncNonces {
+		app,
+		future,
+	}
+}
+
+/// Synchronises nonce managers with the number of transactions sent from the authority accounts,
+/// including pending ones, before the bridge starts relaying.
+pub struct SyncNonces<T: Transport> {
+	app: Arc<App<T>>,
+	future: Join<RetryCall<T, U256>, RetryCall<T, U256>>,
+}
+
+impl<T: Transport> Future for SyncNonces<T> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (home, foreign) = try_ready!(self.future.poll());
+		info!("next nonce of {:?} on home is {}", self.app.config.home.account, home);
+		self.app.nonces.home.sync(home);
+		info!("next nonce of {:?} on foreign is {}", self.app.config.foreign.account, foreign);
+		self.app.nonces.foreign.sync(foreign);
+		Ok(Async::Ready(()))
+	}
+}
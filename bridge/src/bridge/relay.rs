@@ -0,0 +1,121 @@
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use web3::Transport;
+use web3::types::{H256, U256};
+use error::Error;
+use journal::{Journal, JournalEntry, Relay, RelayKind, RelayStatus};
+use metrics;
+use nonce::SendTransaction;
+use status;
+use transaction::{Outcome, PendingTransaction};
+
+/// Creates new `Settled` signing or sending of the relay of the event emitted by the `source` transaction.
+pub fn settled<F: Future<Error = Error>>(kind: RelayKind, source: H256, future: F) -> Settled<F> {
+	Settled {
+		kind,
+		source,
+		future,
+	}
+}
+
+/// Resolves to `None` if signing or sending a single relay fails, so it doesn't stop the other relays.
+/// The error is logged and counted, the relay is retried after the poll interval.
+pub struct Settled<F> {
+	kind: RelayKind,
+	source: H256,
+	future: F,
+}
+
+impl<F: Future<Error = Error>> Future for Settled<F> {
+	type Item = Option<F::Item>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.future.poll() {
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Ok(Async::Ready(item)) => Ok(Async::Ready(Some(item))),
+			Err(err) => {
+				error!("{} of {:?} failed, retrying after the poll interval: {}", self.kind.as_str(), self.source, err);
+				metrics::relay_error(self.kind);
+				status::record_error(format!("{} of {:?} failed: {}", self.kind.as_str(), self.source, err));
+				Ok(Async::Ready(None))
+			},
+		}
+	}
+}
+
+/// Creates new `SendRelay` of the event emitted by the `source` transaction at given block.
+pub fn send_relay<T: Transport>(journal: Arc<Journal>, kind: RelayKind, source: (H256, u64), future: SendTransaction<T>) -> SendRelay<T> {
+	SendRelay {
+		journal,
+		kind,
+		source,
+		future: settled(kind, source.0, future),
+	}
+}
+
+/// Sends relay transaction and records it in the journal as soon as it's sent,
+/// independently of other relays sent at the same time.
+///
+/// Resolves to the hash and nonce of the transaction, or `None` if it failed to be sent.
+/// Relays sent before restart are awaited instead of being sent again, so their nonce is optional.
+pub struct SendRelay<T: Transport> {
+	journal: Arc<Journal>,
+	kind: RelayKind,
+	source: (H256, u64),
+	future: Settled<SendTransaction<T>>,
+}
+
+impl<T: Transport> Future for SendRelay<T> {
+	type Item = Option<(H256, Option<U256>)>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (hash, nonce) = match try_ready!(self.future.poll()) {
+			Some(sent) => sent,
+			None => return Ok(Async::Ready(None)),
+		};
+		self.journal.record(vec![JournalEntry::Relay(Relay {
+			kind: self.kind,
+			source: self.source.0,
+			destination: hash,
+			status: RelayStatus::Sent,
+			block: self.source.1,
+		})])?;
+		Ok(Async::Ready(Some((hash, Some(nonce)))))
+	}
+}
+
+/// Creates new `ConfirmRelay` of `relay` sent in the transaction tracked by `future`.
+pub fn confirm_relay<T: Transport + Clone>(journal: Arc<Journal>, relay: Relay, future: PendingTransaction<T>) -> ConfirmRelay<T> {
+	ConfirmRelay {
+		journal,
+		relay,
+		future,
+	}
+}
+
+/// Waits for relay transaction to be mined and records every replacement of the transaction in the journal,
+/// so the latest one is awaited after restart.
+pub struct ConfirmRelay<T: Transport> {
+	journal: Arc<Journal>,
+	/// Latest recorded relay.
+	relay: Relay,
+	future: PendingTransaction<T>,
+}
+
+impl<T: Transport + Clone> Future for ConfirmRelay<T> {
+	type Item = Outcome;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let result = self.future.poll()?;
+		let hash = self.future.hash();
+		if result.is_not_ready() && hash != self.relay.destination {
+			info!("recording replacement {:?} of relay of {:?}", hash, self.relay.source);
+			self.relay.destination = hash;
+			self.journal.record(vec![JournalEntry::Relay(self.relay.clone())])?;
+		}
+		Ok(result)
+	}
+}
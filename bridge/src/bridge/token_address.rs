@@ -0,0 +1,285 @@
+use std::fmt;
+use std::sync::Arc;
+use std::collections::VecDeque;
+use futures::{Future, Stream, Poll};
+use futures::future::Join4;
+use ethabi::RawLog;
+use web3::Transport;
+use web3::types::{Address, Bytes, FilterBuilder, H256, TransactionRequest, U256};
+use api::{self, LogStream, LogStreamEvent, RetryCall};
+use app::App;
+use contracts::{foreign, storage};
+use database::Database;
+use error::Error;
+use nonce::{self, SendTransaction};
+use pubsub;
+use transaction::{PendingTransaction, PendingTransactionInit, pending_transaction};
+use util::web3_filter;
+
+fn token_address_filter(foreign: &foreign::ForeignBridge, address: Address) -> FilterBuilder {
+	let filter = foreign.events().token_address().create_filter();
+	web3_filter(filter, address)
+}
+
+fn retry_call<T: Transport + Clone>(app: &App<T>, contract: Address, payload: Vec<u8>) -> RetryCall<T, Bytes> {
+	let foreign = &app.connections.foreign;
+	let call = api::call(foreign, contract, payload.into());
+	api::retry_call(foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, call)
+}
+
+fn retry_storage<T: Transport + Clone>(app: &App<T>, contract: Address, position: U256) -> RetryCall<T, H256> {
+	let foreign = &app.connections.foreign;
+	let call = api::storage(foreign, contract, position);
+	api::retry_call(foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, call)
+}
+
+/// Votes of authorities for a candidate token of `ForeignBridge`.
+#[derive(Debug, PartialEq)]
+pub struct TokenAddressVotes {
+	pub token: Address,
+	/// Number of authorities who voted for the token.
+	pub votes: U256,
+	pub required_signatures: U256,
+	/// Whether `foreign.account` has voted for the token.
+	pub voted: bool,
+	/// Token currently used by the contract.
+	pub current: Address,
+}
+
+impl fmt::Display for TokenAddressVotes {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "token {:?} has {} of {} required votes", self.token, self.votes, self.required_signatures)?;
+		writeln!(f, "local authority has {}voted for the token", if self.voted { "" } else { "not " })?;
+		write!(f, "current token is {:?}", self.current)
+	}
+}
+
+/// Creates new `LoadTokenAddressVotes` of `token` at the contract in `init`.
+pub fn create_load_token_address_votes<T: Transport + Clone>(app: Arc<App<T>>, init: &Database, token: Address) -> LoadTokenAddressVotes<T> {
+	load_token_address_votes(app, init.foreign_contract_address, token)
+}
+
+fn load_token_address_votes<T: Transport + Clone>(app: Arc<App<T>>, contract: Address, token: Address) -> LoadTokenAddressVotes<T> {
+	let future = {
+		let functions = app.foreign_bridge.functions();
+		retry_storage(&app, contract, storage::foreign_token_address_votes(token)).join4(
+			retry_call(&app, contract, functions.required_signatures().input()),
+			retry_storage(&app, contract, storage::foreign_token_address_voted(app.config.foreign.account, token)),
+			retry_call(&app, contract, functions.erc20token().input()),
+		)
+	};
+
+	LoadTokenAddressVotes {
+		app,
+		token,
+		future,
+	}
+}
+
+/// Reads votes for a candidate token of `ForeignBridge`.
+pub struct LoadTokenAddressVotes<T: Transport> {
+	app: Arc<App<T>>,
+	token: Address,
+	future: Join4<RetryCall<T, H256>, RetryCall<T, Bytes>, RetryCall<T, H256>, RetryCall<T, Bytes>>,
+}
+
+impl<T: Transport> Future for LoadTokenAddressVotes<T> {
+	type Item = TokenAddressVotes;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (votes, required_signatures, voted, current) = try_ready!(self.future.poll());
+		let functions = self.app.foreign_bridge.functions();
+		let votes = TokenAddressVotes {
+			token: self.token,
+			votes: U256::from(&votes.0[..]),
+			required_signatures: functions.required_signatures().output(&required_signatures.0)?,
+			voted: !voted.is_zero(),
+			current: functions.erc20token().output(&current.0)?,
+		};
+		Ok(votes.into())
+	}
+}
+
+enum SetTokenAddressState<T: Transport + Clone> {
+	/// Checking whether the authority has already voted, the contract rejects repeated votes.
+	CheckVoted(RetryCall<T, H256>),
+	SendVote {
+		future: SendTransaction<T>,
+		request: Option<TransactionRequest>,
+	},
+	ConfirmVote(PendingTransaction<T>),
+	LoadVotes(LoadTokenAddressVotes<T>),
+}
+
+/// Creates new `SetTokenAddress` voting for `token` at the contract in `init`.
+pub fn create_set_token_address<T: Transport + Clone>(app: Arc<App<T>>, init: &Database, token: Address) -> SetTokenAddress<T> {
+	let voted = storage::foreign_token_address_voted(app.config.foreign.account, token);
+	SetTokenAddress {
+		state: SetTokenAddressState::CheckVoted(retry_storage(&app, init.foreign_contract_address, voted)),
+		contract: init.foreign_contract_address,
+		app,
+		token,
+	}
+}
+
+/// Submits the vote of `foreign.account` for the token of `ForeignBridge` with `setTokenAddress`
+/// and reads the votes once the vote is confirmed.
+pub struct SetTokenAddress<T: Transport + Clone> {
+	app: Arc<App<T>>,
+	contract: Address,
+	token: Address,
+	state: SetTokenAddressState<T>,
+}
+
+impl<T: Transport + Clone> SetTokenAddress<T> {
+	fn load_votes(&self) -> SetTokenAddressState<T> {
+		SetTokenAddressState::LoadVotes(load_token_address_votes(self.app.clone(), self.contract, self.token))
+	}
+}
+
+impl<T: Transport + Clone> Future for SetTokenAddress<T> {
+	type Item = TokenAddressVotes;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				SetTokenAddressState::CheckVoted(ref mut future) => {
+					let voted = try_ready!(future.poll());
+					let app = &self.app;
+					if !voted.is_zero() {
+						info!("{:?} has already voted for token {:?}", app.config.foreign.account, self.token);
+						self.load_votes()
+					} else {
+						let request = TransactionRequest {
+							from: app.config.foreign.account,
+							to: Some(self.contract),
+							gas: Some(app.config.txs.set_token_address.gas.into()),
+							gas_price: Some(app.gas_prices.foreign.price(app.config.txs.set_token_address.gas_price)),
+							value: None,
+							data: Some(app.foreign_bridge.functions().set_token_address().input(self.token).into()),
+							nonce: None,
+							condition: None,
+						};
+						let future = nonce::send_transaction(
+							app.connections.foreign.clone(),
+							app.timer.clone(),
+							app.nonces.foreign.clone(),
+							app.signers.foreign.clone(),
+							request.clone(),
+							app.config.foreign.request_timeout
+						);
+						SetTokenAddressState::SendVote {
+							future,
+							request: Some(request),
+						}
+					}
+				},
+				SetTokenAddressState::SendVote { ref mut future, ref mut request } => {
+					let (hash, _) = try_ready!(future.poll());
+					info!("voted for token {:?} in transaction {:?}", self.token, hash);
+					let app = &self.app;
+					SetTokenAddressState::ConfirmVote(pending_transaction(app.connections.foreign.clone(), app.timer.clone(), PendingTransactionInit {
+						hash,
+						request: request.take().expect("request is taken only once; qed"),
+						request_timeout: app.config.foreign.request_timeout,
+						poll_interval: app.config.foreign.poll_interval,
+						confirmations: app.config.foreign.required_confirmations,
+						resubmission: app.config.txs.resubmission.clone(),
+						signer: app.signers.foreign.clone(),
+						retry: app.config.retry.clone(),
+					}))
+				},
+				SetTokenAddressState::ConfirmVote(ref mut future) => {
+					try_ready!(future.poll()).receipt()?;
+					self.load_votes()
+				},
+				SetTokenAddressState::LoadVotes(ref mut future) => return future.poll(),
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+/// Creates new `TokenAddressEvents` of the contract in `init`, following events after the block at which it has been deployed.
+pub fn create_token_address_events<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> TokenAddressEvents<T> {
+	let logs_init = api::LogStreamInit {
+		after: init.foreign_deploy,
+		request_timeout: app.config.foreign.request_timeout,
+		poll_interval: app.config.foreign.poll_interval,
+		confirmations: app.config.foreign.required_confirmations,
+		reorg_depth: app.config.foreign.reorg_depth,
+		max_block_range: app.config.foreign.max_block_range,
+		filter: token_address_filter(&app.foreign_bridge, init.foreign_contract_address),
+	};
+
+	TokenAddressEvents {
+		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init)
+			.with_new_heads(app.subscriptions.foreign.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.foreign.request_timeout)))
+			.with_retry(app.config.retry.clone()),
+		tokens: VecDeque::new(),
+		app,
+	}
+}
+
+/// Follows `TokenAddress` events of `ForeignBridge`.
+///
+/// Yields tokens set up by the authorities in the order of events.
+pub struct TokenAddressEvents<T: Transport> {
+	app: Arc<App<T>>,
+	logs: LogStream<T>,
+	/// Tokens of the last range of logs which haven't been yielded yet.
+	tokens: VecDeque<Address>,
+}
+
+impl<T: Transport + Clone> Stream for TokenAddressEvents<T> {
+	type Item = Address;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		loop {
+			if let Some(token) = self.tokens.pop_front() {
+				return Ok(Some(token).into());
+			}
+
+			let item = match try_stream!(self.logs.poll()) {
+				LogStreamEvent::Logs(item) => item,
+				LogStreamEvent::Reorg { from, to } => {
+					warn!("foreign blocks {}..{} have been reorganized, TokenAddress events will be yielded again", from, to);
+					continue;
+				},
+			};
+
+			for log in item.logs {
+				let raw_log = RawLog {
+					topics: log.topics,
+					data: log.data.0,
+				};
+				let event = self.app.foreign_bridge.events().token_address().parse_log(raw_log)?;
+				self.tokens.push_back(event.token);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::TokenAddressVotes;
+
+	#[test]
+	fn test_token_address_votes_display() {
+		let votes = TokenAddressVotes {
+			token: "00000000000000000000000000000000000000aa".into(),
+			votes: 1.into(),
+			required_signatures: 2.into(),
+			voted: true,
+			current: "0000000000000000000000000000000000000000".into(),
+		};
+		let expected = "token 0x00000000000000000000000000000000000000aa has 1 of 2 required votes
+local authority has voted for the token
+current token is 0x0000000000000000000000000000000000000000";
+		assert_eq!(expected, votes.to_string());
+	}
+}
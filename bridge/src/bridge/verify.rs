@@ -0,0 +1,388 @@
+use std::sync::Arc;
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use futures::future::{join_all, Join, Join4, JoinAll};
+use ethabi::{self, ParamType};
+use rustc_hex::FromHex;
+use rpc;
+use web3;
+use web3::Transport;
+use web3::types::{Address, Bytes, H256, U256};
+use api::{self, RetryCall};
+use app::App;
+use config::Authorities;
+use contracts::{storage, HOME_BRIDGE_RUNTIME, FOREIGN_BRIDGE_RUNTIME};
+use database::Database;
+use error::{Error, ErrorKind};
+use preflight;
+
+/// Strips the metadata appended to the bytecode by solc.
+///
+/// Metadata contains a hash of the sources and compiler settings,
+/// so it differs between otherwise identical builds.
+fn strip_metadata(code: &[u8]) -> &[u8] {
+	if code.len() < 2 {
+		return code;
+	}
+
+	// metadata is a cbor map followed by its length encoded in two bytes
+	let length = ((code[code.len() - 2] as usize) << 8) + code[code.len() - 1] as usize + 2;
+	match code.len().checked_sub(length) {
+		Some(start) if code[start] & 0xe0 == 0xa0 => &code[..start],
+		_ => code,
+	}
+}
+
+fn decode_uint(data: &[u8]) -> Result<U256, Error> {
+	ethabi::decode(&[ParamType::Uint(256)], data)?
+		.pop()
+		.and_then(|token| token.to_uint())
+		.ok_or_else(|| "Invalid uint returned by contract".into())
+}
+
+fn decode_address(data: &[u8]) -> Result<Address, Error> {
+	ethabi::decode(&[ParamType::Address], data)?
+		.pop()
+		.and_then(|token| token.to_address())
+		.ok_or_else(|| "Invalid address returned by contract".into())
+}
+
+/// Authorities checked on a contract, `authorities` of the config followed by the local `account`.
+fn checked_authorities(authorities: &Authorities, account: Address) -> Vec<Address> {
+	let mut accounts = authorities.accounts.clone();
+	if !accounts.contains(&account) {
+		accounts.push(account);
+	}
+	accounts
+}
+
+/// Bridge contract checked against the config.
+struct Contract {
+	name: &'static str,
+	address: Address,
+	/// Local account sending transactions to the contract.
+	account: Address,
+	/// Hex encoded runtime bytecode of the contract.
+	runtime: &'static str,
+}
+
+impl Contract {
+	/// Checks deployed `code`, `results` of `requiredSignatures` and `estimatedGasCostOfWithdraw`
+	/// and whether each of `checked_authorities` `is_authority` of the contract.
+	fn verify(&self, authorities: &Authorities, estimated_gas_cost_of_withdraw: u32, code: &[u8], results: &[Bytes], is_authority: &[bool]) -> Result<(), Error> {
+		let invalid = |reason: String| -> Error { ErrorKind::InvalidContract(self.name, self.address, reason).into() };
+
+		if code.is_empty() {
+			return Err(invalid("there is no contract at the address".into()));
+		}
+		let runtime: Vec<u8> = self.runtime.trim().from_hex()?;
+		if strip_metadata(code) != strip_metadata(&runtime) {
+			return Err(invalid("deployed bytecode differs from the compiled runtime bytecode".into()));
+		}
+
+		let required_signatures = decode_uint(&results[0].0)?;
+		if required_signatures != authorities.required_signatures.into() {
+			return Err(invalid(format!("requiredSignatures is {} but authorities.required_signatures is {}", required_signatures, authorities.required_signatures)));
+		}
+
+		let deployed_gas_cost = decode_uint(&results[1].0)?;
+		if deployed_gas_cost != estimated_gas_cost_of_withdraw.into() {
+			return Err(invalid(format!("estimatedGasCostOfWithdraw is {} but estimated_gas_cost_of_withdraw is {}", deployed_gas_cost, estimated_gas_cost_of_withdraw)));
+		}
+
+		for (authority, is_authority) in checked_authorities(authorities, self.account).into_iter().zip(is_authority) {
+			if *is_authority {
+				continue;
+			}
+
+			let reason = if authority == self.account {
+				format!("local account {:?} is not an authority", authority)
+			} else {
+				format!("{:?} of authorities.accounts is not an authority", authority)
+			};
+			return Err(invalid(reason));
+		}
+
+		Ok(())
+	}
+
+	/// Checks that `elements` of the public `authorities` array, read up to one past the number of configured
+	/// authorities, hold the same accounts as `authorities.accounts`. Elements past the end of the array are empty.
+	fn verify_authorities(&self, authorities: &Authorities, elements: &[Bytes]) -> Result<(), Error> {
+		let invalid = |reason: String| -> Error { ErrorKind::InvalidContract(self.name, self.address, reason).into() };
+
+		let deployed = deployed_authorities(elements)?;
+		let configured = &authorities.accounts;
+
+		if deployed.len() > configured.len() {
+			return Err(invalid(format!("authorities has more accounts than the {} of authorities.accounts", configured.len())));
+		}
+		if deployed.len() < configured.len() {
+			return Err(invalid(format!("authorities has {} accounts but authorities.accounts has {}", deployed.len(), configured.len())));
+		}
+		if let Some(missing) = configured.iter().find(|account| !deployed.contains(account)) {
+			return Err(invalid(format!("{:?} of authorities.accounts is not in authorities", missing)));
+		}
+		if let Some(extra) = deployed.iter().find(|account| !configured.contains(account)) {
+			return Err(invalid(format!("authorities contains {:?} which is not in authorities.accounts", extra)));
+		}
+
+		Ok(())
+	}
+}
+
+/// Accounts of the `elements` of the public `authorities` array up to the first empty element past its end.
+fn deployed_authorities(elements: &[Bytes]) -> Result<Vec<Address>, Error> {
+	elements.iter()
+		.take_while(|element| !element.0.is_empty())
+		.map(|element| decode_address(&element.0))
+		.collect()
+}
+
+/// Element of a public array read with its getter. Resolves to empty output past the end of the array,
+/// where the getter reverts or hits an invalid opcode. Other errors of the call are returned.
+struct ArrayElement<T: Transport> {
+	future: RetryCall<T, Bytes>,
+}
+
+impl<T: Transport> Future for ArrayElement<T> {
+	type Item = Bytes;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.future.poll() {
+			Err(ref err) if is_past_end(err) => Ok(Async::Ready(Bytes::default())),
+			result => result,
+		}
+	}
+}
+
+/// Returns true if the getter failed because the index is past the end of the array.
+/// Depending on the compiler, the getter either reverts or hits an invalid opcode.
+fn is_past_end(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Web3(ref err) => match *err.kind() {
+			web3::error::ErrorKind::Rpc(ref err) => preflight::is_revert(err) || is_invalid_opcode(err),
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+/// geth reports the invalid opcode in the message, parity in the data of the error.
+fn is_invalid_opcode(err: &rpc::Error) -> bool {
+	let data = err.data.as_ref().map_or_else(String::new, |data| data.to_string().to_lowercase());
+	err.message.to_lowercase().contains("invalid opcode") || data.contains("badinstruction")
+}
+
+type ContractFuture<T> = Join<RetryCall<T, Bytes>, JoinAll<Vec<RetryCall<T, Bytes>>>>;
+
+fn contract_future<T: Transport + Clone>(app: &App<T>, transport: &T, request_timeout: Duration, address: Address, payloads: Vec<Vec<u8>>) -> ContractFuture<T> {
+	let retry = |call| api::retry_call(transport.clone(), app.timer.clone(), request_timeout, &app.config.retry, call);
+	let code = retry(api::code(transport, address));
+	let calls = payloads.into_iter()
+		.map(|payload| retry(api::call(transport, address, payload.into())))
+		.collect();
+	code.join(join_all(calls))
+}
+
+/// Creates new `Verify` of contracts in `init`.
+pub fn create_verify<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> Verify<T> {
+	let home = Contract {
+		name: "HomeBridge",
+		address: init.home_contract_address,
+		account: app.config.home.account,
+		runtime: HOME_BRIDGE_RUNTIME,
+	};
+	let foreign = Contract {
+		name: "ForeignBridge",
+		address: init.foreign_contract_address,
+		account: app.config.foreign.account,
+		runtime: FOREIGN_BRIDGE_RUNTIME,
+	};
+
+	let future = {
+		let functions = app.home_bridge.functions();
+		let payloads = vec![functions.required_signatures().input(), functions.estimated_gas_cost_of_withdraw().input()];
+		let home_future = contract_future(&app, &app.connections.home, app.config.home.request_timeout, home.address, payloads);
+
+		let functions = app.foreign_bridge.functions();
+		let payloads = vec![functions.required_signatures().input(), functions.estimated_gas_cost_of_withdraw().input()];
+		let foreign_future = contract_future(&app, &app.connections.foreign, app.config.foreign.request_timeout, foreign.address, payloads);
+
+		// `ForeignBridge.authorities` is a mapping without a getter, the flags are read from the storage
+		let retry = |call| api::retry_call(app.connections.foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, call);
+		let foreign_authorities = checked_authorities(&app.config.authorities, foreign.account).into_iter()
+			.map(|authority| retry(api::storage(&app.connections.foreign, foreign.address, storage::foreign_authority(authority))))
+			.collect();
+
+		// one element past the configured authorities is read to make sure there are no more
+		let retry = |call| api::retry_call(app.connections.home.clone(), app.timer.clone(), app.config.home.request_timeout, &app.config.retry, call);
+		let elements = (0..app.config.authorities.accounts.len() + 1)
+			.map(|index| {
+				let payload = app.home_bridge.functions().authorities().input(U256::from(index));
+				ArrayElement {
+					future: retry(api::call(&app.connections.home, home.address, payload.into())),
+				}
+			})
+			.collect();
+
+		home_future.join4(join_all(elements), foreign_future, join_all(foreign_authorities))
+	};
+
+	Verify {
+		app,
+		home,
+		foreign,
+		future,
+	}
+}
+
+/// Checks that contracts of the database are the bridge contracts of the config
+/// and that local accounts are their authorities.
+pub struct Verify<T: Transport> {
+	app: Arc<App<T>>,
+	home: Contract,
+	foreign: Contract,
+	future: Join4<ContractFuture<T>, JoinAll<Vec<ArrayElement<T>>>, ContractFuture<T>, JoinAll<Vec<RetryCall<T, H256>>>>,
+}
+
+impl<T: Transport> Future for Verify<T> {
+	type Item = ();
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let ((home_code, home_results), home_authorities, (foreign_code, foreign_results), foreign_authorities) = try_ready!(self.future.poll());
+		let config = &self.app.config;
+		let deployed = deployed_authorities(&home_authorities)?;
+		let is_authority = checked_authorities(&config.authorities, self.home.account).iter()
+			.map(|authority| deployed.contains(authority))
+			.collect::<Vec<_>>();
+		self.home.verify(&config.authorities, config.estimated_gas_cost_of_withdraw, &home_code.0, &home_results, &is_authority)?;
+		// `ForeignBridge` keeps authorities in a mapping, which can't be enumerated
+		self.home.verify_authorities(&config.authorities, &home_authorities)?;
+		let is_authority = foreign_authorities.iter().map(|flag| !flag.is_zero()).collect::<Vec<_>>();
+		self.foreign.verify(&config.authorities, config.estimated_gas_cost_of_withdraw, &foreign_code.0, &foreign_results, &is_authority)?;
+		Ok(().into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rpc;
+	use rustc_hex::FromHex;
+	use web3;
+	use web3::types::Bytes;
+	use config::Authorities;
+	use error::{Error, ErrorKind};
+	use super::{strip_metadata, is_past_end, Contract};
+
+	const RUNTIME: &str = "6060604052600080fd00a165627a7a72305820aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa0029";
+
+	fn uint(value: u8) -> Bytes {
+		let mut data = vec![0u8; 32];
+		data[31] = value;
+		data.into()
+	}
+
+	#[test]
+	fn test_strip_metadata() {
+		let code: Vec<u8> = RUNTIME.from_hex().unwrap();
+		assert_eq!(&code[..10], strip_metadata(&code));
+		let code: Vec<u8> = "6060604052600080fd".from_hex().unwrap();
+		assert_eq!(&code[..], strip_metadata(&code));
+		assert_eq!(&[0x29u8][..], strip_metadata(&[0x29]));
+	}
+
+	#[test]
+	fn test_verify_contract() {
+		let authorities = Authorities {
+			accounts: vec![
+				"0000000000000000000000000000000000000001".into(),
+				"0000000000000000000000000000000000000002".into(),
+			],
+			required_signatures: 1,
+		};
+		let contract = Contract {
+			name: "HomeBridge",
+			address: "00000000000000000000000000000000000000dd".into(),
+			account: "0000000000000000000000000000000000000002".into(),
+			runtime: RUNTIME,
+		};
+
+		// same bytecode with different metadata
+		let code: Vec<u8> = "6060604052600080fd00a165627a7a72305820bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb0029".from_hex().unwrap();
+		let results = vec![uint(1), uint(100)];
+		contract.verify(&authorities, 100, &code, &results, &[true, true]).unwrap();
+
+		let invalid = |code: &[u8], results: &[Bytes], is_authority: &[bool]| match *contract.verify(&authorities, 100, code, results, is_authority).unwrap_err().kind() {
+			ErrorKind::InvalidContract(_, _, ref reason) => reason.clone(),
+			ref err => panic!("unexpected error {:?}", err),
+		};
+		assert_eq!("there is no contract at the address", invalid(&[], &results, &[true, true]));
+		assert_eq!("deployed bytecode differs from the compiled runtime bytecode", invalid(&code[1..], &results, &[true, true]));
+		assert_eq!("requiredSignatures is 2 but authorities.required_signatures is 1", invalid(&code, &[uint(2), uint(100)], &[true, true]));
+		assert_eq!("estimatedGasCostOfWithdraw is 0 but estimated_gas_cost_of_withdraw is 100", invalid(&code, &[uint(1), uint(0)], &[true, true]));
+		assert_eq!(
+			"0x0000000000000000000000000000000000000001 of authorities.accounts is not an authority",
+			invalid(&code, &results, &[false, true])
+		);
+		assert_eq!(
+			"local account 0x0000000000000000000000000000000000000002 is not an authority",
+			invalid(&code, &results, &[true, false])
+		);
+	}
+
+	#[test]
+	fn test_verify_authorities() {
+		let authorities = Authorities {
+			accounts: vec![
+				"0000000000000000000000000000000000000001".into(),
+				"0000000000000000000000000000000000000002".into(),
+			],
+			required_signatures: 1,
+		};
+		let contract = Contract {
+			name: "HomeBridge",
+			address: "00000000000000000000000000000000000000dd".into(),
+			account: "0000000000000000000000000000000000000002".into(),
+			runtime: RUNTIME,
+		};
+
+		// order of the accounts doesn't matter
+		contract.verify_authorities(&authorities, &[uint(2), uint(1), Bytes::default()]).unwrap();
+
+		let invalid = |elements: &[Bytes]| match *contract.verify_authorities(&authorities, elements).unwrap_err().kind() {
+			ErrorKind::InvalidContract(_, _, ref reason) => reason.clone(),
+			ref err => panic!("unexpected error {:?}", err),
+		};
+		assert_eq!("authorities has more accounts than the 2 of authorities.accounts", invalid(&[uint(1), uint(2), uint(3)]));
+		assert_eq!("authorities has 1 accounts but authorities.accounts has 2", invalid(&[uint(1), Bytes::default(), Bytes::default()]));
+		assert_eq!(
+			"0x0000000000000000000000000000000000000002 of authorities.accounts is not in authorities",
+			invalid(&[uint(1), uint(3), Bytes::default()])
+		);
+	}
+	fn rpc_error(code: i64, message: &str, data: Option<rpc::Value>) -> Error {
+		let err = rpc::Error {
+			code: rpc::ErrorCode::ServerError(code),
+			message: message.into(),
+			data,
+		};
+		ErrorKind::Web3(web3::error::ErrorKind::Rpc(err).into()).into()
+	}
+
+	#[test]
+	fn test_is_past_end() {
+		assert!(is_past_end(&rpc_error(-32000, "invalid opcode: opcode 0xfe not defined", None)));
+		assert!(is_past_end(&rpc_error(-32000, "execution reverted", None)));
+		assert!(is_past_end(&rpc_error(-32015, "VM execution error.", Some(rpc::Value::String("BadInstruction { instruction: 254 }".into())))));
+		assert!(is_past_end(&rpc_error(-32015, "VM execution error.", Some(rpc::Value::String("Reverted 0x".into())))));
+
+		assert!(!is_past_end(&rpc_error(-32000, "header not found", None)));
+		assert!(!is_past_end(&rpc_error(-32015, "VM execution error.", Some(rpc::Value::String("OutOfGas".into())))));
+		let internal = web3::error::ErrorKind::Rpc(rpc::Error::internal_error()).into();
+		assert!(!is_past_end(&ErrorKind::Web3(internal).into()));
+		assert!(!is_past_end(&ErrorKind::Web3(web3::error::ErrorKind::Unreachable.into()).into()));
+	}
+}
@@ -1,17 +1,24 @@
 use std::sync::Arc;
-use std::ops;
 use futures::{Future, Stream, Poll};
-use futures::future::{JoinAll, join_all};
-use tokio_timer::Timeout;
+use futures::future::{self, Either, FutureResult, JoinAll, join_all};
 use web3::Transport;
-use web3::types::{H256, H520, Address, TransactionRequest, Bytes, FilterBuilder};
-use api::{self, LogStream, ApiCall};
+use tokio_timer::Sleep;
+use web3::types::{H256, H520, U256, Address, TransactionRequest, Bytes, FilterBuilder};
+use api::{self, LogStream, LogStreamEvent};
 use app::App;
-use contracts::foreign;
+use contracts::{foreign, storage};
 use util::web3_filter;
 use database::Database;
 use error::Error;
+use journal::{JournalEntry, Relay, RelayKind, RelayStatus};
+use metrics;
+use pubsub;
 use message_to_mainnet::{MessageToMainnet, MESSAGE_LENGTH};
+use nonce;
+use preflight::{self, Processed};
+use signer::{Signer, SignMessage};
+use transaction::{Outcome, PendingTransactionInit, pending_transaction};
+use super::relay::{SendRelay, ConfirmRelay, Settled, send_relay, confirm_relay, settled};
 
 fn withdraws_filter(foreign: &foreign::ForeignBridge, address: Address) -> FilterBuilder {
 	let filter = foreign.events().withdraw().create_filter();
@@ -23,36 +30,109 @@ fn withdraw_submit_signature_payload(foreign: &foreign::ForeignBridge, withdraw_
 	foreign.functions().submit_signature().input(signature.0.to_vec(), withdraw_message).into()
 }
 
+/// Withdraw whose signature is about to be submitted.
+struct Withdraw {
+	message: Vec<u8>,
+	/// Hash and block number of the withdraw transaction.
+	source: (H256, u64),
+	/// Hash of the transaction submitting the signature once it has been sent.
+	sent: Option<H256>,
+	/// Nonce of the transaction submitting the signature once it has been sent, unknown for submissions sent before restart.
+	nonce: Option<U256>,
+	/// Whether the signature has been submitted before restart.
+	restored: bool,
+}
+
+impl Withdraw {
+	fn relay(&self, destination: H256, status: RelayStatus) -> Relay {
+		Relay {
+			kind: RelayKind::WithdrawConfirm,
+			source: self.source.0,
+			destination,
+			status,
+			block: self.source.1,
+		}
+	}
+}
+
 /// State of withdraw confirmation.
 enum WithdrawConfirmState<T: Transport> {
 	/// Withdraw confirm is waiting for logs.
 	Wait,
+	/// Checking whether the authority has already submitted signatures of withdraws which haven't been sent yet.
+	CheckWithdraws {
+		future: JoinAll<Vec<Either<Processed<T>, FutureResult<bool, Error>>>>,
+		withdraws: Vec<Withdraw>,
+		block: u64,
+	},
 	/// Signing withdraws.
 	SignWithdraws {
-		messages: Vec<Vec<u8>>,
-		future: JoinAll<Vec<Timeout<ApiCall<H520, T::Out>>>>,
+		/// Signed by the node or locally.
+		future: JoinAll<Vec<Settled<SignMessage<T>>>>,
+		withdraws: Vec<Withdraw>,
+		block: u64,
+	},
+	/// Submitting signatures. Every submission is journaled as soon as it's sent,
+	/// submissions sent before restart resolve to the journaled hash.
+	SubmitSignatures {
+		future: JoinAll<Vec<Either<SendRelay<T>, FutureResult<Option<(H256, Option<U256>)>, Error>>>>,
+		requests: Vec<TransactionRequest>,
+		withdraws: Vec<Withdraw>,
+		/// Withdraws which failed to be signed or submitted.
+		failed: Vec<Withdraw>,
 		block: u64,
 	},
-	/// Confirming withdraws.
+	/// Waiting for submissions to be mined and confirmed.
+	/// Failed submissions are skipped and dropped ones are checked and submitted again,
+	/// as are failed submissions sent before restart.
 	ConfirmWithdraws {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
+		future: JoinAll<Vec<ConfirmRelay<T>>>,
+		withdraws: Vec<Withdraw>,
+		/// Withdraws which failed to be signed or submitted.
+		failed: Vec<Withdraw>,
+		block: u64,
+	},
+	/// Withdraws which failed to be signed or submitted are checked and submitted again after the poll interval.
+	WaitForRetry {
+		future: Sleep,
+		withdraws: Vec<Withdraw>,
 		block: u64,
 	},
 	/// All withdraws till given block has been confirmed.
 	Yield(Option<u64>),
 }
 
+impl<T: Transport> WithdrawConfirmState<T> {
+	/// Name of the state reported by the status api.
+	fn name(&self) -> &'static str {
+		match *self {
+			WithdrawConfirmState::Wait => "wait",
+			WithdrawConfirmState::CheckWithdraws { .. } => "check_withdraws",
+			WithdrawConfirmState::SignWithdraws { .. } => "sign_withdraws",
+			WithdrawConfirmState::SubmitSignatures { .. } => "submit_signatures",
+			WithdrawConfirmState::ConfirmWithdraws { .. } => "confirm_withdraws",
+			WithdrawConfirmState::WaitForRetry { .. } => "wait_for_retry",
+			WithdrawConfirmState::Yield(_) => "yield",
+		}
+	}
+}
+
 pub fn create_withdraw_confirm<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawConfirm<T> {
 	let logs_init = api::LogStreamInit {
 		after: init.checked_withdraw_confirm,
 		request_timeout: app.config.foreign.request_timeout,
 		poll_interval: app.config.foreign.poll_interval,
 		confirmations: app.config.foreign.required_confirmations,
+		reorg_depth: app.config.foreign.reorg_depth,
+		max_block_range: app.config.foreign.max_block_range,
 		filter: withdraws_filter(&app.foreign_bridge, init.foreign_contract_address.clone()),
 	};
 
 	WithdrawConfirm {
-		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init),
+		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init)
+			.with_status(RelayKind::WithdrawConfirm, app.status.clone())
+			.with_new_heads(app.subscriptions.foreign.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.foreign.request_timeout)))
+			.with_retry(app.config.retry.clone()),
 		foreign_contract: init.foreign_contract_address,
 		state: WithdrawConfirmState::Wait,
 		app,
@@ -66,7 +146,30 @@ pub struct WithdrawConfirm<T: Transport> {
 	foreign_contract: Address,
 }
 
-impl<T: Transport> Stream for WithdrawConfirm<T> {
+/// Checks whether the authority has already submitted signatures of withdraws which haven't been sent yet.
+fn check_withdraws<T: Transport + Clone>(app: &App<T>, foreign_contract: Address, withdraws: Vec<Withdraw>, block: u64) -> WithdrawConfirmState<T> {
+	let checks = withdraws.iter()
+		.map(|withdraw| match withdraw.sent {
+			Some(_) => Either::B(future::ok(false)),
+			None => Either::A(preflight::processed(
+				app.connections.foreign.clone(),
+				app.timer.clone(),
+				app.config.foreign.request_timeout,
+				&app.config.retry,
+				foreign_contract,
+				storage::foreign_message_signed(app.config.foreign.account, &withdraw.message),
+			)),
+		})
+		.collect::<Vec<_>>();
+
+	WithdrawConfirmState::CheckWithdraws {
+		future: join_all(checks),
+		withdraws,
+		block,
+	}
+}
+
+impl<T: Transport + Clone> Stream for WithdrawConfirm<T> {
 	type Item = u64;
 	type Error = Error;
 
@@ -74,72 +177,233 @@ impl<T: Transport> Stream for WithdrawConfirm<T> {
 		loop {
 			let next_state = match self.state {
 				WithdrawConfirmState::Wait => {
-					let item = try_stream!(self.logs.poll());
+					let item = match try_stream!(self.logs.poll()) {
+						LogStreamEvent::Logs(item) => item,
+						LogStreamEvent::Reorg { from, to } => {
+							warn!("foreign blocks {}..{} have been reorganized, withdraws will be signed again", from, to);
+							// move the checkpoint back so it's persisted before the range is rescanned
+							self.state = WithdrawConfirmState::Yield(Some(from - 1));
+							continue;
+						},
+					};
 					info!("got {} new withdraws to sign", item.logs.len());
-					let withdraw_messages = item.logs
-						.into_iter()
-						.map(|log| {
-							 info!("withdraw is ready for signature submission. tx hash {}", log.transaction_hash.unwrap());
-							 Ok(MessageToMainnet::from_log(log)?.to_bytes())
-						})
-						.collect::<Result<Vec<_>, Error>>()?;
-
-					let requests = withdraw_messages.clone()
-						.into_iter()
-						.map(|message| {
-							self.app.timer.timeout(
-								api::sign(&self.app.connections.foreign, self.app.config.foreign.account, Bytes(message)),
-								self.app.config.foreign.request_timeout)
+					let app = &self.app;
+					let mut withdraws = Vec::new();
+					for log in item.logs {
+						let source = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
+						let confirmed = app.journal.relay(RelayKind::WithdrawConfirm, source);
+						if let Some(Relay { status: RelayStatus::Mined, .. }) = confirmed {
+							info!("withdraw {:?} has already been signed", source);
+							continue;
+						}
+
+						info!("withdraw is ready for signature submission. tx hash {}", source);
+						let block_number = log.block_number.map(|number| number.low_u64()).unwrap_or(item.to);
+						withdraws.push(Withdraw {
+							message: MessageToMainnet::from_log(log)?.to_bytes(),
+							source: (source, block_number),
+							sent: confirmed.as_ref().map(|relay| relay.destination),
+							nonce: None,
+							restored: confirmed.is_some(),
+						});
+					}
+
+					check_withdraws(app, self.foreign_contract, withdraws, item.to)
+				},
+				WithdrawConfirmState::CheckWithdraws { ref mut future, ref mut withdraws, block } => {
+					let signed = try_ready!(future.poll());
+					let withdraws = withdraws.drain(..)
+						.zip(signed.into_iter())
+						.filter(|&(ref withdraw, signed)| {
+							if signed {
+								info!("withdraw {:?} has already been signed by this authority, skipping", withdraw.source.0);
+							}
+							!signed
 						})
+						.map(|(withdraw, _)| withdraw)
+						.collect::<Vec<_>>();
+
+					// submissions sent before restart are signed again, so they can be replaced if they are not mined in time
+					let requests = withdraws.iter()
+						.map(|withdraw| settled(
+							RelayKind::WithdrawConfirm,
+							withdraw.source.0,
+							Signer::sign_message(&*self.app.signers.foreign, &self.app.connections.foreign, withdraw.message.clone()),
+						))
 						.collect::<Vec<_>>();
 
 					info!("signing");
 					WithdrawConfirmState::SignWithdraws {
 						future: join_all(requests),
-						messages: withdraw_messages,
-						block: item.to,
+						withdraws,
+						block,
 					}
 				},
-				WithdrawConfirmState::SignWithdraws { ref mut future, ref mut messages, block } => {
+				WithdrawConfirmState::SignWithdraws { ref mut future, ref mut withdraws, block } => {
 					let signatures = try_ready!(future.poll());
 					info!("signing complete");
+					let mut signed = Vec::new();
+					let mut failed = Vec::new();
+					for (withdraw, signature) in withdraws.drain(..).zip(signatures.into_iter()) {
+						match signature {
+							Some(signature) => signed.push((withdraw, signature)),
+							// a single withdraw which can't be signed doesn't stop the others
+							None => failed.push(withdraw),
+						}
+					}
+
 					// borrow checker...
 					let app = &self.app;
 					let foreign_contract = &self.foreign_contract;
-					let confirmations = messages
-						.drain(ops::RangeFull)
+					let (withdraws, signatures): (Vec<_>, Vec<_>) = signed.into_iter().unzip();
+					let requests = withdraws.iter()
 						.zip(signatures.into_iter())
-						.map(|(withdraw_message, signature)| {
-							 withdraw_submit_signature_payload(&app.foreign_bridge, withdraw_message, signature)
+						.map(|(withdraw, signature)| {
+							 withdraw_submit_signature_payload(&app.foreign_bridge, withdraw.message.clone(), signature)
 						})
 						.map(|payload| TransactionRequest {
 							from: app.config.foreign.account,
 							to: Some(foreign_contract.clone()),
-							gas: Some(app.config.txs.withdraw_confirm.gas.into()),
-							gas_price: Some(app.config.txs.withdraw_confirm.gas_price.into()),
+							gas: Some(app.gas_limits.withdraw_confirm()),
+							gas_price: Some(app.gas_prices.foreign.price(app.config.txs.withdraw_confirm.gas_price)),
 							value: None,
 							data: Some(payload),
 							nonce: None,
 							condition: None,
 						})
-						.map(|request| {
-							info!("submitting signature");
-							app.timer.timeout(
-								api::send_transaction(&app.connections.foreign, request),
-								app.config.foreign.request_timeout)
+						.collect::<Vec<_>>();
+
+					let submissions = withdraws.iter()
+						.zip(requests.iter())
+						.map(|(withdraw, request)| match withdraw.sent {
+							Some(hash) => {
+								info!("signature of withdraw {:?} has already been submitted in {:?}, waiting for confirmation", withdraw.source.0, hash);
+								Either::B(future::ok(Some((hash, None))))
+							},
+							None => {
+								info!("submitting signature");
+								Either::A(send_relay(app.journal.clone(), RelayKind::WithdrawConfirm, withdraw.source, nonce::send_transaction(
+									app.connections.foreign.clone(),
+									app.timer.clone(),
+									app.nonces.foreign.clone(),
+									app.signers.foreign.clone(),
+									request.clone(),
+									app.config.foreign.request_timeout)))
+							},
+						})
+						.collect::<Vec<_>>();
+
+					info!("submitting {} signatures", submissions.len());
+					WithdrawConfirmState::SubmitSignatures {
+						future: join_all(submissions),
+						requests,
+						withdraws,
+						failed,
+						block,
+					}
+				},
+				WithdrawConfirmState::SubmitSignatures { ref mut future, ref mut requests, ref mut withdraws, ref mut failed, block } => {
+					let hashes = try_ready!(future.poll());
+					let mut failed = failed.drain(..).collect::<Vec<_>>();
+					let mut sent = Vec::new();
+					for ((mut withdraw, request), hash) in withdraws.drain(..).zip(requests.drain(..)).zip(hashes.into_iter()) {
+						match hash {
+							Some((hash, nonce)) => {
+								withdraw.sent = Some(hash);
+								withdraw.nonce = nonce;
+								sent.push((withdraw, request));
+							},
+							// a single submission which can't be sent doesn't stop the others
+							None => failed.push(withdraw),
+						}
+					}
+
+					info!("waiting for {} signature submissions to be confirmed", sent.len());
+					let app = &self.app;
+					let (withdraws, requests): (Vec<_>, Vec<_>) = sent.into_iter().unzip();
+					let pending = withdraws.iter()
+						.zip(requests.into_iter())
+						.map(|(withdraw, request)| {
+							let hash = withdraw.sent.expect("only sent withdraws are confirmed; qed");
+							confirm_relay(app.journal.clone(), withdraw.relay(hash, RelayStatus::Sent), pending_transaction(app.connections.foreign.clone(), app.timer.clone(), PendingTransactionInit {
+								hash,
+								request,
+								request_timeout: app.config.foreign.request_timeout,
+								poll_interval: app.config.foreign.poll_interval,
+								confirmations: app.config.foreign.required_confirmations,
+								resubmission: app.config.txs.resubmission.clone(),
+								signer: app.signers.foreign.clone(),
+								retry: app.config.retry.clone(),
+							}))
 						})
 						.collect::<Vec<_>>();
 
-					info!("submitting {} signatures", confirmations.len());
 					WithdrawConfirmState::ConfirmWithdraws {
-						future: join_all(confirmations),
+						future: join_all(pending),
+						withdraws,
+						failed,
 						block,
 					}
 				},
-				WithdrawConfirmState::ConfirmWithdraws { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					info!("submitting signatures complete");
-					WithdrawConfirmState::Yield(Some(block))
+				WithdrawConfirmState::ConfirmWithdraws { ref mut future, ref mut withdraws, ref mut failed, block } => {
+					let outcomes = try_ready!(future.poll());
+					let app = &self.app;
+					let mut mined = Vec::new();
+					let mut unsent = Vec::new();
+					for (withdraw, outcome) in withdraws.drain(..).zip(outcomes.into_iter()) {
+						match outcome {
+							Outcome::Mined(receipt) => mined.push(JournalEntry::Relay(withdraw.relay(receipt.transaction_hash, RelayStatus::Mined))),
+							// transaction sent before restart may have failed for reasons which no longer hold
+							Outcome::Failed(hash) if withdraw.restored => {
+								warn!("signature of withdraw {:?} submitted before restart in transaction {:?} failed, checking it again", withdraw.source.0, hash);
+								unsent.push(Withdraw {
+									sent: None,
+									restored: false,
+									..withdraw
+								});
+							},
+							Outcome::Failed(hash) => {
+								error!("signature of withdraw {:?} submitted in transaction {:?} failed, skipping", withdraw.source.0, hash);
+								app.status.record_error(format!("signature of withdraw {:?} submitted in transaction {:?} failed", withdraw.source.0, hash));
+								metrics::skipped(RelayKind::WithdrawConfirm, 1);
+							},
+							Outcome::Dropped(hash) => {
+								warn!("signature of withdraw {:?} submitted in transaction {:?} has been dropped, submitting it again", withdraw.source.0, hash);
+								// nonce of a dropped transaction is free again
+								if let Some(nonce) = withdraw.nonce {
+									app.nonces.foreign.release(nonce);
+								}
+								unsent.push(Withdraw {
+									sent: None,
+									nonce: None,
+									restored: false,
+									..withdraw
+								});
+							},
+						}
+					}
+
+					let relays_count = mined.len();
+					app.journal.record(mined)?;
+					metrics::relayed(RelayKind::WithdrawConfirm, relays_count);
+					if !unsent.is_empty() {
+						unsent.extend(failed.drain(..));
+						check_withdraws(app, self.foreign_contract, unsent, block)
+					} else if !failed.is_empty() {
+						WithdrawConfirmState::WaitForRetry {
+							future: app.timer.sleep(app.config.foreign.poll_interval),
+							withdraws: failed.drain(..).collect(),
+							block,
+						}
+					} else {
+						info!("submitting signatures complete");
+						WithdrawConfirmState::Yield(Some(block))
+					}
+				},
+				WithdrawConfirmState::WaitForRetry { ref mut future, ref mut withdraws, block } => {
+					try_ready!(future.poll());
+					let withdraws = withdraws.drain(..).collect();
+					check_withdraws(&self.app, self.foreign_contract, withdraws, block)
 				},
 				WithdrawConfirmState::Yield(ref mut block) => match block.take() {
 					None => {
@@ -150,6 +414,7 @@ impl<T: Transport> Stream for WithdrawConfirm<T> {
 				}
 			};
 			self.state = next_state;
+			self.app.status.stream(RelayKind::WithdrawConfirm, self.state.name());
 		}
 	}
 }
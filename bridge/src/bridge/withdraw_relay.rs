@@ -1,18 +1,25 @@
 use std::sync::Arc;
 use futures::{Future, Stream, Poll};
-use futures::future::{JoinAll, join_all, Join};
-use tokio_timer::Timeout;
+use futures::future::{self, Either, FutureResult, JoinAll, join_all, Join};
 use web3::Transport;
-use web3::types::{H256, Address, FilterBuilder, Log, Bytes, TransactionRequest};
+use tokio_timer::Sleep;
+use web3::types::{Address, FilterBuilder, Log, Bytes, TransactionRequest, H256, U256};
 use ethabi::{RawLog, self};
 use app::App;
-use api::{self, LogStream, ApiCall};
-use contracts::foreign;
+use api::{self, LogStream, LogStreamEvent, RetryCall};
+use contracts::{foreign, storage};
 use util::web3_filter;
 use database::Database;
 use error::{self, Error};
+use journal::{JournalEntry, Relay, RelayKind, RelayStatus};
+use metrics;
+use pubsub;
 use message_to_mainnet::MessageToMainnet;
 use signature::Signature;
+use nonce;
+use preflight::{self, Processed, Simulate};
+use transaction::{Outcome, PendingTransactionInit, pending_transaction};
+use super::relay::{SendRelay, ConfirmRelay, send_relay, confirm_relay};
 
 /// returns a filter for `ForeignBridge.CollectedSignatures` events
 fn collected_signatures_filter(foreign: &foreign::ForeignBridge, address: Address) -> FilterBuilder {
@@ -56,34 +63,107 @@ fn signatures_payload(foreign: &foreign::ForeignBridge, required_signatures: u32
 	}))
 }
 
+/// Withdraw which is about to be relayed.
+struct Withdraw {
+	request: TransactionRequest,
+	/// Storage position of the flag set once the withdraw has been executed.
+	withdrawn: U256,
+	/// Hash and block number of the transaction which collected the signatures.
+	source: (H256, u64),
+	/// Hash of the relay transaction once it has been sent.
+	sent: Option<H256>,
+	/// Nonce of the relay transaction once it has been sent, unknown for relays sent before restart.
+	nonce: Option<U256>,
+	/// Whether the relay has been sent before restart.
+	restored: bool,
+}
+
+impl Withdraw {
+	fn relay(&self, destination: H256, status: RelayStatus) -> Relay {
+		Relay {
+			kind: RelayKind::WithdrawRelay,
+			source: self.source.0,
+			destination,
+			status,
+			block: self.source.1,
+		}
+	}
+}
+
 /// state of the withdraw relay state machine
-pub enum WithdrawRelayState<T: Transport> {
+pub enum WithdrawRelayState<T: Transport + Clone> {
 	Wait,
 	FetchMessagesSignatures {
 		future: Join<
-			JoinAll<Vec<Timeout<ApiCall<Bytes, T::Out>>>>,
-			JoinAll<Vec<JoinAll<Vec<Timeout<ApiCall<Bytes, T::Out>>>>>>
+			JoinAll<Vec<RetryCall<T, Bytes>>>,
+			JoinAll<Vec<JoinAll<Vec<RetryCall<T, Bytes>>>>>
 		>,
+		/// Hashes and block numbers of transactions which collected the signatures.
+		sources: Vec<(H256, u64)>,
+		block: u64,
+	},
+	/// Checking whether withdraws which haven't been sent yet have already been executed
+	/// and simulating them.
+	SimulateWithdraws {
+		future: JoinAll<Vec<Either<Join<Processed<T>, Simulate<T>>, FutureResult<(bool, Option<String>), Error>>>>,
+		withdraws: Vec<Withdraw>,
 		block: u64,
 	},
+	/// Withdraws sent before restart resolve to the journaled hash.
 	RelayWithdraws {
-		future: JoinAll<Vec<Timeout<ApiCall<H256, T::Out>>>>,
+		future: JoinAll<Vec<Either<SendRelay<T>, FutureResult<Option<(H256, Option<U256>)>, Error>>>>,
+		withdraws: Vec<Withdraw>,
+		block: u64,
+	},
+	/// Failed relays are skipped and dropped ones are relayed again,
+	/// as are failed relays sent before restart.
+	ConfirmWithdraws {
+		future: JoinAll<Vec<ConfirmRelay<T>>>,
+		withdraws: Vec<Withdraw>,
+		/// Withdraws whose relay failed to be sent.
+		failed: Vec<Withdraw>,
+		block: u64,
+	},
+	/// Withdraws whose relay failed to be sent are relayed again after the poll interval.
+	WaitForRetry {
+		future: Sleep,
+		withdraws: Vec<Withdraw>,
 		block: u64,
 	},
 	Yield(Option<u64>),
 }
 
+impl<T: Transport + Clone> WithdrawRelayState<T> {
+	/// Name of the state reported by the status api.
+	fn name(&self) -> &'static str {
+		match *self {
+			WithdrawRelayState::Wait => "wait",
+			WithdrawRelayState::FetchMessagesSignatures { .. } => "fetch_messages_signatures",
+			WithdrawRelayState::SimulateWithdraws { .. } => "simulate_withdraws",
+			WithdrawRelayState::RelayWithdraws { .. } => "relay_withdraws",
+			WithdrawRelayState::ConfirmWithdraws { .. } => "confirm_withdraws",
+			WithdrawRelayState::WaitForRetry { .. } => "wait_for_retry",
+			WithdrawRelayState::Yield(_) => "yield",
+		}
+	}
+}
+
 pub fn create_withdraw_relay<T: Transport + Clone>(app: Arc<App<T>>, init: &Database) -> WithdrawRelay<T> {
 	let logs_init = api::LogStreamInit {
 		after: init.checked_withdraw_relay,
 		request_timeout: app.config.foreign.request_timeout,
 		poll_interval: app.config.foreign.poll_interval,
 		confirmations: app.config.foreign.required_confirmations,
+		reorg_depth: app.config.foreign.reorg_depth,
+		max_block_range: app.config.foreign.max_block_range,
 		filter: collected_signatures_filter(&app.foreign_bridge, init.foreign_contract_address),
 	};
 
 	WithdrawRelay {
-		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init),
+		logs: api::log_stream(app.connections.foreign.clone(), app.timer.clone(), logs_init)
+			.with_status(RelayKind::WithdrawRelay, app.status.clone())
+			.with_new_heads(app.subscriptions.foreign.clone().map(|transport| pubsub::new_heads(transport, app.timer.clone(), app.config.foreign.request_timeout)))
+			.with_retry(app.config.retry.clone()),
 		home_contract: init.home_contract_address,
 		foreign_contract: init.foreign_contract_address,
 		state: WithdrawRelayState::Wait,
@@ -91,7 +171,7 @@ pub fn create_withdraw_relay<T: Transport + Clone>(app: Arc<App<T>>, init: &Data
 	}
 }
 
-pub struct WithdrawRelay<T: Transport> {
+pub struct WithdrawRelay<T: Transport + Clone> {
 	app: Arc<App<T>>,
 	logs: LogStream<T>,
 	state: WithdrawRelayState<T>,
@@ -99,7 +179,45 @@ pub struct WithdrawRelay<T: Transport> {
 	home_contract: Address,
 }
 
-impl<T: Transport> Stream for WithdrawRelay<T> {
+impl<T: Transport + Clone> WithdrawRelay<T> {
+	/// Calls the foreign contract, retrying the call if it fails with a transient error.
+	fn call_foreign(&self, payload: Bytes) -> RetryCall<T, Bytes> {
+		let app = &self.app;
+		let call = api::call(&app.connections.foreign, self.foreign_contract.clone(), payload);
+		api::retry_call(app.connections.foreign.clone(), app.timer.clone(), app.config.foreign.request_timeout, &app.config.retry, call)
+	}
+}
+
+/// Checks whether withdraws which haven't been sent yet have already been executed and simulates them.
+fn simulate_withdraws<T: Transport + Clone>(app: &App<T>, home_contract: Address, withdraws: Vec<Withdraw>, block: u64) -> WithdrawRelayState<T> {
+	let simulations = withdraws.iter()
+		.map(|withdraw| match withdraw.sent {
+			Some(_) => Either::B(future::ok((false, None))),
+			None => Either::A(preflight::processed(
+				app.connections.home.clone(),
+				app.timer.clone(),
+				app.config.home.request_timeout,
+				&app.config.retry,
+				home_contract,
+				withdraw.withdrawn,
+			).join(preflight::simulate(
+				app.connections.home.clone(),
+				app.timer.clone(),
+				app.config.home.request_timeout,
+				&app.config.retry,
+				&withdraw.request,
+			))),
+		})
+		.collect();
+
+	WithdrawRelayState::SimulateWithdraws {
+		future: join_all(simulations),
+		withdraws,
+		block,
+	}
+}
+
+impl<T: Transport + Clone> Stream for WithdrawRelay<T> {
 	type Item = u64;
 	type Error = Error;
 
@@ -107,41 +225,53 @@ impl<T: Transport> Stream for WithdrawRelay<T> {
 		loop {
 			let next_state = match self.state {
 				WithdrawRelayState::Wait => {
-					let item = try_stream!(self.logs.poll());
+					let item = match try_stream!(self.logs.poll()) {
+						LogStreamEvent::Logs(item) => item,
+						LogStreamEvent::Reorg { from, to } => {
+							warn!("foreign blocks {}..{} have been reorganized, signed withdraws will be relayed again", from, to);
+							// move the checkpoint back so it's persisted before the range is rescanned
+							self.state = WithdrawRelayState::Yield(Some(from - 1));
+							continue;
+						},
+					};
 					info!("got {} new signed withdraws to relay", item.logs.len());
+					let app = &self.app;
+					let to = item.to;
 					let assignments = item.logs
 						.into_iter()
 						.map(|log| {
-							 info!("collected signature is ready for relay: tx hash: {}", log.transaction_hash.unwrap());
-							 signatures_payload(
-								&self.app.foreign_bridge,
-								self.app.config.authorities.required_signatures,
-								self.app.config.foreign.account,
-								log)
+							 let source = log.transaction_hash.expect("log to be mined and contain `transaction_hash`");
+							 info!("collected signature is ready for relay: tx hash: {}", source);
+							 if let Some(Relay { status: RelayStatus::Mined, .. }) = app.journal.relay(RelayKind::WithdrawRelay, source) {
+								info!("withdraw {:?} has already been relayed", source);
+								return Ok(None);
+							 }
+							 let block_number = log.block_number.map(|number| number.low_u64()).unwrap_or(to);
+							 let assignment = signatures_payload(
+								&app.foreign_bridge,
+								app.config.authorities.required_signatures,
+								app.config.foreign.account,
+								log)?;
+							 Ok(assignment.map(|assignment| ((source, block_number), assignment)))
 						})
 						.collect::<error::Result<Vec<_>>>()?;
 
-					let (signatures, messages): (Vec<_>, Vec<_>) = assignments.into_iter()
+					let (sources, assignments): (Vec<_>, Vec<_>) = assignments.into_iter()
 						.filter_map(|a| a)
+						.unzip();
+
+					let (signatures, messages): (Vec<_>, Vec<_>) = assignments.into_iter()
 						.map(|assignment| (assignment.signature_payloads, assignment.message_payload))
 						.unzip();
 
 					let message_calls = messages.into_iter()
-						.map(|payload| {
-							self.app.timer.timeout(
-								api::call(&self.app.connections.foreign, self.foreign_contract.clone(), payload),
-								self.app.config.foreign.request_timeout)
-						})
+						.map(|payload| self.call_foreign(payload))
 						.collect::<Vec<_>>();
 
 					let signature_calls = signatures.into_iter()
 						.map(|payloads| {
 							payloads.into_iter()
-								.map(|payload| {
-									self.app.timer.timeout(
-										api::call(&self.app.connections.foreign, self.foreign_contract.clone(), payload),
-										self.app.config.foreign.request_timeout)
-								})
+								.map(|payload| self.call_foreign(payload))
 								.collect::<Vec<_>>()
 						})
 						.map(|calls| join_all(calls))
@@ -150,10 +280,11 @@ impl<T: Transport> Stream for WithdrawRelay<T> {
 					info!("fetching messages and signatures");
 					WithdrawRelayState::FetchMessagesSignatures {
 						future: join_all(message_calls).join(join_all(signature_calls)),
+						sources,
 						block: item.to,
 					}
 				},
-				WithdrawRelayState::FetchMessagesSignatures { ref mut future, block } => {
+				WithdrawRelayState::FetchMessagesSignatures { ref mut future, ref mut sources, block } => {
 					let (messages_raw, signatures_raw) = try_ready!(future.poll());
 					info!("fetching messages and signatures complete");
 					assert_eq!(messages_raw.len(), signatures_raw.len());
@@ -187,7 +318,14 @@ impl<T: Transport> Stream for WithdrawRelay<T> {
 						)
 						.collect::<error::Result<Vec<_>>>()?;
 
-					let relays = messages.into_iter()
+					let withdrawn = messages.iter()
+						.map(|message| {
+							let hash = MessageToMainnet::from_bytes(message.0.as_slice()).sidenet_transaction_hash;
+							storage::home_withdraw(hash)
+						})
+						.collect::<Vec<_>>();
+
+					let requests = messages.into_iter()
 						.zip(signatures.into_iter())
 						.map(|(message, signatures)| {
 							let payload: Bytes = app.home_bridge.functions().withdraw().input(
@@ -195,32 +333,181 @@ impl<T: Transport> Stream for WithdrawRelay<T> {
 								signatures.iter().map(|x| x.r),
 								signatures.iter().map(|x| x.s),
 								message.clone().0).into();
-							let request = TransactionRequest {
+							TransactionRequest {
 								from: app.config.home.account,
 								to: Some(home_contract.clone()),
-								gas: Some(app.config.txs.withdraw_relay.gas.into()),
+								gas: Some(app.gas_limits.withdraw_relay()),
 								gas_price: Some(MessageToMainnet::from_bytes(message.0.as_slice()).mainnet_gas_price),
 								value: None,
 								data: Some(payload),
 								nonce: None,
 								condition: None,
-							};
-							app.timer.timeout(
-								api::send_transaction(&app.connections.home, request),
-								app.config.home.request_timeout)
+							}
+						})
+						.collect::<Vec<_>>();
+
+					let withdraws = requests.into_iter()
+						.zip(withdrawn.into_iter())
+						.zip(sources.drain(..))
+						.map(|((request, withdrawn), source)| {
+							let sent = app.journal.relay(RelayKind::WithdrawRelay, source.0).map(|relay| relay.destination);
+							Withdraw {
+								request,
+								withdrawn,
+								source,
+								sent,
+								nonce: None,
+								restored: sent.is_some(),
+							}
+						})
+						.collect();
+
+					simulate_withdraws(app, *home_contract, withdraws, block)
+				},
+				WithdrawRelayState::SimulateWithdraws { ref mut future, ref mut withdraws, block } => {
+					let results = try_ready!(future.poll());
+					let app = &self.app;
+					let mut pending = Vec::new();
+					for (withdraw, (processed, revert)) in withdraws.drain(..).zip(results.into_iter()) {
+						if processed {
+							info!("withdraw {:?} has already been executed on home, skipping", withdraw.source.0);
+							continue;
+						}
+						// a single withdraw can't stop the bridge
+						if let Some(revert) = revert {
+							error!("relay of withdraw {:?} would revert: {}, skipping", withdraw.source.0, revert);
+							app.status.record_error(format!("relay of withdraw {:?} would revert: {}", withdraw.source.0, revert));
+							metrics::skipped(RelayKind::WithdrawRelay, 1);
+							continue;
+						}
+						pending.push(withdraw);
+					}
+
+					let relays = pending.iter()
+						.map(|withdraw| match withdraw.sent {
+							Some(hash) => {
+								info!("withdraw {:?} has already been sent in {:?}, waiting for confirmation", withdraw.source.0, hash);
+								Either::B(future::ok(Some((hash, None))))
+							},
+							None => Either::A(send_relay(app.journal.clone(), RelayKind::WithdrawRelay, withdraw.source, nonce::send_transaction(
+								app.connections.home.clone(),
+								app.timer.clone(),
+								app.nonces.home.clone(),
+								app.signers.home.clone(),
+								withdraw.request.clone(),
+								app.config.home.request_timeout,
+							))),
 						})
 						.collect::<Vec<_>>();
 
 					info!("relaying {} withdraws", relays.len());
 					WithdrawRelayState::RelayWithdraws {
 						future: join_all(relays),
+						withdraws: pending,
 						block,
 					}
 				},
-				WithdrawRelayState::RelayWithdraws { ref mut future, block } => {
-					let _ = try_ready!(future.poll());
-					info!("relaying withdraws complete");
-					WithdrawRelayState::Yield(Some(block))
+				WithdrawRelayState::RelayWithdraws { ref mut future, ref mut withdraws, block } => {
+					let hashes = try_ready!(future.poll());
+					let mut sent = Vec::new();
+					let mut failed = Vec::new();
+					for (mut withdraw, hash) in withdraws.drain(..).zip(hashes.into_iter()) {
+						match hash {
+							Some((hash, nonce)) => {
+								withdraw.sent = Some(hash);
+								withdraw.nonce = nonce;
+								sent.push(withdraw);
+							},
+							// a single relay which can't be sent doesn't stop the others
+							None => failed.push(withdraw),
+						}
+					}
+
+					info!("waiting for {} withdraw relays to be confirmed", sent.len());
+					let app = &self.app;
+					let pending = sent.iter()
+						.map(|withdraw| {
+							let hash = withdraw.sent.expect("only sent withdraws are confirmed; qed");
+							confirm_relay(app.journal.clone(), withdraw.relay(hash, RelayStatus::Sent), pending_transaction(app.connections.home.clone(), app.timer.clone(), PendingTransactionInit {
+								hash,
+								request: withdraw.request.clone(),
+								request_timeout: app.config.home.request_timeout,
+								poll_interval: app.config.home.poll_interval,
+								confirmations: app.config.home.required_confirmations,
+								// `HomeBridge.withdraw` requires the gas price of the message, a replacement at a higher gas price would revert
+								resubmission: None,
+								signer: app.signers.home.clone(),
+								retry: app.config.retry.clone(),
+							}))
+						})
+						.collect::<Vec<_>>();
+
+					WithdrawRelayState::ConfirmWithdraws {
+						future: join_all(pending),
+						withdraws: sent,
+						failed,
+						block,
+					}
+				},
+				WithdrawRelayState::ConfirmWithdraws { ref mut future, ref mut withdraws, ref mut failed, block } => {
+					let outcomes = try_ready!(future.poll());
+					let app = &self.app;
+					let mut mined = Vec::new();
+					let mut unsent = Vec::new();
+					for (withdraw, outcome) in withdraws.drain(..).zip(outcomes.into_iter()) {
+						match outcome {
+							Outcome::Mined(receipt) => mined.push(JournalEntry::Relay(withdraw.relay(receipt.transaction_hash, RelayStatus::Mined))),
+							// transaction sent before restart may have failed for reasons which no longer hold
+							Outcome::Failed(hash) if withdraw.restored => {
+								warn!("relay of withdraw {:?} sent before restart in transaction {:?} failed, checking it again", withdraw.source.0, hash);
+								unsent.push(Withdraw {
+									sent: None,
+									restored: false,
+									..withdraw
+								});
+							},
+							Outcome::Failed(hash) => {
+								error!("relay of withdraw {:?} in transaction {:?} failed, skipping", withdraw.source.0, hash);
+								app.status.record_error(format!("relay of withdraw {:?} in transaction {:?} failed", withdraw.source.0, hash));
+								metrics::skipped(RelayKind::WithdrawRelay, 1);
+							},
+							Outcome::Dropped(hash) => {
+								warn!("relay of withdraw {:?} in transaction {:?} has been dropped, relaying it again", withdraw.source.0, hash);
+								// nonce of a dropped transaction is free again
+								if let Some(nonce) = withdraw.nonce {
+									app.nonces.home.release(nonce);
+								}
+								unsent.push(Withdraw {
+									sent: None,
+									nonce: None,
+									restored: false,
+									..withdraw
+								});
+							},
+						}
+					}
+
+					let relays_count = mined.len();
+					app.journal.record(mined)?;
+					metrics::relayed(RelayKind::WithdrawRelay, relays_count);
+					if !unsent.is_empty() {
+						unsent.extend(failed.drain(..));
+						simulate_withdraws(app, self.home_contract, unsent, block)
+					} else if !failed.is_empty() {
+						WithdrawRelayState::WaitForRetry {
+							future: app.timer.sleep(app.config.home.poll_interval),
+							withdraws: failed.drain(..).collect(),
+							block,
+						}
+					} else {
+						info!("relaying withdraws complete");
+						WithdrawRelayState::Yield(Some(block))
+					}
+				},
+				WithdrawRelayState::WaitForRetry { ref mut future, ref mut withdraws, block } => {
+					try_ready!(future.poll());
+					let withdraws = withdraws.drain(..).collect();
+					simulate_withdraws(&self.app, self.home_contract, withdraws, block)
 				},
 				WithdrawRelayState::Yield(ref mut block) => match block.take() {
 					None => {
@@ -231,6 +518,7 @@ impl<T: Transport> Stream for WithdrawRelay<T> {
 				}
 			};
 			self.state = next_state;
+			self.app.status.stream(RelayKind::WithdrawRelay, self.state.name());
 		}
 	}
 }
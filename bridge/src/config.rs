@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::path::{PathBuf, Path};
-use std::fs;
+use std::{cmp, fs};
 use std::io::Read;
 use std::time::Duration;
 use rustc_hex::FromHex;
-use web3::types::{Address, Bytes};
+use web3::types::{Address, Bytes, U256};
 use error::{ResultExt, Error};
 use {toml};
 
@@ -11,6 +13,18 @@ const DEFAULT_POLL_INTERVAL: u64 = 1;
 const DEFAULT_CONFIRMATIONS: usize = 12;
 const DEFAULT_TIMEOUT: u64 = 5;
 const DEFAULT_RPC_PORT: u16 = 8545;
+const DEFAULT_REORG_DEPTH: usize = 100;
+const DEFAULT_QUORUM: usize = 1;
+const DEFAULT_MAX_LAG: u64 = 5;
+const DEFAULT_GAS_PRICE_MULTIPLIER: f64 = 1.2;
+const DEFAULT_RETRY_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY: u64 = 1;
+const DEFAULT_RETRY_MAX_DELAY: u64 = 30;
+const DEFAULT_GAS_PRICE_UPDATE_INTERVAL: u64 = 60;
+const DEFAULT_GAS_PRICE_PERCENTILE: u8 = 60;
+const DEFAULT_GAS_PRICE_BLOCKS: u64 = 20;
+const DEFAULT_GAS_PRICE_ORACLE_FIELD: &str = "fast";
+const DEFAULT_GAS_PRICE_ORACLE_MULTIPLIER: f64 = 1_000_000_000.0;
 
 /// Application config.
 #[derive(Debug, PartialEq, Clone)]
@@ -21,6 +35,9 @@ pub struct Config {
 	pub txs: Transactions,
 	pub estimated_gas_cost_of_withdraw: u32,
 	pub keystore: PathBuf,
+	pub metrics: Option<Metrics>,
+	pub status: Option<Status>,
+	pub retry: Retry,
 }
 
 impl Config {
@@ -44,9 +61,19 @@ impl Config {
 				accounts: config.authorities.accounts,
 				required_signatures: config.authorities.required_signatures,
 			},
-			txs: config.transactions.map(Transactions::from_load_struct).unwrap_or_default(),
+			txs: match config.transactions {
+				Some(txs) => Transactions::from_load_struct(txs)?,
+				None => Transactions::default(),
+			},
 			estimated_gas_cost_of_withdraw: config.estimated_gas_cost_of_withdraw,
 			keystore: config.keystore,
+			metrics: config.metrics.map(|metrics| Metrics {
+				address: metrics.address,
+			}),
+			status: config.status.map(|status| Status {
+				address: status.address,
+			}),
+			retry: config.retry.map(Retry::from_load_struct).unwrap_or_default(),
 		};
 
 		Ok(result)
@@ -57,17 +84,48 @@ impl Config {
 pub struct Node {
 	pub account: Address,
 	pub contract: ContractConfig,
-	pub ipc: PathBuf,
+	/// Redundant endpoints of the chain. The bridge fails over to the next one
+	/// if the current endpoint is disconnected or falls behind.
+	pub endpoints: Vec<TransportConfig>,
+	/// Number of endpoints which must return the same logs before they are relayed.
+	pub quorum: usize,
+	/// Number of blocks the current endpoint may fall behind the best endpoint before the bridge fails over.
+	pub max_lag: u64,
 	pub request_timeout: Duration,
 	pub poll_interval: Duration,
 	pub required_confirmations: usize,
-	pub rpc_host: String,
-	pub rpc_port: u16,
+	pub reorg_depth: usize,
+	/// Maximum number of blocks whose logs are fetched with a single request. `None` means no limit.
+	pub max_block_range: Option<u64>,
 	pub password: PathBuf,
+	/// Chain id used to sign transactions locally.
+	pub chain_id: Option<u64>,
+	pub signer: SignerConfig,
+	/// Gas price of transactions sent to the chain.
+	pub gas_price: GasPriceConfig,
 }
 
 impl Node {
 	fn from_load_struct(node: load::Node) -> Result<Node, Error> {
+		let endpoints = match node.endpoints {
+			Some(endpoints) => {
+				if node.transport.is_some() || node.ipc.is_some() || node.rpc_host.is_some() || node.ws_url.is_some() {
+					return Err("`endpoints` can't be used together with `transport`, `ipc`, `rpc_host` or `ws_url`".into());
+				}
+				endpoints.iter().map(|endpoint| TransportConfig::from_endpoint(endpoint)).collect()
+			},
+			None => vec![TransportConfig::from_load_struct(node.transport, node.ipc, node.rpc_host, node.rpc_port, node.ws_url)?],
+		};
+
+		let quorum = node.quorum.unwrap_or(DEFAULT_QUORUM);
+		if quorum == 0 || quorum > endpoints.len() {
+			return Err(format!("`quorum` must be between 1 and the number of endpoints ({}), got {}", endpoints.len(), quorum).into());
+		}
+
+		if node.max_block_range == Some(0) {
+			return Err("`max_block_range` must be greater than 0".into());
+		}
+
 		let result = Node {
 			account: node.account,
 			contract: ContractConfig {
@@ -78,17 +136,187 @@ impl Node {
 					Bytes(read.from_hex()?)
 				}
 			},
-			ipc: node.ipc,
+			endpoints,
+			quorum,
+			max_lag: node.max_lag.unwrap_or(DEFAULT_MAX_LAG),
 			request_timeout: Duration::from_secs(node.request_timeout.unwrap_or(DEFAULT_TIMEOUT)),
 			poll_interval: Duration::from_secs(node.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL)),
 			required_confirmations: node.required_confirmations.unwrap_or(DEFAULT_CONFIRMATIONS),
-			rpc_host: node.rpc_host.unwrap(),
-			rpc_port: node.rpc_port.unwrap_or(DEFAULT_RPC_PORT),
+			reorg_depth: node.reorg_depth.unwrap_or(DEFAULT_REORG_DEPTH),
+			max_block_range: node.max_block_range,
+			signer: match node.signer {
+				Some(load::SignerSetting::Name(load::SignerName::Keystore)) |
+				Some(load::SignerSetting::Table(load::Signer::Keystore)) => SignerConfig::Keystore,
+				Some(load::SignerSetting::Table(load::Signer::Remote { url })) => SignerConfig::Remote { url },
+				// the account is unlocked on the node unless the keystore or a remote signer is chosen explicitly
+				Some(load::SignerSetting::Name(load::SignerName::Node)) |
+				Some(load::SignerSetting::Table(load::Signer::Node)) |
+				None => SignerConfig::Node,
+			},
 			password: node.password,
+			chain_id: node.chain_id,
+			gas_price: node.gas_price.map(GasPriceConfig::from_load_struct).unwrap_or_else(|| Ok(GasPriceConfig::default()))?,
+		};
+
+		Ok(result)
+	}
+}
+
+/// Connection to the node.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TransportConfig {
+	/// Path to the ipc socket.
+	Ipc(PathBuf),
+	/// Url of the JSON-RPC http server.
+	Http(String),
+	/// Url of the JSON-RPC websocket server. New blocks are pushed by the node.
+	Ws(String),
+}
+
+impl TransportConfig {
+	fn from_load_struct(transport: Option<load::Transport>, ipc: Option<PathBuf>, rpc_host: Option<String>, rpc_port: Option<u16>, ws_url: Option<String>) -> Result<TransportConfig, Error> {
+		let http = |host: String| TransportConfig::Http(format!("{}:{}", host, rpc_port.unwrap_or(DEFAULT_RPC_PORT)));
+		let result = match transport {
+			Some(load::Transport::Ipc) => TransportConfig::Ipc(ipc.ok_or("`ipc` is required by the ipc transport")?),
+			Some(load::Transport::Http) => http(rpc_host.ok_or("`rpc_host` is required by the http transport")?),
+			Some(load::Transport::Ws) => TransportConfig::Ws(ws_url.ok_or("`ws_url` is required by the ws transport")?),
+			// nodes with `rpc_host` were connected over http before the transport was configurable
+			None => match (ws_url, rpc_host) {
+				(Some(url), _) => TransportConfig::Ws(url),
+				(None, Some(ref host)) if !host.is_empty() => http(host.clone()),
+				_ => TransportConfig::Ipc(ipc.unwrap_or_default()),
+			},
 		};
 
 		Ok(result)
 	}
+
+	/// Transport of the endpoint is chosen by the scheme of its url. Endpoints without a scheme are ipc paths.
+	fn from_endpoint(endpoint: &str) -> TransportConfig {
+		if endpoint.starts_with("http://") || endpoint.starts_with("https://") {
+			TransportConfig::Http(endpoint.to_owned())
+		} else if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+			TransportConfig::Ws(endpoint.to_owned())
+		} else {
+			TransportConfig::Ipc(endpoint.into())
+		}
+	}
+}
+
+/// Where messages and transactions of the node account are signed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SignerConfig {
+	/// Account is unlocked on the node.
+	Node,
+	/// Key is loaded from the keystore and decrypted with the node password.
+	Keystore,
+	/// Remote JSON-RPC signer, e.g. clef.
+	Remote {
+		url: String,
+	},
+}
+
+/// Gas price of transactions sent to a chain.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GasPriceConfig {
+	pub strategy: GasPriceStrategy,
+	/// Gas prices lower than this are raised to it.
+	pub min: Option<u64>,
+	/// Gas prices higher than this are lowered to it.
+	pub max: Option<u64>,
+	/// How often the gas price is fetched by strategies other than `Fixed`.
+	pub update_interval: Duration,
+}
+
+impl Default for GasPriceConfig {
+	fn default() -> Self {
+		GasPriceConfig {
+			strategy: GasPriceStrategy::Fixed,
+			min: None,
+			max: None,
+			update_interval: Duration::from_secs(DEFAULT_GAS_PRICE_UPDATE_INTERVAL),
+		}
+	}
+}
+
+impl GasPriceConfig {
+	fn from_load_struct(cfg: load::GasPrice) -> Result<Self, Error> {
+		let strategy = match cfg.strategy {
+			load::GasPriceStrategy::Fixed => GasPriceStrategy::Fixed,
+			load::GasPriceStrategy::Node => GasPriceStrategy::Node,
+			load::GasPriceStrategy::Percentile => {
+				let percentile = cfg.percentile.unwrap_or(DEFAULT_GAS_PRICE_PERCENTILE);
+				if percentile > 100 {
+					return Err(format!("gas price `percentile` must be between 0 and 100, got {}", percentile).into());
+				}
+				let blocks = cfg.blocks.unwrap_or(DEFAULT_GAS_PRICE_BLOCKS);
+				if blocks == 0 {
+					return Err("gas price `blocks` must be greater than 0".into());
+				}
+				GasPriceStrategy::Percentile { percentile, blocks }
+			},
+			load::GasPriceStrategy::Oracle => {
+				let url = cfg.url.ok_or("`url` is required by the gas price oracle")?;
+				// the bridge is built without tls support
+				if !url.starts_with("http://") {
+					return Err(format!("`url` of the gas price oracle must be an http:// url, got {}", url).into());
+				}
+				GasPriceStrategy::Oracle {
+					url,
+					field: cfg.field.unwrap_or_else(|| DEFAULT_GAS_PRICE_ORACLE_FIELD.to_owned()),
+					multiplier: cfg.multiplier.unwrap_or(DEFAULT_GAS_PRICE_ORACLE_MULTIPLIER),
+				}
+			},
+		};
+
+		if let (Some(min), Some(max)) = (cfg.min, cfg.max) {
+			if min > max {
+				return Err(format!("gas price `min` ({}) must not be greater than `max` ({})", min, max).into());
+			}
+		}
+
+		let result = GasPriceConfig {
+			strategy,
+			min: cfg.min,
+			max: cfg.max,
+			update_interval: Duration::from_secs(cfg.update_interval.unwrap_or(DEFAULT_GAS_PRICE_UPDATE_INTERVAL)),
+		};
+
+		Ok(result)
+	}
+
+	/// Returns `gas_price` limited to `min` and `max`.
+	pub fn clamp(&self, gas_price: U256) -> U256 {
+		let gas_price = match self.min {
+			Some(min) => cmp::max(gas_price, min.into()),
+			None => gas_price,
+		};
+		match self.max {
+			Some(max) => cmp::min(gas_price, max.into()),
+			None => gas_price,
+		}
+	}
+}
+
+/// Where the gas price of transactions comes from.
+#[derive(Debug, PartialEq, Clone)]
+pub enum GasPriceStrategy {
+	/// `gas_price` of the transaction config.
+	Fixed,
+	/// `eth_gasPrice` of the node.
+	Node,
+	/// Percentile of gas prices of transactions in the latest `blocks` blocks.
+	Percentile {
+		percentile: u8,
+		blocks: u64,
+	},
+	/// Number `field` of the JSON object returned by `url`, multiplied by `multiplier`.
+	Oracle {
+		url: String,
+		/// Path to the number, nested fields are separated with dots.
+		field: String,
+		multiplier: f64,
+	},
 }
 
 #[derive(Debug, PartialEq, Default, Clone)]
@@ -98,17 +326,69 @@ pub struct Transactions {
 	pub deposit_relay: TransactionConfig,
 	pub withdraw_confirm: TransactionConfig,
 	pub withdraw_relay: TransactionConfig,
+	pub set_token_address: TransactionConfig,
+	pub resubmission: Option<Resubmission>,
 }
 
 impl Transactions {
-	fn from_load_struct(cfg: load::Transactions) -> Self {
-		Transactions {
+	fn from_load_struct(cfg: load::Transactions) -> Result<Self, Error> {
+		let result = Transactions {
 			home_deploy: cfg.home_deploy.map(TransactionConfig::from_load_struct).unwrap_or_default(),
 			foreign_deploy: cfg.foreign_deploy.map(TransactionConfig::from_load_struct).unwrap_or_default(),
 			deposit_relay: cfg.deposit_relay.map(TransactionConfig::from_load_struct).unwrap_or_default(),
 			withdraw_confirm: cfg.withdraw_confirm.map(TransactionConfig::from_load_struct).unwrap_or_default(),
 			withdraw_relay: cfg.withdraw_relay.map(TransactionConfig::from_load_struct).unwrap_or_default(),
+			set_token_address: cfg.set_token_address.map(TransactionConfig::from_load_struct).unwrap_or_default(),
+			resubmission: match cfg.resubmission {
+				Some(resubmission) => Some(Resubmission::from_load_struct(resubmission)?),
+				None => None,
+			},
+		};
+
+		Ok(result)
+	}
+}
+
+/// Policy of resubmitting relay transactions which are not mined in time.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Resubmission {
+	/// How long to wait for a transaction to be mined before it's resubmitted.
+	pub timeout: Duration,
+	/// Gas price of the replacement is gas price of the previous transaction multiplied by this value.
+	pub gas_price_multiplier: f64,
+	/// Transactions are never resubmitted with a gas price higher than this.
+	pub max_gas_price: u64,
+}
+
+impl Resubmission {
+	fn from_load_struct(cfg: load::Resubmission) -> Result<Self, Error> {
+		let gas_price_multiplier = cfg.gas_price_multiplier.unwrap_or(DEFAULT_GAS_PRICE_MULTIPLIER);
+		// also rejects NaN
+		if !(gas_price_multiplier > 1.0) {
+			return Err(format!("`gas_price_multiplier` must be greater than 1, got {}", gas_price_multiplier).into());
 		}
+
+		let result = Resubmission {
+			timeout: Duration::from_secs(cfg.timeout),
+			gas_price_multiplier,
+			max_gas_price: cfg.max_gas_price,
+		};
+		Ok(result)
+	}
+
+	/// Returns gas price of the replacement of a transaction sent with `gas_price`,
+	/// or `None` if the gas price already reached `max_gas_price`.
+	pub fn next_gas_price(&self, gas_price: U256) -> Option<U256> {
+		let max_gas_price = U256::from(self.max_gas_price);
+		if gas_price >= max_gas_price {
+			return None;
+		}
+
+		let gas_price = gas_price.low_u64();
+		let next = (gas_price as f64 * self.gas_price_multiplier) as u64;
+		// make sure that zero or tiny gas prices are increased as well
+		let next = cmp::max(next, gas_price + 1);
+		Some(cmp::min(U256::from(next), max_gas_price))
 	}
 }
 
@@ -138,10 +418,95 @@ pub struct Authorities {
 	pub required_signatures: u32,
 }
 
+/// Prometheus metrics server.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Metrics {
+	/// Address the server listens on.
+	pub address: SocketAddr,
+}
+
+/// Health and status server.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Status {
+	/// Address the server listens on.
+	pub address: SocketAddr,
+}
+
+/// How many times and how often a request which timed out or lost the connection is sent again.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RetryPolicy {
+	/// Number of retries after the first attempt. `0` disables retries.
+	pub attempts: u32,
+	/// Delay before the first retry. Doubled after every failed retry.
+	pub delay: Duration,
+	/// Maximum delay between retries.
+	pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		RetryPolicy {
+			attempts: DEFAULT_RETRY_ATTEMPTS,
+			delay: Duration::from_secs(DEFAULT_RETRY_DELAY),
+			max_delay: Duration::from_secs(DEFAULT_RETRY_MAX_DELAY),
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// Fields missing in `cfg` are taken from `default`.
+	fn from_load_struct(cfg: load::RetryPolicy, default: &RetryPolicy) -> Self {
+		RetryPolicy {
+			attempts: cfg.attempts.unwrap_or(default.attempts),
+			delay: cfg.delay.map(Duration::from_secs).unwrap_or(default.delay),
+			max_delay: cfg.max_delay.map(Duration::from_secs).unwrap_or(default.max_delay),
+		}
+	}
+
+	/// Delay before the `retry`th retry, counted from 1.
+	pub fn delay(&self, retry: u32) -> Duration {
+		let multiplier = 1u32.checked_shl(retry.saturating_sub(1)).unwrap_or(u32::max_value());
+		cmp::min(self.delay.checked_mul(multiplier).unwrap_or(self.max_delay), self.max_delay)
+	}
+}
+
+/// Retry policies of requests which timed out or lost the connection to the node.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Retry {
+	/// Policy of methods without their own policy.
+	pub default: RetryPolicy,
+	/// Policies by JSON-RPC method, e.g. `eth_getLogs`.
+	pub methods: BTreeMap<String, RetryPolicy>,
+}
+
+impl Retry {
+	fn from_load_struct(cfg: load::Retry) -> Self {
+		let default = RetryPolicy::from_load_struct(load::RetryPolicy {
+			attempts: cfg.attempts,
+			delay: cfg.delay,
+			max_delay: cfg.max_delay,
+		}, &RetryPolicy::default());
+
+		Retry {
+			methods: cfg.methods.unwrap_or_default().into_iter()
+				.map(|(method, policy)| (method, RetryPolicy::from_load_struct(policy, &default)))
+				.collect(),
+			default,
+		}
+	}
+
+	/// Policy of the JSON-RPC `method`.
+	pub fn policy(&self, method: &str) -> &RetryPolicy {
+		self.methods.get(method).unwrap_or(&self.default)
+	}
+}
+
 /// Some config values may not be defined in `toml` file, but they should be specified at runtime.
 /// `load` module separates `Config` representation in file with optional from the one used
 /// in application.
 mod load {
+	use std::collections::BTreeMap;
+	use std::net::SocketAddr;
 	use std::path::PathBuf;
 	use web3::types::Address;
 
@@ -154,6 +519,9 @@ mod load {
 		pub transactions: Option<Transactions>,
 		pub estimated_gas_cost_of_withdraw: u32,
 		pub keystore: PathBuf,
+		pub metrics: Option<Metrics>,
+		pub status: Option<Status>,
+		pub retry: Option<Retry>,
 	}
 
 	#[derive(Deserialize)]
@@ -161,13 +529,80 @@ mod load {
 	pub struct Node {
 		pub account: Address,
 		pub contract: ContractConfig,
-		pub ipc: PathBuf,
+		pub transport: Option<Transport>,
+		pub ipc: Option<PathBuf>,
 		pub request_timeout: Option<u64>,
 		pub poll_interval: Option<u64>,
 		pub required_confirmations: Option<usize>,
+		pub reorg_depth: Option<usize>,
+		pub max_block_range: Option<u64>,
 		pub rpc_host: Option<String>,
 		pub rpc_port: Option<u16>,
+		pub ws_url: Option<String>,
+		pub endpoints: Option<Vec<String>>,
+		pub quorum: Option<usize>,
+		pub max_lag: Option<u64>,
 		pub password: PathBuf,
+		pub chain_id: Option<u64>,
+		pub signer: Option<SignerSetting>,
+		pub gas_price: Option<GasPrice>,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct GasPrice {
+		pub strategy: GasPriceStrategy,
+		pub min: Option<u64>,
+		pub max: Option<u64>,
+		pub update_interval: Option<u64>,
+		pub percentile: Option<u8>,
+		pub blocks: Option<u64>,
+		pub url: Option<String>,
+		pub field: Option<String>,
+		pub multiplier: Option<f64>,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(rename_all = "snake_case")]
+	pub enum GasPriceStrategy {
+		Fixed,
+		Node,
+		Percentile,
+		Oracle,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(rename_all = "snake_case")]
+	pub enum Transport {
+		Ipc,
+		Http,
+		Ws,
+	}
+
+	/// Either `signer = "<name>"` or a `[signer]` table.
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	pub enum SignerSetting {
+		Name(SignerName),
+		Table(Signer),
+	}
+
+	/// Signers which don't need any options.
+	#[derive(Deserialize)]
+	#[serde(rename_all = "snake_case")]
+	pub enum SignerName {
+		Node,
+		Keystore,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+	pub enum Signer {
+		Node,
+		Keystore,
+		Remote {
+			url: String,
+		},
 	}
 
 	#[derive(Deserialize)]
@@ -178,6 +613,16 @@ mod load {
 		pub deposit_relay: Option<TransactionConfig>,
 		pub withdraw_confirm: Option<TransactionConfig>,
 		pub withdraw_relay: Option<TransactionConfig>,
+		pub set_token_address: Option<TransactionConfig>,
+		pub resubmission: Option<Resubmission>,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct Resubmission {
+		pub timeout: u64,
+		pub gas_price_multiplier: Option<f64>,
+		pub max_gas_price: u64,
 	}
 
 	#[derive(Deserialize)]
@@ -199,13 +644,42 @@ mod load {
 		pub accounts: Vec<Address>,
 		pub required_signatures: u32,
 	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct Metrics {
+		pub address: SocketAddr,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct Status {
+		pub address: SocketAddr,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct Retry {
+		pub attempts: Option<u32>,
+		pub delay: Option<u64>,
+		pub max_delay: Option<u64>,
+		pub methods: Option<BTreeMap<String, RetryPolicy>>,
+	}
+
+	#[derive(Deserialize)]
+	#[serde(deny_unknown_fields)]
+	pub struct RetryPolicy {
+		pub attempts: Option<u32>,
+		pub delay: Option<u64>,
+		pub max_delay: Option<u64>,
+	}
 }
 
 #[cfg(test)]
 mod tests {
 	use std::time::Duration;
 	use rustc_hex::FromHex;
-	use super::{Config, Node, ContractConfig, Transactions, Authorities, TransactionConfig};
+	use super::{Config, Node, ContractConfig, Transactions, Authorities, TransactionConfig, Resubmission, SignerConfig, TransportConfig, Metrics, Status, Retry, RetryPolicy, GasPriceConfig, GasPriceStrategy};
 
 	#[test]
 	fn load_full_setup_from_str() {
@@ -219,9 +693,13 @@ account = "0x1B68Cb0B50181FC4006Ce572cF346e596E51818b"
 ipc = "/home.ipc"
 poll_interval = 2
 required_confirmations = 100
+reorg_depth = 0
+max_block_range = 1000
 rpc_host = "127.0.0.1"
 rpc_port = 8545
 password = "/password.txt"
+chain_id = 77
+signer = "keystore"
 
 [home.contract]
 bin = "../compiled_contracts/HomeBridge.bin"
@@ -232,6 +710,17 @@ ipc = "/foreign.ipc"
 rpc_host = "127.0.0.1"
 rpc_port = 8545
 password = "/password.txt"
+chain_id = 42
+
+[foreign.signer]
+type = "remote"
+url = "http://127.0.0.1:8550"
+
+[foreign.gas_price]
+strategy = "percentile"
+blocks = 10
+min = 1000000000
+max = 50000000000
 
 [foreign.contract]
 bin = "../compiled_contracts/ForeignBridge.bin"
@@ -246,35 +735,72 @@ required_signatures = 2
 
 [transactions]
 home_deploy = { gas = 20 }
+
+[transactions.resubmission]
+timeout = 60
+max_gas_price = 100000000000
+
+[metrics]
+address = "127.0.0.1:9545"
+
+[status]
+address = "127.0.0.1:9546"
+
+[retry]
+attempts = 5
+
+[retry.methods.eth_getLogs]
+attempts = 10
+max_delay = 60
 "#;
 
 		let mut expected = Config {
 			txs: Transactions::default(),
 			home: Node {
 				account: "1B68Cb0B50181FC4006Ce572cF346e596E51818b".into(),
-				ipc: "/home.ipc".into(),
+				endpoints: vec![TransportConfig::Http("127.0.0.1:8545".into())],
+				quorum: 1,
+				max_lag: 5,
 				contract: ContractConfig {
 					bin: include_str!("../../compiled_contracts/HomeBridge.bin").from_hex().unwrap().into(),
 				},
 				poll_interval: Duration::from_secs(2),
 				request_timeout: Duration::from_secs(5),
 				required_confirmations: 100,
-				rpc_host: "127.0.0.1".into(),
-				rpc_port: 8545,
-				password: "/password.txt".into()
+				reorg_depth: 0,
+				max_block_range: Some(1000),
+				password: "/password.txt".into(),
+				chain_id: Some(77),
+				signer: SignerConfig::Keystore,
+				gas_price: GasPriceConfig::default(),
 			},
 			foreign: Node {
 				account: "0000000000000000000000000000000000000001".into(),
 				contract: ContractConfig {
 					bin: include_str!("../../compiled_contracts/ForeignBridge.bin").from_hex().unwrap().into(),
 				},
-				ipc: "/foreign.ipc".into(),
+				endpoints: vec![TransportConfig::Http("127.0.0.1:8545".into())],
+				quorum: 1,
+				max_lag: 5,
 				poll_interval: Duration::from_secs(1),
 				request_timeout: Duration::from_secs(5),
 				required_confirmations: 12,
-				rpc_host: "127.0.0.1".into(),
-				rpc_port: 8545,
-				password: "/password.txt".into()
+				reorg_depth: 100,
+				max_block_range: None,
+				password: "/password.txt".into(),
+				chain_id: Some(42),
+				signer: SignerConfig::Remote {
+					url: "http://127.0.0.1:8550".into(),
+				},
+				gas_price: GasPriceConfig {
+					strategy: GasPriceStrategy::Percentile {
+						percentile: 60,
+						blocks: 10,
+					},
+					min: Some(1_000_000_000),
+					max: Some(50_000_000_000),
+					update_interval: Duration::from_secs(60),
+				},
 			},
 			authorities: Authorities {
 				accounts: vec![
@@ -286,12 +812,35 @@ home_deploy = { gas = 20 }
 			},
 			estimated_gas_cost_of_withdraw: 100_000,
 			keystore: "/keys/".into(),
+			metrics: Some(Metrics {
+				address: "127.0.0.1:9545".parse().unwrap(),
+			}),
+			status: Some(Status {
+				address: "127.0.0.1:9546".parse().unwrap(),
+			}),
+			retry: Retry {
+				default: RetryPolicy {
+					attempts: 5,
+					delay: Duration::from_secs(1),
+					max_delay: Duration::from_secs(30),
+				},
+				methods: vec![("eth_getLogs".to_owned(), RetryPolicy {
+					attempts: 10,
+					delay: Duration::from_secs(1),
+					max_delay: Duration::from_secs(60),
+				})].into_iter().collect(),
+			},
 		};
 
 		expected.txs.home_deploy = TransactionConfig {
 			gas: 20,
 			gas_price: 0,
 		};
+		expected.txs.resubmission = Some(Resubmission {
+			timeout: Duration::from_secs(60),
+			gas_price_multiplier: 1.2,
+			max_gas_price: 100_000_000_000,
+		});
 
 		let config = Config::load_from_str(toml).unwrap();
 		assert_eq!(expected, config);
@@ -334,29 +883,39 @@ required_signatures = 2
 			txs: Transactions::default(),
 			home: Node {
 				account: "1B68Cb0B50181FC4006Ce572cF346e596E51818b".into(),
-				ipc: "".into(),
+				endpoints: vec![TransportConfig::Ipc("".into())],
+				quorum: 1,
+				max_lag: 5,
 				contract: ContractConfig {
 					bin: include_str!("../../compiled_contracts/HomeBridge.bin").from_hex().unwrap().into(),
 				},
 				poll_interval: Duration::from_secs(1),
 				request_timeout: Duration::from_secs(5),
 				required_confirmations: 12,
-				rpc_host: "".into(),
-				rpc_port: 8545,
+				reorg_depth: 100,
+				max_block_range: None,
 				password: "".into(),
+				chain_id: None,
+				signer: SignerConfig::Node,
+				gas_price: GasPriceConfig::default(),
 			},
 			foreign: Node {
 				account: "0000000000000000000000000000000000000001".into(),
-				ipc: "".into(),
+				endpoints: vec![TransportConfig::Ipc("".into())],
+				quorum: 1,
+				max_lag: 5,
 				contract: ContractConfig {
 					bin: include_str!("../../compiled_contracts/ForeignBridge.bin").from_hex().unwrap().into(),
 				},
 				poll_interval: Duration::from_secs(1),
 				request_timeout: Duration::from_secs(5),
 				required_confirmations: 12,
-				rpc_host: "".into(),
-				rpc_port: 8545,
+				reorg_depth: 100,
+				max_block_range: None,
 				password: "".into(),
+				chain_id: None,
+				signer: SignerConfig::Node,
+				gas_price: GasPriceConfig::default(),
 			},
 			authorities: Authorities {
 				accounts: vec![
@@ -368,9 +927,197 @@ required_signatures = 2
 			},
 			estimated_gas_cost_of_withdraw: 200_000_000,
 			keystore: "/keys/".into(),
+			metrics: None,
+			status: None,
+			retry: Retry::default(),
 		};
 
 		let config = Config::load_from_str(toml).unwrap();
 		assert_eq!(expected, config);
 	}
+
+	#[test]
+	fn keystore_signer_is_opt_in() {
+		let toml = |signer: &str| format!(r#"
+estimated_gas_cost_of_withdraw = 200000000
+
+keystore = "/keys/"
+
+[home]
+account = "0x1B68Cb0B50181FC4006Ce572cF346e596E51818b"
+ipc = "/home.ipc"
+password = "/password.txt"
+chain_id = 77
+{}
+
+[home.contract]
+bin = "../compiled_contracts/HomeBridge.bin"
+
+[foreign]
+account = "0x0000000000000000000000000000000000000001"
+ipc = "/foreign.ipc"
+password = "/password.txt"
+
+[foreign.contract]
+bin = "../compiled_contracts/ForeignBridge.bin"
+
+[authorities]
+accounts = ["0x0000000000000000000000000000000000000001"]
+required_signatures = 1
+"#, signer);
+
+		let config = Config::load_from_str(&toml("")).unwrap();
+		assert_eq!(SignerConfig::Node, config.home.signer);
+		assert_eq!(SignerConfig::Node, config.foreign.signer);
+
+		let config = Config::load_from_str(&toml(r#"signer = "keystore""#)).unwrap();
+		assert_eq!(SignerConfig::Keystore, config.home.signer);
+		assert_eq!(SignerConfig::Node, config.foreign.signer);
+
+		let config = Config::load_from_str(&toml("signer = { type = \"keystore\" }")).unwrap();
+		assert_eq!(SignerConfig::Keystore, config.home.signer);
+
+		assert!(Config::load_from_str(&toml(r#"signer = "clef""#)).is_err());
+	}
+
+	#[test]
+	fn transport_from_load_struct() {
+		use super::load::Transport;
+
+		let ipc = || Some("/home.ipc".into());
+		let host = || Some("http://127.0.0.1".to_owned());
+		assert_eq!(TransportConfig::Http("http://127.0.0.1:8545".into()), TransportConfig::from_load_struct(None, ipc(), host(), None, None).unwrap());
+		assert_eq!(TransportConfig::Http("http://127.0.0.1:8550".into()), TransportConfig::from_load_struct(None, None, host(), Some(8550), None).unwrap());
+		assert_eq!(TransportConfig::Ipc("/home.ipc".into()), TransportConfig::from_load_struct(None, ipc(), Some("".into()), None, None).unwrap());
+		assert_eq!(TransportConfig::Ipc("/home.ipc".into()), TransportConfig::from_load_struct(Some(Transport::Ipc), ipc(), host(), None, None).unwrap());
+		assert!(TransportConfig::from_load_struct(Some(Transport::Ipc), None, host(), None, None).is_err());
+		assert!(TransportConfig::from_load_struct(Some(Transport::Http), ipc(), None, None, None).is_err());
+		assert_eq!(TransportConfig::Ws("ws://127.0.0.1:8546".into()), TransportConfig::from_load_struct(None, ipc(), host(), None, Some("ws://127.0.0.1:8546".into())).unwrap());
+		assert!(TransportConfig::from_load_struct(Some(Transport::Ws), ipc(), host(), None, None).is_err());
+	}
+
+	#[test]
+	fn transport_from_endpoint() {
+		assert_eq!(TransportConfig::Http("https://node.example:8545".into()), TransportConfig::from_endpoint("https://node.example:8545"));
+		assert_eq!(TransportConfig::Ws("ws://127.0.0.1:8546".into()), TransportConfig::from_endpoint("ws://127.0.0.1:8546"));
+		assert_eq!(TransportConfig::Ipc("/home.ipc".into()), TransportConfig::from_endpoint("/home.ipc"));
+	}
+
+	#[test]
+	fn retry_policy_delay() {
+		let policy = RetryPolicy {
+			attempts: 10,
+			delay: Duration::from_secs(2),
+			max_delay: Duration::from_secs(20),
+		};
+
+		assert_eq!(Duration::from_secs(2), policy.delay(1));
+		assert_eq!(Duration::from_secs(4), policy.delay(2));
+		assert_eq!(Duration::from_secs(16), policy.delay(4));
+		assert_eq!(Duration::from_secs(20), policy.delay(5));
+		assert_eq!(Duration::from_secs(20), policy.delay(100));
+	}
+
+	#[test]
+	fn retry_policy_of_method() {
+		let retry = Retry {
+			default: RetryPolicy::default(),
+			methods: vec![("eth_getLogs".to_owned(), RetryPolicy {
+				attempts: 0,
+				..RetryPolicy::default()
+			})].into_iter().collect(),
+		};
+
+		assert_eq!(0, retry.policy("eth_getLogs").attempts);
+		assert_eq!(3, retry.policy("eth_call").attempts);
+	}
+
+	#[test]
+	fn resubmission_next_gas_price() {
+		let resubmission = Resubmission {
+			timeout: Duration::from_secs(60),
+			gas_price_multiplier: 1.5,
+			max_gas_price: 250,
+		};
+
+		assert_eq!(Some(1.into()), resubmission.next_gas_price(0.into()));
+		assert_eq!(Some(150.into()), resubmission.next_gas_price(100.into()));
+		assert_eq!(Some(225.into()), resubmission.next_gas_price(150.into()));
+		assert_eq!(Some(250.into()), resubmission.next_gas_price(225.into()));
+		assert_eq!(None, resubmission.next_gas_price(250.into()));
+	}
+
+	#[test]
+	fn resubmission_from_load_struct() {
+		use std::f64;
+		use super::load;
+
+		let resubmission = |gas_price_multiplier| Resubmission::from_load_struct(load::Resubmission {
+			timeout: 60,
+			gas_price_multiplier,
+			max_gas_price: 250,
+		});
+
+		assert_eq!(1.2, resubmission(None).unwrap().gas_price_multiplier);
+		assert_eq!(1.5, resubmission(Some(1.5)).unwrap().gas_price_multiplier);
+		assert!(resubmission(Some(1.0)).is_err());
+		assert!(resubmission(Some(0.5)).is_err());
+		assert!(resubmission(Some(-2.0)).is_err());
+		assert!(resubmission(Some(f64::NAN)).is_err());
+	}
+
+	#[test]
+	fn gas_price_from_load_struct() {
+		use super::load::{GasPrice, GasPriceStrategy as Strategy};
+
+		let gas_price = |strategy| GasPrice {
+			strategy,
+			min: None,
+			max: None,
+			update_interval: None,
+			percentile: None,
+			blocks: None,
+			url: None,
+			field: None,
+			multiplier: None,
+		};
+
+		assert_eq!(GasPriceStrategy::Node, GasPriceConfig::from_load_struct(gas_price(Strategy::Node)).unwrap().strategy);
+		assert_eq!(GasPriceStrategy::Oracle {
+			url: "http://gasprice.example/api".into(),
+			field: "fast".into(),
+			multiplier: 1_000_000_000.0,
+		}, GasPriceConfig::from_load_struct(GasPrice {
+			url: Some("http://gasprice.example/api".into()),
+			..gas_price(Strategy::Oracle)
+		}).unwrap().strategy);
+		assert!(GasPriceConfig::from_load_struct(gas_price(Strategy::Oracle)).is_err());
+		assert!(GasPriceConfig::from_load_struct(GasPrice {
+			url: Some("https://gasprice.example/api".into()),
+			..gas_price(Strategy::Oracle)
+		}).is_err());
+		assert!(GasPriceConfig::from_load_struct(GasPrice {
+			percentile: Some(101),
+			..gas_price(Strategy::Percentile)
+		}).is_err());
+		assert!(GasPriceConfig::from_load_struct(GasPrice {
+			min: Some(2),
+			max: Some(1),
+			..gas_price(Strategy::Fixed)
+		}).is_err());
+	}
+
+	#[test]
+	fn gas_price_clamp() {
+		let config = GasPriceConfig {
+			min: Some(10),
+			max: Some(20),
+			..GasPriceConfig::default()
+		};
+
+		assert_eq!(10, config.clamp(0.into()).low_u64());
+		assert_eq!(15, config.clamp(15.into()).low_u64());
+		assert_eq!(20, config.clamp(100.into()).low_u64());
+		assert_eq!(100, GasPriceConfig::default().clamp(100.into()).low_u64());
+	}
 }
@@ -1,3 +1,140 @@
 use_contract!(home, "HomeBridge", "../compiled_contracts/HomeBridge.abi");
 use_contract!(foreign, "ForeignBridge", "../compiled_contracts/ForeignBridge.abi");
 use_contract!(erc20, "ERC20", "../compiled_contracts/ERC20.abi");
+
+/// Runtime bytecode of `HomeBridge`, hex encoded.
+pub const HOME_BRIDGE_RUNTIME: &str = include_str!("../../compiled_contracts/HomeBridge.bin-runtime");
+/// Runtime bytecode of `ForeignBridge`, hex encoded.
+pub const FOREIGN_BRIDGE_RUNTIME: &str = include_str!("../../compiled_contracts/ForeignBridge.bin-runtime");
+
+/// Storage layout of the bridge contracts.
+///
+/// State without a getter is read with `eth_getStorageAt`, so it can be read from contracts
+/// deployed by any version of the bridge. Slots follow the declaration order in `contracts/bridge.sol`,
+/// including the storage of the inherited contracts.
+pub mod storage {
+	use tiny_keccak::keccak256;
+	use web3::types::{Address, H256, U256};
+
+	/// Slot of `HomeBridge.authorities`, which holds the length of the array.
+	const HOME_AUTHORITIES: u8 = 4;
+	/// Slot of `HomeBridge.withdraws`.
+	const HOME_WITHDRAWS: u8 = 5;
+	/// Slot of `ForeignBridge.authorities`.
+	const FOREIGN_AUTHORITIES: u8 = 6;
+	/// Slot of `ForeignBridge.messages_signed`.
+	const FOREIGN_MESSAGES_SIGNED: u8 = 9;
+	/// Slot of `ForeignBridge.deposits_signed`.
+	const FOREIGN_DEPOSITS_SIGNED: u8 = 11;
+	/// Slot of `ForeignBridge.tokenAddressAprroval_signs`.
+	const FOREIGN_TOKEN_ADDRESS_VOTED: u8 = 14;
+	/// Slot of `ForeignBridge.num_tokenAddressAprroval_signs`.
+	const FOREIGN_TOKEN_ADDRESS_VOTES: u8 = 15;
+
+	/// Position of the value of `key` in the mapping at `slot`, `keccak256(key . slot)` with both padded to 32 bytes.
+	fn mapping_position(key: &[u8], slot: u8) -> U256 {
+		let mut preimage = [0u8; 64];
+		preimage[32 - key.len()..32].copy_from_slice(key);
+		preimage[63] = slot;
+		U256::from(&keccak256(&preimage)[..])
+	}
+
+	/// `keccak256(authority, hash)` of the contract, the key of entries signed by `authority`.
+	fn sender_hash(authority: Address, hash: &[u8]) -> [u8; 32] {
+		let mut preimage = authority.0.to_vec();
+		preimage.extend_from_slice(hash);
+		keccak256(&preimage)
+	}
+
+	/// Position of the length of `HomeBridge.authorities`.
+	pub fn home_authorities_length() -> U256 {
+		HOME_AUTHORITIES.into()
+	}
+
+	/// Position of `HomeBridge.authorities[index]`, elements of an array start at `keccak256(slot)`.
+	pub fn home_authority(index: usize) -> U256 {
+		let mut slot = [0u8; 32];
+		slot[31] = HOME_AUTHORITIES;
+		U256::from(&keccak256(&slot)[..]).overflowing_add(index.into()).0
+	}
+
+	/// Position of `HomeBridge.withdraws[transaction_hash]`, set once the withdraw has been executed.
+	pub fn home_withdraw(transaction_hash: H256) -> U256 {
+		mapping_position(&transaction_hash.0, HOME_WITHDRAWS)
+	}
+
+	/// Position of `ForeignBridge.authorities[authority]`.
+	pub fn foreign_authority(authority: Address) -> U256 {
+		mapping_position(&authority.0, FOREIGN_AUTHORITIES)
+	}
+
+	/// Position of the `ForeignBridge.deposits_signed` entry set once `authority` has relayed the deposit.
+	pub fn foreign_deposit_signed(authority: Address, recipient: Address, value: U256, transaction_hash: H256) -> U256 {
+		let mut deposit = recipient.0.to_vec();
+		let mut value_bytes = [0u8; 32];
+		value.to_big_endian(&mut value_bytes);
+		deposit.extend_from_slice(&value_bytes);
+		deposit.extend_from_slice(&transaction_hash.0);
+		let hash = sender_hash(authority, &keccak256(&deposit));
+		mapping_position(&hash, FOREIGN_DEPOSITS_SIGNED)
+	}
+
+	/// Position of the `ForeignBridge.messages_signed` entry set once `authority` has submitted its signature of `message`.
+	pub fn foreign_message_signed(authority: Address, message: &[u8]) -> U256 {
+		let hash = sender_hash(authority, &keccak256(message));
+		mapping_position(&hash, FOREIGN_MESSAGES_SIGNED)
+	}
+
+	/// Position of the number of authorities who voted for `token` with `ForeignBridge.setTokenAddress`.
+	pub fn foreign_token_address_votes(token: Address) -> U256 {
+		mapping_position(&token.0, FOREIGN_TOKEN_ADDRESS_VOTES)
+	}
+
+	/// Position of the `ForeignBridge.tokenAddressAprroval_signs` entry set once `authority` has voted for `token`.
+	pub fn foreign_token_address_voted(authority: Address, token: Address) -> U256 {
+		let hash = sender_hash(authority, &token.0);
+		mapping_position(&hash, FOREIGN_TOKEN_ADDRESS_VOTED)
+	}
+
+	#[cfg(test)]
+	mod tests {
+		use web3::types::{H256, U256};
+		use super::{mapping_position, home_authorities_length, home_authority, home_withdraw, foreign_authority, foreign_deposit_signed, foreign_token_address_votes, foreign_token_address_voted};
+
+		fn position(hash: &str) -> U256 {
+			let hash: H256 = hash.into();
+			U256::from(&hash.0[..])
+		}
+
+		#[test]
+		fn test_mapping_position() {
+			assert_eq!(position("ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"), mapping_position(&[0u8; 32], 0));
+		}
+
+		#[test]
+		fn test_positions() {
+			assert_eq!(U256::from(4), home_authorities_length());
+			assert_eq!(position("8a35acfbc15ff81a39ae7d344fd709f28e8600b4aa8c65c6b64bfe7fe36bd19c"), home_authority(1));
+			assert_eq!(
+				position("3138e755d0ca50e25d3fdfe53c5cdbb4b25616bfa5c59c282040bb8f9aa80abc"),
+				home_withdraw("1045bfe274b88120a6b1e5d01b5ec00ab5d01098346e90e7c7a3c9b8f0181c80".into())
+			);
+			assert_eq!(
+				position("3e5fec24aa4dc4e5aee2e025e51e1392c72a2500577559fae9665c6d52bd6a31"),
+				foreign_authority(1.into())
+			);
+			assert_eq!(
+				position("cffdb18a452f0c1d519e760e4c453870b4b3fbdbcd2c0ebcdbf09a51d0fd880c"),
+				foreign_deposit_signed(1.into(), "aff3454fce5edbc8cca8697c15331677e6ebcccc".into(), 0xf0.into(), "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into())
+			);
+			assert_eq!(
+				position("6eb50ef5afe8977e559654481465388b120beaf6deb08ec3e8d19d4ea73395bb"),
+				foreign_token_address_votes(0xaa.into())
+			);
+			assert_eq!(
+				position("3798132754c34c9d0bb8d776175cbc73bc93484de2824f7869696292379063e3"),
+				foreign_token_address_voted(1.into(), 0xaa.into())
+			);
+		}
+	}
+}
@@ -1,4 +1,5 @@
-use std::path::Path;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use std::{io, str, fs, fmt};
 use std::io::{Read, Write};
 use web3::types::Address;
@@ -46,19 +47,73 @@ impl Database {
 			Err(err) => return Err(err).chain_err(|| "Cannot open database"),
 		};
 
-		let mut buffer = String::new();
-		file.read_to_string(&mut buffer)?;
-		buffer.parse()
+		let mut buffer = Vec::new();
+		file.read_to_end(&mut buffer)?;
+
+		let corrupt = |reason: String| -> Error { ErrorKind::CorruptDatabase(format!("{:?}", path.as_ref()), reason).into() };
+		let buffer = String::from_utf8(buffer).map_err(|err| corrupt(err.to_string()))?;
+		if buffer.trim().is_empty() {
+			return Err(corrupt("file is empty".into()));
+		}
+		// serialized database always ends with a newline, file without it has been cut mid-write
+		if !buffer.ends_with('\n') {
+			return Err(corrupt("file doesn't end with a newline".into()));
+		}
+		toml::from_str(&buffer).map_err(|err| corrupt(err.to_string()))
 	}
 
 	pub fn save<W: Write>(&self, mut write: W) -> Result<(), Error> {
 		write.write_all(self.to_string().as_bytes())?;
 		Ok(())
 	}
+
+	/// Atomically replaces database at given path.
+	///
+	/// Database is written to a temporary file, which is synced to disk and renamed over the original,
+	/// so the file at `path` always contains either the previous or the new database.
+	pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+		let path = path.as_ref();
+		let temp_path = temp_path(path);
+		{
+			let mut file = fs::File::create(&temp_path).chain_err(|| format!("Cannot create {:?}", temp_path))?;
+			self.save(&mut file)?;
+			file.sync_all()?;
+		}
+		fs::rename(&temp_path, path).chain_err(|| format!("Cannot replace database {:?}", path))?;
+		sync_parent(path)?;
+		Ok(())
+	}
+}
+
+/// Path of the temporary file which is renamed over `path` once it's written.
+pub fn temp_path(path: &Path) -> PathBuf {
+	let mut temp: OsString = path.as_os_str().to_owned();
+	temp.push(".tmp");
+	temp.into()
+}
+
+/// Makes sure that rename of the file is on disk.
+#[cfg(unix)]
+pub fn sync_parent(path: &Path) -> Result<(), Error> {
+	match path.parent() {
+		Some(parent) if parent != Path::new("") => fs::File::open(parent)?.sync_all()?,
+		_ => fs::File::open(".")?.sync_all()?,
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn sync_parent(_path: &Path) -> Result<(), Error> {
+	Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+	extern crate tempdir;
+	use std::fs;
+	use std::io::Write;
+	use self::tempdir::TempDir;
+	use error::ErrorKind;
 	use super::Database;
 
 	#[test]
@@ -88,4 +143,59 @@ checked_withdraw_confirm = 121
 		let s = database.to_string();
 		assert_eq!(s, toml);
 	}
+
+	#[test]
+	fn database_store_replaces_longer_file() {
+		let tempdir = TempDir::new("database_store_replaces_longer_file").unwrap();
+		let path = tempdir.path().join("db.toml");
+
+		let database = Database {
+			checked_deposit_relay: 1200000,
+			checked_withdraw_relay: 1210000,
+			checked_withdraw_confirm: 1210000,
+			..Default::default()
+		};
+		database.store(&path).unwrap();
+
+		let database = Database {
+			checked_deposit_relay: 1,
+			..Default::default()
+		};
+		database.store(&path).unwrap();
+		assert_eq!(database, Database::load(&path).unwrap());
+		assert!(!tempdir.path().join("db.toml.tmp").exists());
+	}
+
+	#[test]
+	fn database_load_truncated() {
+		let tempdir = TempDir::new("database_load_truncated").unwrap();
+		let path = tempdir.path().join("db.toml");
+
+		let database = Database {
+			checked_withdraw_confirm: 121,
+			..Default::default()
+		};
+		let serialized = database.to_string();
+		// cut in the middle of the last value
+		fs::File::create(&path).unwrap().write_all(serialized[..serialized.len() - 2].as_bytes()).unwrap();
+
+		match *Database::load(&path).unwrap_err().kind() {
+			ErrorKind::CorruptDatabase(_, _) => {},
+			ref kind => panic!("unexpected error: {:?}", kind),
+		}
+	}
+
+	#[test]
+	fn database_load_corrupt() {
+		let tempdir = TempDir::new("database_load_corrupt").unwrap();
+		let path = tempdir.path().join("db.toml");
+
+		for content in &["", "home_contract_address = \"0x49edf201c1e139282643d5e7c6fb0c7219ad1db7\"\n", "\u{0}\u{0}\u{0}\n"] {
+			fs::File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+			match *Database::load(&path).unwrap_err().kind() {
+				ErrorKind::CorruptDatabase(_, _) => {},
+				ref kind => panic!("unexpected error: {:?}", kind),
+			}
+		}
+	}
 }
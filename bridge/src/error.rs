@@ -2,8 +2,9 @@
 
 use std::io;
 use api::ApiCall;
+use web3::types::{Address, H256};
 use tokio_timer::{TimerError, TimeoutError};
-use {web3, toml, ethabi, rustc_hex};
+use {web3, toml, ethabi, rustc_hex, metrics};
 
 error_chain! {
 	types {
@@ -31,6 +32,22 @@ error_chain! {
 			description("File not found"),
 			display("File {} not found", filename),
 		}
+		CorruptDatabase(filename: String, reason: String) {
+			description("database is corrupt"),
+			display("Database {} is truncated or corrupt: {}", filename, reason),
+		}
+		TransactionFailed(hash: H256) {
+			description("transaction failed"),
+			display("Transaction {:?} has been mined, but its execution failed", hash),
+		}
+		TransactionDropped(hash: H256) {
+			description("transaction dropped"),
+			display("Transaction {:?} has been dropped from the transaction pool", hash),
+		}
+		InvalidContract(name: &'static str, address: Address, reason: String) {
+			description("contract doesn't match the config"),
+			display("{} at {:?} doesn't match the config: {}", name, address, reason),
+		}
 		// workaround for lack of web3:Error Display and Error implementations
 		Web3(err: web3::Error) {
 			description("web3 error"),
@@ -43,6 +60,7 @@ impl<T, F> From<TimeoutError<ApiCall<T, F>>> for Error {
 	fn from(err: TimeoutError<ApiCall<T, F>>) -> Self {
 		match err {
 			TimeoutError::Timer(call, _) | TimeoutError::TimedOut(call) => {
+				metrics::rpc_error(call.message());
 				ErrorKind::Timeout(call.message()).into()
 			}
 		}
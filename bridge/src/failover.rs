@@ -0,0 +1,392 @@
+/// Transport spreading requests over redundant endpoints of a single chain.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::Arc;
+use futures::{Future, Stream, Poll, Async};
+use rpc;
+use serde_json;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use web3::{self, RequestId, Transport};
+use web3::helpers;
+use web3::transports::ws::WebSocket;
+use web3::types::{Log, U256};
+use api;
+use config::Node;
+use error::Error;
+use preflight::is_revert;
+use status::Status;
+use transport::ReconnectingTransport;
+
+#[derive(Debug, Clone)]
+struct Endpoint {
+	transport: ReconnectingTransport,
+	/// Latest block number returned by the endpoint.
+	head: Rc<Cell<Option<u64>>>,
+	/// True while the background block number request to the endpoint is pending.
+	polling: Rc<Cell<bool>>,
+}
+
+/// Transport which fails over to the next endpoint if the current one is disconnected, fails a request,
+/// doesn't respond in time or falls behind the best endpoint by more than `max_lag` blocks.
+///
+/// With `quorum` greater than one, `eth_getLogs` is sent to all endpoints and resolves only
+/// once `quorum` of them returned the same logs.
+#[derive(Debug, Clone)]
+pub struct FailoverTransport {
+	name: &'static str,
+	endpoints: Vec<Endpoint>,
+	/// Index of the endpoint receiving requests.
+	current: Rc<Cell<usize>>,
+	quorum: usize,
+	max_lag: u64,
+	handle: Handle,
+	ids: Rc<Cell<RequestId>>,
+	/// Status updated with every block number returned by the current endpoint.
+	status: Option<Arc<Status>>,
+}
+
+fn method(request: &rpc::Call) -> Option<String> {
+	match *request {
+		rpc::Call::MethodCall(ref call) => Some(call.method.clone()),
+		_ => None,
+	}
+}
+
+/// Returns true if both endpoints returned the same logs. Fields which differ between clients,
+/// e.g. `type` or `transactionLogIndex`, are ignored.
+fn same_logs(a: &[Log], b: &[Log]) -> bool {
+	a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| {
+		a.address == b.address &&
+			a.topics == b.topics &&
+			a.data == b.data &&
+			a.block_hash == b.block_hash &&
+			a.block_number == b.block_number &&
+			a.transaction_hash == b.transaction_hash &&
+			a.transaction_index == b.transaction_index &&
+			a.log_index == b.log_index
+	})
+}
+
+fn block_number(value: &rpc::Value) -> Option<u64> {
+	serde_json::from_value::<U256>(value.clone()).ok().map(|number| number.low_u64())
+}
+
+impl FailoverTransport {
+	/// Connects to all endpoints of the node. `name` of the chain is used only in logs and error messages.
+	pub fn new(handle: &Handle, timer: &Timer, node: &Node, name: &'static str) -> Result<Self, Error> {
+		let endpoints = node.endpoints.iter()
+			.map(|endpoint| Ok(Endpoint {
+				transport: ReconnectingTransport::new(handle, timer, endpoint, name)?,
+				head: Default::default(),
+				polling: Default::default(),
+			}))
+			.collect::<Result<Vec<_>, Error>>()?;
+
+		let result = FailoverTransport {
+			name,
+			endpoints,
+			current: Default::default(),
+			quorum: node.quorum,
+			max_lag: node.max_lag,
+			handle: handle.clone(),
+			ids: Default::default(),
+			status: None,
+		};
+		Ok(result)
+	}
+
+	/// Reports every block number returned by the current endpoint to `status` and asks for it
+	/// every `poll_interval`, so connectivity is known even while no bridge component polls the chain.
+	pub fn with_status(mut self, status: Arc<Status>, timer: &Timer, node: &Node) -> Self {
+		self.status = Some(status);
+		let transport = self.clone();
+		let name = self.name;
+		let request_timeout = node.request_timeout;
+		let timeout = timer.clone();
+		let probe = timer.interval(node.poll_interval)
+			.for_each(move |_| timeout.timeout(api::block_number(&transport), request_timeout).then(|_| Ok(())))
+			.map_err(move |err| warn!("{} connectivity probe stopped: {}", name, err));
+		self.handle.spawn(probe);
+		self
+	}
+
+	/// Websocket connection of the current endpoint which supports subscriptions, if any.
+	pub fn websocket(&self) -> Option<WebSocket> {
+		self.endpoints[self.current.get()].transport.websocket()
+	}
+
+	fn switch_to(&self, index: usize, reason: &str) {
+		warn!("{} endpoint {} {}, failing over to endpoint {}", self.name, self.current.get(), reason, index);
+		self.current.set(index);
+	}
+
+	/// Endpoint receiving requests. Fails over to the next connected endpoint if the current one is disconnected.
+	fn current(&self) -> &Endpoint {
+		let current = self.current.get();
+		if !self.endpoints[current].transport.is_connected() {
+			let len = self.endpoints.len();
+			if let Some(next) = (1..len).map(|i| (current + i) % len).find(|&i| self.endpoints[i].transport.is_connected()) {
+				self.switch_to(next, "is disconnected");
+			}
+		}
+		&self.endpoints[self.current.get()]
+	}
+
+	/// Fails over to the next endpoint, preferably a connected one, if endpoint `index` is still the current one.
+	fn fail_over(&self, index: usize, reason: &str) {
+		let len = self.endpoints.len();
+		if len == 1 || self.current.get() != index {
+			return;
+		}
+		let next = (1..len).map(|i| (index + i) % len)
+			.find(|&i| self.endpoints[i].transport.is_connected())
+			.unwrap_or((index + 1) % len);
+		self.switch_to(next, reason);
+	}
+
+	/// Sends `request` to the current endpoint, failing over if it fails or doesn't complete.
+	fn send_current(&self, id: RequestId, request: rpc::Call) -> FailoverRequest {
+		let future = self.current().transport.send(id, request);
+		FailoverRequest {
+			future,
+			transport: self.clone(),
+			endpoint: self.current.get(),
+			done: false,
+		}
+	}
+
+	/// Fails over to the endpoint with the highest block, if the current one is too far behind it.
+	fn check_lag(&self) {
+		let current = self.current.get();
+		let best = self.endpoints.iter()
+			.enumerate()
+			.filter_map(|(index, endpoint)| endpoint.head.get().map(|head| (head, index)))
+			.max();
+		if let (Some(head), Some((best_head, best))) = (self.endpoints[current].head.get(), best) {
+			if best_head > head + self.max_lag {
+				self.switch_to(best, &format!("is {} blocks behind", best_head - head));
+			}
+		}
+	}
+
+	/// Sends `eth_blockNumber` to the current endpoint and, in the background, to all the others
+	/// to find out whether the current endpoint falls behind.
+	fn send_block_number(&self, id: RequestId, request: rpc::Call) -> web3::Result<rpc::Value> {
+		let current = self.current.get();
+		for (index, endpoint) in self.endpoints.iter().enumerate() {
+			if index == current || endpoint.polling.get() {
+				continue;
+			}
+			endpoint.polling.set(true);
+			let head = endpoint.head.clone();
+			let polling = endpoint.polling.clone();
+			let failed = endpoint.polling.clone();
+			self.handle.spawn(endpoint.transport.send(id, request.clone())
+				.map(move |value| {
+					head.set(block_number(&value));
+					polling.set(false);
+				})
+				.map_err(move |_| failed.set(false)));
+		}
+
+		let transport = self.clone();
+		Box::new(self.send_current(id, request).map(move |value| {
+			let head = block_number(&value);
+			transport.endpoints[current].head.set(head);
+			if let (Some(status), Some(head)) = (transport.status.as_ref(), head) {
+				status.chain_head(transport.name, head);
+			}
+			transport.check_lag();
+			value
+		}))
+	}
+}
+
+impl Transport for FailoverTransport {
+	type Out = web3::Result<rpc::Value>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		let id = self.ids.get() + 1;
+		self.ids.set(id);
+		(id, helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		let method = method(&request);
+		if self.endpoints.len() == 1 && method.as_ref().map(String::as_str) != Some("eth_blockNumber") {
+			return self.endpoints[0].transport.send(id, request);
+		}
+
+		match method.as_ref().map(String::as_str) {
+			Some("eth_getLogs") if self.quorum > 1 => Box::new(QuorumRequest {
+				name: self.name,
+				futures: self.endpoints.iter().map(|endpoint| Some(endpoint.transport.send(id, request.clone()))).collect(),
+				responses: Vec::new(),
+				quorum: self.quorum,
+			}),
+			Some("eth_blockNumber") => {
+				// make sure a disconnected endpoint is replaced before its block number is requested
+				self.current();
+				self.send_block_number(id, request)
+			},
+			_ => Box::new(self.send_current(id, request)),
+		}
+	}
+}
+
+/// Request to the current endpoint. The transport fails over to the next endpoint if the request fails,
+/// unless the node reports a revert, or if it's dropped before it completes, e.g. because it timed out.
+struct FailoverRequest {
+	transport: FailoverTransport,
+	/// Index of the endpoint receiving the request.
+	endpoint: usize,
+	future: web3::Result<rpc::Value>,
+	/// True once the request has completed.
+	done: bool,
+}
+
+impl Future for FailoverRequest {
+	type Item = rpc::Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let result = self.future.poll();
+		match result {
+			Ok(Async::NotReady) => {},
+			Ok(Async::Ready(_)) => self.done = true,
+			Err(ref err) => {
+				self.done = true;
+				let reverted = match *err.kind() {
+					web3::error::ErrorKind::Rpc(ref err) => is_revert(err),
+					_ => false,
+				};
+				if !reverted {
+					self.transport.fail_over(self.endpoint, &format!("failed with {}", err));
+				}
+			},
+		}
+		result
+	}
+}
+
+impl Drop for FailoverRequest {
+	fn drop(&mut self) {
+		if !self.done {
+			self.transport.fail_over(self.endpoint, "hasn't responded in time");
+		}
+	}
+}
+
+/// `eth_getLogs` request which resolves once `quorum` endpoints returned the same logs.
+/// Fails with a transport error otherwise, so the request is retried, e.g. once a lagging endpoint catches up.
+struct QuorumRequest {
+	name: &'static str,
+	futures: Vec<Option<web3::Result<rpc::Value>>>,
+	/// Responses and logs decoded from them.
+	responses: Vec<(rpc::Value, Vec<Log>)>,
+	quorum: usize,
+}
+
+impl Future for QuorumRequest {
+	type Item = rpc::Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		for (index, slot) in self.futures.iter_mut().enumerate() {
+			let result = match *slot {
+				Some(ref mut future) => future.poll(),
+				None => continue,
+			};
+
+			match result {
+				Ok(Async::NotReady) => continue,
+				Ok(Async::Ready(response)) => match serde_json::from_value(response.clone()) {
+					Ok(logs) => self.responses.push((response, logs)),
+					Err(err) => warn!("{} endpoint {} returned invalid logs: {}", self.name, index, err),
+				},
+				Err(err) => warn!("{} endpoint {} failed: {:?}", self.name, index, err),
+			}
+			*slot = None;
+		}
+
+		let agreed = self.responses.iter()
+			.find(|&&(_, ref logs)| self.responses.iter().filter(|&&(_, ref other)| same_logs(logs, other)).count() >= self.quorum)
+			.map(|&(ref response, _)| response.clone());
+		if let Some(response) = agreed {
+			return Ok(Async::Ready(response));
+		}
+
+		if self.futures.iter().all(Option::is_none) {
+			let message = format!("{} endpoints don't agree, {} of them need to return the same response", self.name, self.quorum);
+			return Err(web3::error::ErrorKind::Transport(message).into());
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::{Future, future};
+	use serde_json::{self, Value};
+	use web3;
+	use transport::is_connection_error;
+	use super::QuorumRequest;
+
+	fn json(value: &str) -> Value {
+		serde_json::from_str(value).unwrap()
+	}
+
+	fn logs(block_number: &str, log_type: &str) -> Value {
+		json(&format!(r#"[{{
+			"address": "0x0000000000000000000000000000000000000001",
+			"topics": [],
+			"data": "0x10",
+			"blockNumber": "{}",
+			"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+			"type": "{}"
+		}}]"#, block_number, log_type))
+	}
+
+	fn quorum_request(responses: Vec<Result<Value, web3::Error>>, quorum: usize) -> QuorumRequest {
+		QuorumRequest {
+			name: "home",
+			futures: responses.into_iter().map(|response| Some(Box::new(future::result(response)) as web3::Result<Value>)).collect(),
+			responses: Vec::new(),
+			quorum,
+		}
+	}
+
+	#[test]
+	fn test_quorum_request_agreed() {
+		let logs = logs("0x10", "mined");
+		let request = quorum_request(vec![Ok(json("[]")), Ok(logs.clone()), Ok(logs.clone())], 2);
+		assert_eq!(logs, request.wait().unwrap());
+	}
+
+	#[test]
+	fn test_quorum_request_ignores_client_specific_fields() {
+		let request = quorum_request(vec![Ok(logs("0x10", "mined")), Ok(logs("0x10", ""))], 2);
+		assert_eq!(logs("0x10", "mined"), request.wait().unwrap());
+	}
+
+	#[test]
+	fn test_quorum_request_disagreed() {
+		let failed = Err(web3::error::ErrorKind::Unreachable.into());
+		let request = quorum_request(vec![Ok(json("[]")), Ok(logs("0x10", "mined")), failed], 2);
+		// a lagging endpoint may return no logs yet, the request is retried
+		assert!(is_connection_error(&request.wait().unwrap_err()));
+
+		let request = quorum_request(vec![Ok(logs("0x10", "mined")), Ok(logs("0x11", "mined"))], 2);
+		assert!(request.wait().is_err());
+	}
+
+	#[test]
+	fn test_quorum_request_invalid_logs() {
+		let invalid = json(r#"[{"blockNumber": "0x10"}]"#);
+		let request = quorum_request(vec![Ok(invalid.clone()), Ok(invalid)], 2);
+		assert!(request.wait().is_err());
+	}
+}
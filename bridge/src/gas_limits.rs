@@ -0,0 +1,105 @@
+/// Gas limits of relay transactions shared by all bridge components.
+
+use std::sync::Mutex;
+use web3::types::U256;
+use config::Transactions;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct Limits {
+	deposit_relay: U256,
+	withdraw_relay: U256,
+	withdraw_confirm: U256,
+}
+
+/// Gas limits read from `HomeBridge` and `ForeignBridge`.
+///
+/// Limits which are not set in the contracts (are zero) fall back to `transactions.*.gas` of the config.
+#[derive(Debug)]
+pub struct GasLimits {
+	config: Limits,
+	contracts: Mutex<Limits>,
+}
+
+impl GasLimits {
+	pub fn new(txs: &Transactions) -> Self {
+		GasLimits {
+			config: Limits {
+				deposit_relay: txs.deposit_relay.gas.into(),
+				withdraw_relay: txs.withdraw_relay.gas.into(),
+				withdraw_confirm: txs.withdraw_confirm.gas.into(),
+			},
+			contracts: Default::default(),
+		}
+	}
+
+	fn limit<F: Fn(&Limits) -> U256>(&self, f: F) -> U256 {
+		let limit = f(&*self.contracts.lock().expect("gas limits lock is never poisoned; qed"));
+		if limit.is_zero() {
+			f(&self.config)
+		} else {
+			limit
+		}
+	}
+
+	/// Gas limit of `ForeignBridge.deposit`.
+	pub fn deposit_relay(&self) -> U256 {
+		self.limit(|limits| limits.deposit_relay)
+	}
+
+	/// Gas limit of `HomeBridge.withdraw`.
+	pub fn withdraw_relay(&self) -> U256 {
+		self.limit(|limits| limits.withdraw_relay)
+	}
+
+	/// Gas limit of `ForeignBridge.submitSignature`.
+	pub fn withdraw_confirm(&self) -> U256 {
+		self.limit(|limits| limits.withdraw_confirm)
+	}
+
+	/// Updates limits stored in `HomeBridge`.
+	pub fn set_home(&self, withdraw_relay: U256) {
+		let mut limits = self.contracts.lock().expect("gas limits lock is never poisoned; qed");
+		if limits.withdraw_relay != withdraw_relay {
+			info!("gas limit of withdraw relays set to {}", withdraw_relay);
+			limits.withdraw_relay = withdraw_relay;
+		}
+	}
+
+	/// Updates limits stored in `ForeignBridge`.
+	pub fn set_foreign(&self, deposit_relay: U256, withdraw_confirm: U256) {
+		let mut limits = self.contracts.lock().expect("gas limits lock is never poisoned; qed");
+		if limits.deposit_relay != deposit_relay {
+			info!("gas limit of deposit relays set to {}", deposit_relay);
+			limits.deposit_relay = deposit_relay;
+		}
+		if limits.withdraw_confirm != withdraw_confirm {
+			info!("gas limit of withdraw confirms set to {}", withdraw_confirm);
+			limits.withdraw_confirm = withdraw_confirm;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use config::{Transactions, TransactionConfig};
+	use super::GasLimits;
+
+	#[test]
+	fn test_gas_limits_fall_back_to_config() {
+		let txs = Transactions {
+			deposit_relay: TransactionConfig { gas: 100, gas_price: 0 },
+			withdraw_relay: TransactionConfig { gas: 200, gas_price: 0 },
+			withdraw_confirm: TransactionConfig { gas: 300, gas_price: 0 },
+			..Default::default()
+		};
+		let limits = GasLimits::new(&txs);
+		assert_eq!(100, limits.deposit_relay().low_u64());
+		assert_eq!(200, limits.withdraw_relay().low_u64());
+
+		limits.set_home(250.into());
+		limits.set_foreign(0.into(), 350.into());
+		assert_eq!(100, limits.deposit_relay().low_u64());
+		assert_eq!(250, limits.withdraw_relay().low_u64());
+		assert_eq!(350, limits.withdraw_confirm().low_u64());
+	}
+}
@@ -0,0 +1,341 @@
+/// Gas prices of transactions sent by the bridge.
+
+use std::cmp;
+use std::sync::Mutex;
+use std::time::Duration;
+use futures::{Future, Stream, Poll, Async};
+use futures::future::{join_all, JoinAll};
+use futures::stream::Concat2;
+use hyper::{self, Body, Client, Uri};
+use hyper::client::{FutureResponse, HttpConnector};
+use serde_json::{self, Value};
+use tokio_core::reactor::Handle;
+use tokio_timer::{Timer, Sleep};
+use web3::Transport;
+use web3::types::{Block, Transaction, U256};
+use api::{self, RetryCall};
+use config::{Config, GasPriceConfig, GasPriceStrategy, Retry};
+use error::{Error, ErrorKind};
+
+/// Gas price of transactions sent to one of the chains.
+#[derive(Debug)]
+pub struct GasPrice {
+	config: GasPriceConfig,
+	/// The latest price fetched by a strategy other than `Fixed`.
+	current: Mutex<Option<U256>>,
+}
+
+impl GasPrice {
+	fn new(config: &GasPriceConfig) -> Self {
+		GasPrice {
+			config: config.clone(),
+			current: Default::default(),
+		}
+	}
+
+	pub fn config(&self) -> &GasPriceConfig {
+		&self.config
+	}
+
+	/// Gas price of a transaction configured with `fixed` gas price.
+	///
+	/// `fixed` is also used until the first price of other strategies is fetched.
+	pub fn price(&self, fixed: u64) -> U256 {
+		let current = match self.config.strategy {
+			GasPriceStrategy::Fixed => None,
+			_ => *self.current.lock().expect("gas price lock is never poisoned; qed"),
+		};
+		self.config.clamp(current.unwrap_or_else(|| fixed.into()))
+	}
+
+	/// Updates the price fetched by the strategy.
+	pub fn set(&self, price: U256) {
+		let mut current = self.current.lock().expect("gas price lock is never poisoned; qed");
+		if *current != Some(price) {
+			debug!("gas price set to {}, {} after limits", price, self.config.clamp(price));
+			*current = Some(price);
+		}
+	}
+}
+
+/// Gas prices of home and foreign transactions.
+pub struct GasPrices {
+	pub home: GasPrice,
+	pub foreign: GasPrice,
+	/// Client of gas price oracles. Present only if any chain uses the `Oracle` strategy.
+	client: Option<Client<HttpConnector>>,
+}
+
+impl GasPrices {
+	/// Creates gas prices without a client of gas price oracles.
+	pub fn new(config: &Config) -> Self {
+		GasPrices {
+			home: GasPrice::new(&config.home.gas_price),
+			foreign: GasPrice::new(&config.foreign.gas_price),
+			client: None,
+		}
+	}
+
+	/// Creates gas prices with a client of gas price oracles running on the event loop of `handle`.
+	pub fn from_config(config: &Config, handle: &Handle) -> Self {
+		let is_oracle = |gas_price: &GasPriceConfig| match gas_price.strategy {
+			GasPriceStrategy::Oracle { .. } => true,
+			_ => false,
+		};
+
+		let mut result = Self::new(config);
+		if is_oracle(&config.home.gas_price) || is_oracle(&config.foreign.gas_price) {
+			result.client = Some(Client::new(handle));
+		}
+		result
+	}
+
+	pub fn client(&self) -> Option<&Client<HttpConnector>> {
+		self.client.as_ref()
+	}
+}
+
+/// Returns `percentile` of `prices`, or `None` if there are no prices.
+fn percentile(mut prices: Vec<U256>, percentile: u8) -> Option<U256> {
+	if prices.is_empty() {
+		return None;
+	}
+
+	prices.sort();
+	let index = (prices.len() - 1) * cmp::min(percentile, 100) as usize / 100;
+	Some(prices[index])
+}
+
+/// Reads number `field` from the JSON `body` of the oracle response and multiplies it by `multiplier`.
+fn oracle_gas_price(body: &[u8], field: &str, multiplier: f64) -> Result<U256, Error> {
+	let json: Value = serde_json::from_slice(body)
+		.map_err(|err| format!("Invalid response of the gas price oracle: {}", err))?;
+	let value = field.split('.')
+		.fold(Some(&json), |value, key| value.and_then(|value| value.get(key)))
+		.and_then(Value::as_f64)
+		.ok_or_else(|| format!("Response of the gas price oracle has no number `{}`", field))?;
+
+	if value < 0.0 {
+		return Err(format!("Gas price oracle returned negative gas price {}", value).into());
+	}
+
+	Ok(((value * multiplier) as u64).into())
+}
+
+/// Creates new `FetchGasPrice` with the strategy of `config`.
+pub fn fetch_gas_price<T: Transport + Clone>(
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	retry: &Retry,
+	config: &GasPriceConfig,
+	client: Option<&Client<HttpConnector>>,
+) -> FetchGasPrice<T> {
+	match config.strategy {
+		GasPriceStrategy::Fixed => FetchGasPrice::Fixed,
+		GasPriceStrategy::Node => {
+			let call = api::gas_price(&transport);
+			FetchGasPrice::Node(api::retry_call(transport, timer, request_timeout, retry, call))
+		},
+		GasPriceStrategy::Percentile { percentile, blocks } => {
+			let call = api::block_number(&transport);
+			FetchGasPrice::Percentile(Percentile {
+				state: PercentileState::BlockNumber(api::retry_call(transport.clone(), timer.clone(), request_timeout, retry, call)),
+				transport,
+				timer,
+				request_timeout,
+				retry: retry.clone(),
+				percentile,
+				blocks,
+			})
+		},
+		GasPriceStrategy::Oracle { ref url, ref field, multiplier } => match client {
+			Some(client) => {
+				let state = match url.parse::<Uri>() {
+					Ok(uri) => OracleState::Request(client.get(uri)),
+					Err(err) => OracleState::Failed(Some(format!("Invalid url of the gas price oracle {}: {}", url, err).into())),
+				};
+				FetchGasPrice::Oracle(Oracle {
+					field: field.clone(),
+					multiplier,
+					timeout: timer.sleep(request_timeout),
+					state,
+				})
+			},
+			None => FetchGasPrice::Oracle(Oracle {
+				field: field.clone(),
+				multiplier,
+				timeout: timer.sleep(request_timeout),
+				state: OracleState::Failed(Some("Gas price oracle client is not available".into())),
+			}),
+		},
+	}
+}
+
+/// Fetches the gas price of a chain.
+///
+/// Resolves to `None` if the strategy has no price, e.g. `Fixed` or blocks without transactions.
+pub enum FetchGasPrice<T: Transport> {
+	Fixed,
+	Node(RetryCall<T, U256>),
+	Percentile(Percentile<T>),
+	Oracle(Oracle),
+}
+
+impl<T: Transport + Clone> Future for FetchGasPrice<T> {
+	type Item = Option<U256>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match *self {
+			FetchGasPrice::Fixed => Ok(Async::Ready(None)),
+			FetchGasPrice::Node(ref mut future) => future.poll().map(|price| price.map(Some)),
+			FetchGasPrice::Percentile(ref mut future) => future.poll(),
+			FetchGasPrice::Oracle(ref mut future) => future.poll().map(|price| price.map(Some)),
+		}
+	}
+}
+
+enum PercentileState<T: Transport> {
+	BlockNumber(RetryCall<T, U256>),
+	Blocks(JoinAll<Vec<RetryCall<T, Option<Block<Transaction>>>>>),
+}
+
+/// Percentile of gas prices of transactions in the latest blocks.
+pub struct Percentile<T: Transport> {
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	retry: Retry,
+	percentile: u8,
+	blocks: u64,
+	state: PercentileState<T>,
+}
+
+impl<T: Transport + Clone> Future for Percentile<T> {
+	type Item = Option<U256>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				PercentileState::BlockNumber(ref mut future) => {
+					let last_block = try_ready!(future.poll()).low_u64();
+					let first_block = (last_block + 1).saturating_sub(self.blocks);
+					let (transport, timer, request_timeout, retry) = (&self.transport, &self.timer, self.request_timeout, &self.retry);
+					let blocks = (first_block..last_block + 1)
+						.map(|number| {
+							let call = api::block_with_txs(transport, number);
+							api::retry_call(transport.clone(), timer.clone(), request_timeout, retry, call)
+						})
+						.collect();
+					PercentileState::Blocks(join_all(blocks))
+				},
+				PercentileState::Blocks(ref mut future) => {
+					let blocks = try_ready!(future.poll());
+					// blocks missing on the node are skipped
+					let prices = blocks.into_iter()
+						.flat_map(|block| block.map(|block| block.transactions).unwrap_or_default())
+						.map(|tx| tx.gas_price)
+						.collect();
+					return Ok(percentile(prices, self.percentile).into());
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+enum OracleState {
+	Request(FutureResponse),
+	Body(Concat2<Body>),
+	Failed(Option<Error>),
+}
+
+/// Gas price read from the JSON response of an http oracle.
+pub struct Oracle {
+	field: String,
+	multiplier: f64,
+	timeout: Sleep,
+	state: OracleState,
+}
+
+impl Future for Oracle {
+	type Item = U256;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		if let Async::Ready(()) = self.timeout.poll()? {
+			return Err(ErrorKind::Timeout("gas price oracle").into());
+		}
+
+		loop {
+			let next_state = match self.state {
+				OracleState::Request(ref mut future) => {
+					let response = try_ready!(future.poll().map_err(|err| format!("Gas price oracle request failed: {}", err)));
+					if !response.status().is_success() {
+						return Err(format!("Gas price oracle responded with {}", response.status()).into());
+					}
+					OracleState::Body(response.body().concat2())
+				},
+				OracleState::Body(ref mut future) => {
+					let body = try_ready!(future.poll().map_err(|err: hyper::Error| format!("Cannot read response of the gas price oracle: {}", err)));
+					return oracle_gas_price(&body, &self.field, self.multiplier).map(Async::Ready);
+				},
+				OracleState::Failed(ref mut err) => {
+					return Err(err.take().expect("Oracle is not polled after it failed; qed"));
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use config::{GasPriceConfig, GasPriceStrategy};
+	use super::{percentile, oracle_gas_price, GasPrice};
+
+	#[test]
+	fn test_percentile() {
+		let prices = vec![5.into(), 1.into(), 4.into(), 2.into(), 3.into()];
+		assert_eq!(None, percentile(vec![], 50));
+		assert_eq!(Some(1.into()), percentile(prices.clone(), 0));
+		assert_eq!(Some(3.into()), percentile(prices.clone(), 50));
+		assert_eq!(Some(4.into()), percentile(prices.clone(), 80));
+		assert_eq!(Some(5.into()), percentile(prices, 100));
+	}
+
+	#[test]
+	fn test_oracle_gas_price() {
+		let body = br#"{"fast": 20.5, "safe": {"low": 12}}"#;
+		assert_eq!(20_500_000_000u64, oracle_gas_price(body, "fast", 1_000_000_000.0).unwrap().low_u64());
+		assert_eq!(1_200_000_000u64, oracle_gas_price(body, "safe.low", 100_000_000.0).unwrap().low_u64());
+		assert!(oracle_gas_price(body, "fastest", 1.0).is_err());
+		assert!(oracle_gas_price(b"<html>", "fast", 1.0).is_err());
+	}
+
+	#[test]
+	fn test_gas_price() {
+		let fixed = GasPrice::new(&GasPriceConfig {
+			min: Some(10),
+			..GasPriceConfig::default()
+		});
+		fixed.set(100.into());
+		assert_eq!(10, fixed.price(0).low_u64());
+		assert_eq!(20, fixed.price(20).low_u64());
+
+		let node = GasPrice::new(&GasPriceConfig {
+			strategy: GasPriceStrategy::Node,
+			max: Some(50),
+			..GasPriceConfig::default()
+		});
+		assert_eq!(20, node.price(20).low_u64());
+		node.set(30.into());
+		assert_eq!(30, node.price(20).low_u64());
+		node.set(100.into());
+		assert_eq!(50, node.price(20).low_u64());
+	}
+}
@@ -0,0 +1,29 @@
+/// Minimal http server used to expose metrics and status of the bridge.
+
+use std::net::SocketAddr;
+use futures::{Future, Stream};
+use hyper;
+use hyper::server::{Http, Request, Response, Service};
+use tokio_core::reactor::Handle;
+use error::{Error, ResultExt};
+
+/// Starts serving requests with services created by `new_service` on the event loop.
+///
+/// `name` is used only in logs and error messages.
+pub fn serve<S, F>(name: &'static str, address: &SocketAddr, handle: &Handle, new_service: F) -> Result<(), Error> where
+	S: Service<Request = Request, Response = Response, Error = hyper::Error> + 'static,
+	F: Fn() -> S + 'static,
+{
+	let server = Http::new()
+		.serve_addr_handle(address, handle, move || Ok(new_service()))
+		.chain_err(|| format!("Cannot start {} server at {}", name, address))?;
+
+	let connections = handle.clone();
+	handle.spawn(server
+		.for_each(move |connection| {
+			connections.spawn(connection.map(|_| ()).map_err(move |err| warn!("{} connection failed: {}", name, err)));
+			Ok(())
+		})
+		.map_err(move |err| error!("{} server failed: {}", name, err)));
+	Ok(())
+}
@@ -0,0 +1,460 @@
+/// Append-only journal of relays and checkpoints.
+///
+/// Every entry is a single line of JSON. Lines are appended and synced to disk
+/// before the journal is updated in memory, so after a crash the journal contains
+/// every relay which has been sent and every checkpoint which has been reached.
+/// Line which has been torn by a crash mid-write is dropped when the journal is opened.
+///
+/// Superseded entries are removed by compaction when the journal is opened
+/// and whenever it grows too large.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use web3::types::H256;
+use serde_json;
+use database::{self, Database};
+use error::{Error, ErrorKind, ResultExt};
+
+/// Journal is compacted once it has this many more lines than live entries.
+const COMPACTION_THRESHOLD: usize = 10_000;
+
+/// Bridge component performing relays.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayKind {
+	DepositRelay,
+	WithdrawRelay,
+	WithdrawConfirm,
+}
+
+impl RelayKind {
+	pub fn as_str(&self) -> &'static str {
+		match *self {
+			RelayKind::DepositRelay => "deposit_relay",
+			RelayKind::WithdrawRelay => "withdraw_relay",
+			RelayKind::WithdrawConfirm => "withdraw_confirm",
+		}
+	}
+
+	/// Chain scanned by the component.
+	pub fn chain(&self) -> &'static str {
+		match *self {
+			RelayKind::DepositRelay => "home",
+			RelayKind::WithdrawRelay | RelayKind::WithdrawConfirm => "foreign",
+		}
+	}
+}
+
+/// Status of a single relay.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayStatus {
+	/// Relay transaction has been sent, but it's not confirmed yet.
+	Sent,
+	/// Relay transaction has been mined and confirmed.
+	Mined,
+}
+
+/// Relay of a single event.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Relay {
+	pub kind: RelayKind,
+	/// Hash of the transaction which emitted the relayed event.
+	pub source: H256,
+	/// Hash of the relay transaction. Hash of its replacement once it's mined.
+	pub destination: H256,
+	pub status: RelayStatus,
+	/// Number of block containing the relayed event.
+	pub block: u64,
+}
+
+/// Journal entry.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalEntry {
+	/// All events till given block have been relayed by the component.
+	Checked {
+		kind: RelayKind,
+		block: u64,
+	},
+	Relay(Relay),
+}
+
+#[derive(Default)]
+struct JournalState {
+	/// None if the journal is kept only in memory.
+	file: Option<(PathBuf, fs::File)>,
+	/// Number of lines in the file.
+	lines: usize,
+	checked: HashMap<RelayKind, u64>,
+	relays: HashMap<(RelayKind, H256), Relay>,
+}
+
+impl JournalState {
+	fn apply(&mut self, entry: JournalEntry) {
+		match entry {
+			JournalEntry::Checked { kind, block } => {
+				self.checked.insert(kind, block);
+			},
+			JournalEntry::Relay(relay) => {
+				self.relays.insert((relay.kind, relay.source), relay);
+			},
+		}
+	}
+
+	/// Entries describing current state, a single one for every checkpoint and relay.
+	fn entries(&self) -> Vec<JournalEntry> {
+		let mut checked = self.checked.iter().map(|(kind, block)| (*kind, *block)).collect::<Vec<_>>();
+		checked.sort_by_key(|&(kind, _)| kind.as_str());
+
+		let mut relays = self.relays.values().cloned().collect::<Vec<_>>();
+		relays.sort_by_key(|relay| (relay.block, relay.kind.as_str(), relay.source));
+
+		checked.into_iter()
+			.map(|(kind, block)| JournalEntry::Checked { kind, block })
+			.chain(relays.into_iter().map(JournalEntry::Relay))
+			.collect()
+	}
+
+	/// Rewrites the journal with entries describing current state.
+	///
+	/// Relays of events at or below the checkpoint of their component are dropped.
+	/// Components move the checkpoint only once all their relays are mined or failed,
+	/// so these relays are looked up only if a reorg moves the checkpoint back, in which case
+	/// the preflight checks still prevent relaying the events twice.
+	fn compact(&mut self) -> Result<(), Error> {
+		{
+			let checked = &self.checked;
+			self.relays.retain(|_, relay| checked.get(&relay.kind).map_or(true, |block| relay.block > *block));
+		}
+
+		let entries = self.entries();
+		if let Some((ref path, ref mut file)) = self.file {
+			// same as the database, the file at `path` always contains either the previous or the compacted journal
+			let temp_path = database::temp_path(path);
+			{
+				let mut temp = fs::File::create(&temp_path).chain_err(|| format!("Cannot create {:?}", temp_path))?;
+				temp.write_all(&serialize(&entries))?;
+				temp.sync_all()?;
+			}
+			fs::rename(&temp_path, path).chain_err(|| format!("Cannot replace journal {:?}", path))?;
+			database::sync_parent(path)?;
+			*file = fs::OpenOptions::new()
+				.append(true)
+				.open(path)
+				.chain_err(|| format!("Cannot open journal {:?}", path))?;
+		}
+
+		info!("journal compacted from {} to {} entries", self.lines, entries.len());
+		self.lines = entries.len();
+		Ok(())
+	}
+}
+
+fn serialize(entries: &[JournalEntry]) -> Vec<u8> {
+	let mut buffer = Vec::new();
+	for entry in entries {
+		serde_json::to_writer(&mut buffer, entry).expect("serialization can't fail; qed");
+		buffer.push(b'\n');
+	}
+	buffer
+}
+
+/// Journal of relays and checkpoints. Default journal is kept only in memory.
+#[derive(Default)]
+pub struct Journal {
+	state: Mutex<JournalState>,
+}
+
+impl Journal {
+	/// Path of the journal kept next to the database.
+	pub fn path<P: AsRef<Path>>(database: P) -> PathBuf {
+		let mut path: OsString = database.as_ref().as_os_str().to_owned();
+		path.push(".journal");
+		path.into()
+	}
+
+	/// Opens the journal, creating it if it doesn't exist yet.
+	pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+		let mut file = fs::OpenOptions::new()
+			.read(true)
+			.append(true)
+			.create(true)
+			.open(&path)
+			.chain_err(|| format!("Cannot open journal {:?}", path.as_ref()))?;
+
+		let mut buffer = String::new();
+		file.read_to_string(&mut buffer)?;
+
+		let mut state = JournalState::default();
+		// every entry is terminated with a newline, anything after the last one is a torn write
+		let valid = buffer.rfind('\n').map(|index| index + 1).unwrap_or(0);
+		for (index, line) in buffer[..valid].lines().enumerate() {
+			let entry = serde_json::from_str(line)
+				.map_err(|err| ErrorKind::CorruptDatabase(format!("{:?}", path.as_ref()), format!("line {}: {}", index + 1, err)))?;
+			state.apply(entry);
+			state.lines += 1;
+		}
+
+		if valid != buffer.len() {
+			warn!("dropping incomplete entry at the end of journal {:?}", path.as_ref());
+			file.set_len(valid as u64)?;
+			file.sync_data()?;
+		}
+
+		state.file = Some((path.as_ref().to_owned(), file));
+		if state.lines > state.checked.len() + state.relays.len() {
+			state.compact()?;
+		}
+
+		Ok(Journal {
+			state: Mutex::new(state),
+		})
+	}
+
+	/// Appends entries to the journal. Entries are on disk once this function returns.
+	pub fn record(&self, entries: Vec<JournalEntry>) -> Result<(), Error> {
+		if entries.is_empty() {
+			return Ok(());
+		}
+
+		let mut state = self.state.lock().expect("journal lock is never poisoned; qed");
+		if let Some((_, ref mut file)) = state.file {
+			file.write_all(&serialize(&entries)).chain_err(|| "Cannot write to journal")?;
+			file.sync_data().chain_err(|| "Cannot write to journal")?;
+		}
+
+		state.lines += entries.len();
+		for entry in entries {
+			state.apply(entry);
+		}
+
+		if state.lines > state.checked.len() + state.relays.len() + COMPACTION_THRESHOLD {
+			state.compact()?;
+		}
+		Ok(())
+	}
+
+	/// Last block checked by the component.
+	pub fn checked(&self, kind: RelayKind) -> Option<u64> {
+		self.state.lock().expect("journal lock is never poisoned; qed").checked.get(&kind).cloned()
+	}
+
+	/// Latest recorded relay of the event emitted by `source` transaction.
+	pub fn relay(&self, kind: RelayKind, source: H256) -> Option<Relay> {
+		self.state.lock().expect("journal lock is never poisoned; qed").relays.get(&(kind, source)).cloned()
+	}
+
+	/// Records checkpoints of newly deployed bridge, replacing checkpoints of any previous deployment.
+	pub fn reset(&self, database: &Database) -> Result<(), Error> {
+		self.record(checkpoints(database))
+	}
+
+	/// Returns database with checkpoints from the journal.
+	///
+	/// Checkpoints of a database created before the journal existed are migrated to the journal.
+	/// Once the journal has a checkpoint, it wins over the one in the database.
+	pub fn restore(&self, database: &Database) -> Result<Database, Error> {
+		let migrate = checkpoints(database).into_iter()
+			.filter(|entry| match *entry {
+				JournalEntry::Checked { kind, block } => match self.checked(kind) {
+					Some(checked) => {
+						if checked != block {
+							warn!("ignoring {} checkpoint {} of the database, the journal has checkpoint {}", kind.as_str(), block, checked);
+						}
+						false
+					},
+					None => true,
+				},
+				JournalEntry::Relay(_) => false,
+			})
+			.collect::<Vec<_>>();
+		if !migrate.is_empty() {
+			info!("migrating {} checkpoints from the database to the journal", migrate.len());
+		}
+		self.record(migrate)?;
+
+		let result = Database {
+			checked_deposit_relay: self.checked(RelayKind::DepositRelay).expect("checkpoint has been migrated; qed"),
+			checked_withdraw_relay: self.checked(RelayKind::WithdrawRelay).expect("checkpoint has been migrated; qed"),
+			checked_withdraw_confirm: self.checked(RelayKind::WithdrawConfirm).expect("checkpoint has been migrated; qed"),
+			..database.clone()
+		};
+		Ok(result)
+	}
+}
+
+fn checkpoints(database: &Database) -> Vec<JournalEntry> {
+	vec![
+		JournalEntry::Checked { kind: RelayKind::DepositRelay, block: database.checked_deposit_relay },
+		JournalEntry::Checked { kind: RelayKind::WithdrawRelay, block: database.checked_withdraw_relay },
+		JournalEntry::Checked { kind: RelayKind::WithdrawConfirm, block: database.checked_withdraw_confirm },
+	]
+}
+
+#[cfg(test)]
+mod tests {
+	extern crate tempdir;
+	use std::fs;
+	use std::io::{Read, Write};
+	use self::tempdir::TempDir;
+	use database::Database;
+	use error::ErrorKind;
+	use super::{Journal, JournalEntry, Relay, RelayKind, RelayStatus, COMPACTION_THRESHOLD};
+
+	fn relay(status: RelayStatus) -> Relay {
+		Relay {
+			kind: RelayKind::DepositRelay,
+			source: "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+			destination: "1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".into(),
+			status,
+			block: 10,
+		}
+	}
+
+	#[test]
+	fn test_journal_entry_to_json() {
+		let entry = JournalEntry::Checked { kind: RelayKind::WithdrawConfirm, block: 5 };
+		assert_eq!(r#"{"type":"checked","kind":"withdraw_confirm","block":5}"#, ::serde_json::to_string(&entry).unwrap());
+
+		let entry = JournalEntry::Relay(relay(RelayStatus::Sent));
+		let json = ::serde_json::to_string(&entry).unwrap();
+		assert_eq!(entry, ::serde_json::from_str(&json).unwrap());
+	}
+
+	#[test]
+	fn test_journal_reopen() {
+		let tempdir = TempDir::new("test_journal_reopen").unwrap();
+		let path = Journal::path(tempdir.path().join("db"));
+
+		let journal = Journal::open(&path).unwrap();
+		journal.record(vec![JournalEntry::Relay(relay(RelayStatus::Sent))]).unwrap();
+		journal.record(vec![
+			JournalEntry::Relay(relay(RelayStatus::Mined)),
+			JournalEntry::Checked { kind: RelayKind::DepositRelay, block: 9 },
+		]).unwrap();
+		drop(journal);
+
+		let journal = Journal::open(&path).unwrap();
+		assert_eq!(Some(relay(RelayStatus::Mined)), journal.relay(RelayKind::DepositRelay, relay(RelayStatus::Mined).source));
+		assert_eq!(None, journal.relay(RelayKind::WithdrawRelay, relay(RelayStatus::Mined).source));
+		assert_eq!(Some(9), journal.checked(RelayKind::DepositRelay));
+		assert_eq!(None, journal.checked(RelayKind::WithdrawRelay));
+	}
+
+	#[test]
+	fn test_journal_drops_torn_entry() {
+		let tempdir = TempDir::new("test_journal_drops_torn_entry").unwrap();
+		let path = tempdir.path().join("db.journal");
+
+		let journal = Journal::open(&path).unwrap();
+		journal.record(vec![JournalEntry::Checked { kind: RelayKind::DepositRelay, block: 10 }]).unwrap();
+		drop(journal);
+
+		let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+		file.write_all(br#"{"type":"checked","kind":"deposit_re"#).unwrap();
+		drop(file);
+
+		let journal = Journal::open(&path).unwrap();
+		assert_eq!(Some(10), journal.checked(RelayKind::DepositRelay));
+		journal.record(vec![JournalEntry::Checked { kind: RelayKind::DepositRelay, block: 11 }]).unwrap();
+		drop(journal);
+
+		let journal = Journal::open(&path).unwrap();
+		assert_eq!(Some(11), journal.checked(RelayKind::DepositRelay));
+	}
+
+	#[test]
+	fn test_journal_compacted_on_open() {
+		let tempdir = TempDir::new("test_journal_compacted_on_open").unwrap();
+		let path = tempdir.path().join("db.journal");
+		let failed = Relay {
+			source: "0000000000000000000000000000000000000000000000000000000000000001".into(),
+			block: 5,
+			..relay(RelayStatus::Sent)
+		};
+		let pending = Relay {
+			source: "0000000000000000000000000000000000000000000000000000000000000002".into(),
+			block: 11,
+			..relay(RelayStatus::Sent)
+		};
+
+		let journal = Journal::open(&path).unwrap();
+		journal.record(vec![JournalEntry::Relay(relay(RelayStatus::Sent)), JournalEntry::Relay(failed.clone()), JournalEntry::Relay(pending.clone())]).unwrap();
+		journal.record(vec![JournalEntry::Relay(relay(RelayStatus::Mined))]).unwrap();
+		for block in 1..11 {
+			journal.record(vec![JournalEntry::Checked { kind: RelayKind::DepositRelay, block }]).unwrap();
+		}
+		drop(journal);
+
+		let journal = Journal::open(&path).unwrap();
+		// relays at or below the checkpoint are dropped, the one above it is kept
+		assert_eq!(None, journal.relay(RelayKind::DepositRelay, relay(RelayStatus::Mined).source));
+		assert_eq!(None, journal.relay(RelayKind::DepositRelay, failed.source));
+		assert_eq!(Some(pending.clone()), journal.relay(RelayKind::DepositRelay, pending.source));
+		assert_eq!(Some(10), journal.checked(RelayKind::DepositRelay));
+		drop(journal);
+
+		let mut content = String::new();
+		fs::File::open(&path).unwrap().read_to_string(&mut content).unwrap();
+		assert_eq!(2, content.lines().count());
+		assert!(!tempdir.path().join("db.journal.tmp").exists());
+	}
+
+	#[test]
+	fn test_journal_compacted_when_it_grows() {
+		let tempdir = TempDir::new("test_journal_compacted_when_it_grows").unwrap();
+		let path = tempdir.path().join("db.journal");
+
+		let journal = Journal::open(&path).unwrap();
+		let checkpoints = (0..COMPACTION_THRESHOLD as u64 + 2)
+			.map(|block| JournalEntry::Checked { kind: RelayKind::WithdrawRelay, block })
+			.collect();
+		journal.record(checkpoints).unwrap();
+		journal.record(vec![JournalEntry::Checked { kind: RelayKind::DepositRelay, block: 3 }]).unwrap();
+		assert_eq!(Some(COMPACTION_THRESHOLD as u64 + 1), journal.checked(RelayKind::WithdrawRelay));
+
+		let mut content = String::new();
+		fs::File::open(&path).unwrap().read_to_string(&mut content).unwrap();
+		assert_eq!(2, content.lines().count());
+	}
+
+	#[test]
+	fn test_journal_rejects_corrupt_entry() {
+		let tempdir = TempDir::new("test_journal_rejects_corrupt_entry").unwrap();
+		let path = tempdir.path().join("db.journal");
+		fs::File::create(&path).unwrap().write_all(b"not json\n").unwrap();
+
+		match *Journal::open(&path).unwrap_err().kind() {
+			ErrorKind::CorruptDatabase(_, _) => {},
+			ref kind => panic!("unexpected error: {:?}", kind),
+		}
+	}
+
+	#[test]
+	fn test_journal_migrates_database_checkpoints() {
+		let journal = Journal::default();
+		let database = Database {
+			checked_deposit_relay: 5,
+			checked_withdraw_relay: 6,
+			checked_withdraw_confirm: 7,
+			..Default::default()
+		};
+
+		assert_eq!(database, journal.restore(&database).unwrap());
+		assert_eq!(Some(5), journal.checked(RelayKind::DepositRelay));
+
+		journal.record(vec![JournalEntry::Checked { kind: RelayKind::DepositRelay, block: 20 }]).unwrap();
+		let restored = journal.restore(&database).unwrap();
+		assert_eq!(20, restored.checked_deposit_relay);
+		assert_eq!(6, restored.checked_withdraw_relay);
+		assert_eq!(7, restored.checked_withdraw_confirm);
+
+		journal.reset(&database).unwrap();
+		assert_eq!(database, journal.restore(&database).unwrap());
+	}
+}
@@ -4,6 +4,7 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
+extern crate jsonrpc_core as rpc;
 extern crate toml;
 pub extern crate web3;
 extern crate tokio_core;
@@ -19,6 +20,15 @@ extern crate rustc_hex;
 #[macro_use]
 extern crate log;
 extern crate ethereum_types;
+extern crate crypto;
+extern crate secp256k1;
+extern crate tiny_keccak;
+extern crate rlp;
+extern crate hyper;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate prometheus;
 #[macro_use]
 extern crate pretty_assertions;
 #[cfg(test)]
@@ -35,6 +45,19 @@ pub mod bridge;
 pub mod contracts;
 pub mod database;
 pub mod error;
+pub mod failover;
+pub mod gas_limits;
+pub mod gas_price;
+pub mod http;
+pub mod journal;
 pub mod util;
 pub mod message_to_mainnet;
+pub mod metrics;
+pub mod nonce;
+pub mod preflight;
+pub mod pubsub;
 pub mod signature;
+pub mod signer;
+pub mod status;
+pub mod transaction;
+pub mod transport;
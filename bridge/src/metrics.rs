@@ -0,0 +1,191 @@
+/// Prometheus metrics of the bridge process and the http server exposing them.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+use futures::future::{self, FutureResult};
+use hyper::{self, Method, StatusCode};
+use hyper::header::{ContentLength, ContentType};
+use hyper::server::{Request, Response, Service};
+use prometheus::{self, CounterVec, Encoder, Gauge, GaugeVec, HistogramVec, TextEncoder};
+use tokio_core::reactor::Handle;
+use web3::types::U256;
+use error::Error;
+use http;
+use journal::RelayKind;
+
+lazy_static! {
+	static ref CHECKED_BLOCK: GaugeVec = register_gauge_vec!(
+		"bridge_checked_block",
+		"Number of the last block checked by the bridge component.",
+		&["kind"]
+	).expect("metric is registered only once; qed");
+
+	static ref HEAD_BLOCK: GaugeVec = register_gauge_vec!(
+		"bridge_head_block",
+		"Number of the latest block on the chain.",
+		&["chain"]
+	).expect("metric is registered only once; qed");
+
+	static ref CHECKPOINT_LAG: GaugeVec = register_gauge_vec!(
+		"bridge_checkpoint_lag_blocks",
+		"Number of blocks between the head of the chain and the last block checked by the bridge component.",
+		&["kind"]
+	).expect("metric is registered only once; qed");
+
+	static ref RELAYS: CounterVec = register_counter_vec!(
+		"bridge_relays_total",
+		"Number of relayed deposits, relayed withdraws and submitted withdraw signatures.",
+		&["kind"]
+	).expect("metric is registered only once; qed");
+
+	static ref SKIPPED_RELAYS: CounterVec = register_counter_vec!(
+		"bridge_skipped_relays_total",
+		"Number of relays skipped because their transaction would revert or failed.",
+		&["kind"]
+	).expect("metric is registered only once; qed");
+
+	static ref RELAY_ERRORS: CounterVec = register_counter_vec!(
+		"bridge_relay_errors_total",
+		"Number of relays which failed to be signed or sent and are retried.",
+		&["kind"]
+	).expect("metric is registered only once; qed");
+
+	static ref RPC_ERRORS: CounterVec = register_counter_vec!(
+		"bridge_rpc_errors_total",
+		"Number of failed and timed out RPC requests.",
+		&["method"]
+	).expect("metric is registered only once; qed");
+
+	static ref TOKEN_RESERVE: Gauge = register_gauge!(
+		"bridge_token_reserve",
+		"Number of tokens owned by ForeignBridge at the last check before relaying deposits."
+	).expect("metric is registered only once; qed");
+
+	static ref QUEUED_DEPOSITS: Gauge = register_gauge!(
+		"bridge_queued_deposits",
+		"Number of deposits waiting for ForeignBridge to own enough tokens."
+	).expect("metric is registered only once; qed");
+
+	static ref RPC_DURATION: HistogramVec = register_histogram_vec!(
+		"bridge_rpc_request_duration_seconds",
+		"Duration of RPC requests.",
+		&["method"]
+	).expect("metric is registered only once; qed");
+}
+
+fn update_lag(kind: RelayKind) {
+	let head = HEAD_BLOCK.with_label_values(&[kind.chain()]).get();
+	let checked = CHECKED_BLOCK.with_label_values(&[kind.as_str()]).get();
+	CHECKPOINT_LAG.with_label_values(&[kind.as_str()]).set((head - checked).max(0.0));
+}
+
+/// Records the last block checked by the bridge component.
+pub fn checked_block(kind: RelayKind, block: u64) {
+	CHECKED_BLOCK.with_label_values(&[kind.as_str()]).set(block as f64);
+	update_lag(kind);
+}
+
+/// Records the latest block seen by the bridge component.
+pub fn head_block(kind: RelayKind, block: u64) {
+	HEAD_BLOCK.with_label_values(&[kind.chain()]).set(block as f64);
+	update_lag(kind);
+}
+
+/// Records number of completed relays.
+pub fn relayed(kind: RelayKind, count: usize) {
+	RELAYS.with_label_values(&[kind.as_str()]).inc_by(count as f64);
+}
+
+/// Records number of relays skipped because their transaction would revert or failed.
+pub fn skipped(kind: RelayKind, count: usize) {
+	SKIPPED_RELAYS.with_label_values(&[kind.as_str()]).inc_by(count as f64);
+}
+
+/// Records relay which failed to be signed or sent.
+pub fn relay_error(kind: RelayKind) {
+	RELAY_ERRORS.with_label_values(&[kind.as_str()]).inc();
+}
+
+/// Records the token reserve of `ForeignBridge` and number of deposits waiting for it.
+pub fn token_reserve(balance: U256, queued_deposits: usize) {
+	// gauges are floats, precision of large balances is lost
+	let balance = balance.0.iter().rev().fold(0.0, |acc, word| acc * 18_446_744_073_709_551_616.0 + *word as f64);
+	TOKEN_RESERVE.set(balance);
+	QUEUED_DEPOSITS.set(queued_deposits as f64);
+}
+
+/// Records duration of the RPC request.
+pub fn rpc_call(method: &str, duration: Duration) {
+	let seconds = duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0;
+	RPC_DURATION.with_label_values(&[method]).observe(seconds);
+}
+
+/// Records failed or timed out RPC request.
+pub fn rpc_error(method: &str) {
+	RPC_ERRORS.with_label_values(&[method]).inc();
+}
+
+/// Metrics in prometheus text format.
+pub fn encode() -> (String, Vec<u8>) {
+	let encoder = TextEncoder::new();
+	let mut buffer = Vec::new();
+	encoder.encode(&prometheus::gather(), &mut buffer).expect("encoding to memory can't fail; qed");
+	(encoder.format_type().to_owned(), buffer)
+}
+
+struct MetricsService;
+
+impl Service for MetricsService {
+	type Request = Request;
+	type Response = Response;
+	type Error = hyper::Error;
+	type Future = FutureResult<Response, hyper::Error>;
+
+	fn call(&self, request: Request) -> Self::Future {
+		let response = match (request.method(), request.path()) {
+			(&Method::Get, "/metrics") => {
+				let (format, body) = encode();
+				Response::new()
+					.with_header(ContentType(format.parse().expect("prometheus format type is a valid mime; qed")))
+					.with_header(ContentLength(body.len() as u64))
+					.with_body(body)
+			},
+			_ => Response::new().with_status(StatusCode::NotFound),
+		};
+		future::ok(response)
+	}
+}
+
+/// Starts serving metrics at `http://<address>/metrics` on the event loop.
+pub fn serve(address: &SocketAddr, handle: &Handle) -> Result<(), Error> {
+	http::serve("metrics", address, handle, || MetricsService)?;
+	info!("serving metrics at http://{}/metrics", address);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use journal::RelayKind;
+	use super::{checked_block, head_block, relayed, skipped, relay_error, rpc_call, rpc_error, encode};
+
+	#[test]
+	fn test_metrics_encode() {
+		head_block(RelayKind::DepositRelay, 120);
+		checked_block(RelayKind::DepositRelay, 100);
+		relayed(RelayKind::DepositRelay, 2);
+		skipped(RelayKind::WithdrawRelay, 1);
+		relay_error(RelayKind::WithdrawConfirm);
+		rpc_call("eth_blockNumber", Duration::from_millis(10));
+		rpc_error("eth_blockNumber");
+
+		let (_, body) = encode();
+		let body = String::from_utf8(body).unwrap();
+		assert!(body.contains(r#"bridge_checkpoint_lag_blocks{kind="deposit_relay"} 20"#));
+		assert!(body.contains(r#"bridge_head_block{chain="home"} 120"#));
+		assert!(body.contains(r#"bridge_skipped_relays_total{kind="withdraw_relay"}"#));
+		assert!(body.contains(r#"bridge_relay_errors_total{kind="withdraw_confirm"}"#));
+		assert!(body.contains(r#"bridge_rpc_errors_total{method="eth_blockNumber"}"#));
+		assert!(body.contains(r#"bridge_rpc_request_duration_seconds_count{method="eth_blockNumber"}"#));
+	}
+}
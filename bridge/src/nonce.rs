@@ -0,0 +1,339 @@
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use tiny_keccak::keccak256;
+use tokio_timer::{Timer, Timeout};
+use web3;
+use web3::Transport;
+use web3::types::{H256, U256, TransactionRequest};
+use api::{self, ApiCall};
+use error::{Error, ErrorKind};
+use signer::{AccountSigner, Signer, SignTransaction, SignedTransaction};
+
+/// How many times sending a transaction is retried after the node rejected its nonce.
+const MAX_NONCE_RETRIES: usize = 3;
+
+#[derive(Debug, Default)]
+struct NonceState {
+	/// Lowest nonce which has never been handed out, `None` until the manager is synchronised.
+	next: Option<U256>,
+	/// Nonces which have been handed out, but whose transactions have never reached the node
+	/// or have been dropped by it. They are handed out again before new ones, lowest first.
+	released: BTreeSet<U256>,
+}
+
+/// Hands out sequential nonces for transactions sent from a single account.
+///
+/// Nonces are handed out locally, so transactions sent concurrently
+/// do not depend on the order in which the node imports them.
+/// Until the manager is synchronised with the node it has no nonces to hand out.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+	state: Mutex<NonceState>,
+}
+
+impl NonceManager {
+	pub fn new() -> Self {
+		NonceManager::default()
+	}
+
+	/// Returns next nonce or `None` if the manager needs to be synchronised first.
+	pub fn next(&self) -> Option<U256> {
+		let mut state = self.state.lock().expect("nonce manager lock is never poisoned; qed");
+		if let Some(nonce) = state.released.iter().next().cloned() {
+			state.released.remove(&nonce);
+			return Some(nonce);
+		}
+		let nonce = state.next?;
+		state.next = Some(nonce + U256::one());
+		Some(nonce)
+	}
+
+	/// Synchronises the manager with the number of transactions sent from the account, including pending ones.
+	///
+	/// If the manager has been synchronised in the meantime, the count is ignored.
+	pub fn sync(&self, transaction_count: U256) {
+		let mut state = self.state.lock().expect("nonce manager lock is never poisoned; qed");
+		if state.next.is_none() {
+			state.next = Some(transaction_count);
+		}
+	}
+
+	/// Synchronises the manager again after the node rejected a nonce which had already been used,
+	/// e.g. by a transaction sent from the account by someone else.
+	///
+	/// Nonces handed out to transactions which are still being sent are kept,
+	/// so they are never handed out twice.
+	pub fn resync(&self, transaction_count: U256) {
+		let mut state = self.state.lock().expect("nonce manager lock is never poisoned; qed");
+		let next = state.next.map_or(transaction_count, |next| ::std::cmp::max(next, transaction_count));
+		state.next = Some(next);
+		state.released = state.released.split_off(&transaction_count);
+	}
+
+	/// Returns the nonce of a transaction which has never reached the node or has been dropped by it,
+	/// so it's handed out again instead of leaving a gap.
+	pub fn release(&self, nonce: U256) {
+		let mut state = self.state.lock().expect("nonce manager lock is never poisoned; qed");
+		if state.next.map_or(false, |next| nonce < next) {
+			state.released.insert(nonce);
+		}
+	}
+}
+
+/// Nonce managers of accounts used on both sides of the bridge.
+#[derive(Debug, Clone, Default)]
+pub struct Nonces {
+	pub home: Arc<NonceManager>,
+	pub foreign: Arc<NonceManager>,
+}
+
+/// Returns true if the node rejected the transaction because its nonce had already been used.
+fn is_nonce_too_low(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Web3(ref err) => {
+			let message = err.to_string().to_lowercase();
+			// geth and parity report it differently
+			message.contains("nonce too low") || message.contains("nonce is too low")
+		},
+		_ => false,
+	}
+}
+
+/// Returns true if the node received the transaction and refused it.
+///
+/// After a timeout or a lost connection the node may have accepted the transaction.
+fn is_rejected(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Web3(ref err) => match *err.kind() {
+			web3::error::ErrorKind::Rpc(_) => true,
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+enum SendTransactionState<T: Transport> {
+	/// Waiting to be polled for the first time.
+	Init,
+	/// Fetching number of transactions sent from the account to synchronise the nonce manager.
+	FetchNonce(Timeout<ApiCall<U256, T::Out>>),
+	/// Signing transaction with locally assigned nonce.
+	Sign(SignTransaction),
+	/// Sending signed transaction.
+	SendTransaction(Timeout<ApiCall<H256, T::Out>>),
+	/// Sending failed without an answer from the node. Fetching number of transactions sent
+	/// from the account to find out whether the node accepted the transaction.
+	Reconcile {
+		future: Timeout<ApiCall<U256, T::Out>>,
+		error: Option<Error>,
+	},
+}
+
+/// Creates new `SendTransaction`.
+pub fn send_transaction<T: Transport>(
+	transport: T,
+	timer: Timer,
+	nonces: Arc<NonceManager>,
+	signer: Arc<AccountSigner>,
+	request: TransactionRequest,
+	request_timeout: Duration
+) -> SendTransaction<T> {
+	SendTransaction {
+		transport,
+		timer,
+		nonces,
+		signer,
+		request,
+		request_timeout,
+		nonce: None,
+		hash: None,
+		retries: 0,
+		state: SendTransactionState::Init,
+	}
+}
+
+/// Sends a transaction with a nonce assigned by `NonceManager` and resolves to its hash and nonce.
+///
+/// If the node rejects the nonce, the manager is resynchronised and the transaction is sent again.
+/// If signing fails or the node rejects the transaction otherwise, the nonce is released, so it's handed out again.
+/// If sending fails without an answer from the node, e.g. after a timeout, the transaction is never sent again.
+/// The nonce is released only if the node has not used it. A signed transaction which the node accepted
+/// resolves to its hash, the nonce of any other one stays reserved.
+pub struct SendTransaction<T: Transport> {
+	transport: T,
+	timer: Timer,
+	nonces: Arc<NonceManager>,
+	signer: Arc<AccountSigner>,
+	request: TransactionRequest,
+	request_timeout: Duration,
+	/// Nonce of the transaction once it has been assigned.
+	nonce: Option<U256>,
+	/// Hash of the transaction once it has been signed locally.
+	hash: Option<H256>,
+	retries: usize,
+	state: SendTransactionState<T>,
+}
+
+impl<T: Transport> SendTransaction<T> {
+	fn fetch_nonce(&self) -> SendTransactionState<T> {
+		let future = api::transaction_count(&self.transport, self.request.from);
+		SendTransactionState::FetchNonce(self.timer.timeout(future, self.request_timeout))
+	}
+
+	fn sign(&mut self, nonce: U256) -> SendTransactionState<T> {
+		self.nonce = Some(nonce);
+		let request = TransactionRequest {
+			nonce: Some(nonce),
+			..self.request.clone()
+		};
+		SendTransactionState::Sign(Signer::<T>::sign_transaction(&*self.signer, request))
+	}
+
+	fn send(&mut self, transaction: SignedTransaction) -> SendTransactionState<T> {
+		let future = match transaction {
+			SignedTransaction::Raw(raw) => {
+				self.hash = Some(keccak256(&raw.0).into());
+				api::send_raw_transaction(&self.transport, raw)
+			},
+			SignedTransaction::Unsigned(request) => {
+				self.hash = None;
+				api::send_transaction(&self.transport, request)
+			},
+		};
+		SendTransactionState::SendTransaction(self.timer.timeout(future, self.request_timeout))
+	}
+
+	/// Releases the nonce which has not been used.
+	fn release(&mut self) {
+		if let Some(nonce) = self.nonce.take() {
+			self.nonces.release(nonce);
+		}
+	}
+}
+
+impl<T: Transport> Future for SendTransaction<T> {
+	type Item = (H256, U256);
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				SendTransactionState::Init => match self.nonces.next() {
+					Some(nonce) => self.sign(nonce),
+					None => self.fetch_nonce(),
+				},
+				SendTransactionState::FetchNonce(ref mut future) => {
+					let transaction_count = try_ready!(future.poll());
+					// after the node rejected the nonce of this transaction, the manager is synchronised again
+					match self.retries {
+						0 => self.nonces.sync(transaction_count),
+						_ => self.nonces.resync(transaction_count),
+					}
+					let nonce = self.nonces.next().expect("the manager has just been synchronised; qed");
+					self.sign(nonce)
+				},
+				SendTransactionState::Sign(ref mut future) => match future.poll() {
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Ok(Async::Ready(transaction)) => self.send(transaction),
+					Err(err) => {
+						self.release();
+						return Err(err);
+					},
+				},
+				SendTransactionState::SendTransaction(ref mut future) => match future.poll() {
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Ok(Async::Ready(hash)) => {
+						let nonce = self.nonce.expect("transaction is sent only after its nonce is assigned; qed");
+						return Ok(Async::Ready((hash, nonce)));
+					},
+					Err(ref err) if self.retries < MAX_NONCE_RETRIES && is_nonce_too_low(err) => {
+						warn!("nonce of transaction from {:?} is too low, resynchronising: {}", self.request.from, err);
+						// the nonce has been used by another transaction, it's never handed out again
+						self.nonce = None;
+						self.retries += 1;
+						self.fetch_nonce()
+					},
+					Err(err) => if is_rejected(&err) {
+						self.release();
+						return Err(err);
+					} else {
+						warn!("sending transaction from {:?} failed, checking whether the node received it: {}", self.request.from, err);
+						let future = api::transaction_count(&self.transport, self.request.from);
+						SendTransactionState::Reconcile {
+							future: self.timer.timeout(future, self.request_timeout),
+							error: Some(err),
+						}
+					},
+				},
+				SendTransactionState::Reconcile { ref mut future, ref mut error } => {
+					let nonce = self.nonce.expect("transaction is sent only after its nonce is assigned; qed");
+					let accepted = match future.poll() {
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						Ok(Async::Ready(transaction_count)) => Some(transaction_count > nonce),
+						Err(err) => {
+							warn!("cannot check whether the node received transaction from {:?}: {}", self.request.from, err);
+							None
+						},
+					};
+					let error = error.take().expect("error is taken only once the check resolved; qed");
+					match (accepted, self.hash) {
+						(Some(false), _) => self.release(),
+						(Some(true), Some(hash)) => {
+							info!("transaction {:?} from {:?} has been received by the node", hash, self.request.from);
+							return Ok(Async::Ready((hash, nonce)));
+						},
+						// the nonce may have been used by the transaction, it's never handed out again
+						_ => warn!("nonce {} of transaction from {:?} stays reserved", nonce, self.request.from),
+					}
+					return Err(error);
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::NonceManager;
+
+	#[test]
+	fn test_nonce_manager() {
+		let nonces = NonceManager::new();
+		assert_eq!(None, nonces.next());
+		nonces.sync(5.into());
+		assert_eq!(Some(5.into()), nonces.next());
+		assert_eq!(Some(6.into()), nonces.next());
+		// already synchronised, count is ignored
+		nonces.sync(3.into());
+		assert_eq!(Some(7.into()), nonces.next());
+		// released nonces are handed out again, lowest first
+		nonces.release(6.into());
+		nonces.release(5.into());
+		assert_eq!(Some(5.into()), nonces.next());
+		assert_eq!(Some(6.into()), nonces.next());
+		assert_eq!(Some(8.into()), nonces.next());
+		// nonces which have never been handed out are not released
+		nonces.release(20.into());
+		assert_eq!(Some(9.into()), nonces.next());
+	}
+
+	#[test]
+	fn test_nonce_manager_resync() {
+		let nonces = NonceManager::new();
+		nonces.sync(5.into());
+		assert_eq!(Some(5.into()), nonces.next());
+		assert_eq!(Some(6.into()), nonces.next());
+		nonces.release(5.into());
+		// nonces below the count have been used by other transactions
+		nonces.resync(8.into());
+		assert_eq!(Some(8.into()), nonces.next());
+		assert_eq!(Some(9.into()), nonces.next());
+		// nonces handed out to transactions which are still being sent are kept
+		nonces.resync(7.into());
+		assert_eq!(Some(10.into()), nonces.next());
+	}
+}
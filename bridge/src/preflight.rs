@@ -0,0 +1,362 @@
+/// Checks of relay transactions before they are sent, so replayed or reverting relays don't burn gas.
+
+use std::fmt;
+use std::time::Duration;
+use futures::{Future, Poll, Async};
+use futures::future::Join;
+use ethabi;
+use rpc;
+use tokio_timer::Timer;
+use web3::{self, Transport};
+use web3::types::{Address, Bytes, CallRequest, H256, TransactionRequest, U256};
+use api::{self, RetryCall, retry_call};
+use config::Retry;
+use contracts::{erc20, foreign, storage};
+use error::{Error, ErrorKind};
+
+/// Reason why a relay transaction would revert.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Revert {
+	/// Account of the bridge is not an authority of the contract.
+	NotAuthority,
+	/// Token address of `ForeignBridge` has not been set yet.
+	TokenNotSet,
+	/// `ForeignBridge` doesn't own enough tokens to release the deposit.
+	InsufficientTokenBalance,
+	/// Reason could not be determined.
+	Unknown,
+}
+
+impl fmt::Display for Revert {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let reason = match *self {
+			Revert::NotAuthority => "account is not an authority",
+			Revert::TokenNotSet => "token address is not set",
+			Revert::InsufficientTokenBalance => "insufficient token balance",
+			Revert::Unknown => "unknown reason",
+		};
+		f.write_str(reason)
+	}
+}
+
+/// JSON-RPC error code of geth for execution reverted with a reason.
+const EXECUTION_REVERTED: i64 = 3;
+/// JSON-RPC error code of parity for failed execution, the reason is in the error data.
+const EXECUTION_ERROR: i64 = -32015;
+
+/// Returns true if the node rejected the request because the transaction reverts or runs out of its gas.
+///
+/// Other errors returned by the node, e.g. invalid params, are not treated as a revert.
+fn is_rejected(err: &Error) -> bool {
+	match *err.kind() {
+		ErrorKind::Web3(ref err) => match *err.kind() {
+			web3::error::ErrorKind::Rpc(ref err) => is_revert(err) || is_out_of_gas(err),
+			_ => false,
+		},
+		_ => false,
+	}
+}
+
+/// Returns true if the node reports that the call or transaction reverts.
+pub fn is_revert(err: &rpc::Error) -> bool {
+	match err.code.code() {
+		EXECUTION_REVERTED => true,
+		EXECUTION_ERROR => err.data.as_ref().map_or(false, |data| data.to_string().to_lowercase().contains("revert")),
+		// geth reports estimates of reverting transactions without a reason as a generic server error
+		_ => {
+			let message = err.message.to_lowercase();
+			message.contains("execution reverted") || message.contains("always failing transaction")
+		},
+	}
+}
+
+/// Returns true if the node reports that the transaction needs more gas than its limit.
+fn is_out_of_gas(err: &rpc::Error) -> bool {
+	let message = err.message.to_lowercase();
+	// geth: `gas required exceeds allowance`, parity: `requires higher than upper limit` or `OutOfGas` execution error
+	message.contains("exceeds allowance") || message.contains("higher than upper limit") ||
+		(err.code.code() == EXECUTION_ERROR && err.data.as_ref().map_or(false, |data| data.to_string().to_lowercase().contains("outofgas")))
+}
+
+/// Creates new `Simulate` of the transaction `request`.
+///
+/// The transaction is simulated at its gas price and gas limit, `HomeBridge.withdraw` reverts unless it's sent at the gas price of the message.
+pub fn simulate<T: Transport>(transport: T, timer: Timer, request_timeout: Duration, retry: &Retry, request: &TransactionRequest) -> Simulate<T> {
+	let call = CallRequest {
+		from: Some(request.from),
+		to: request.to.expect("relay transactions are always sent to the bridge contract; qed"),
+		gas: request.gas,
+		gas_price: request.gas_price,
+		value: request.value,
+		data: request.data.clone(),
+	};
+	let estimate = api::estimate_gas(&transport, call);
+
+	Simulate {
+		future: retry_call(transport, timer, request_timeout, retry, estimate),
+		gas: request.gas,
+	}
+}
+
+/// Simulates a transaction with `eth_estimateGas`.
+///
+/// Resolves to `None` if the transaction would succeed and to the error reported by the node if it would revert
+/// or needs more gas than the limit of the transaction.
+pub struct Simulate<T: Transport> {
+	future: RetryCall<T, U256>,
+	/// Gas limit of the transaction, not checked if it's not set or zero.
+	gas: Option<U256>,
+}
+
+impl<T: Transport> Future for Simulate<T> {
+	type Item = Option<String>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match self.future.poll() {
+			Ok(Async::Ready(estimate)) => Ok(Async::Ready(exceeds_gas(estimate, self.gas))),
+			Ok(Async::NotReady) => Ok(Async::NotReady),
+			Err(ref err) if is_rejected(err) => Ok(Async::Ready(Some(err.to_string()))),
+			Err(err) => Err(err),
+		}
+	}
+}
+
+/// Returns the reason why the transaction would fail if its gas limit is lower than the `estimate`.
+///
+/// Nodes don't always cap the estimate at the gas limit of the request, so the estimate is checked as well.
+fn exceeds_gas(estimate: U256, gas: Option<U256>) -> Option<String> {
+	match gas {
+		Some(gas) if !gas.is_zero() && estimate > gas => Some(format!("estimated gas {} exceeds the gas limit {}", estimate, gas)),
+		_ => None,
+	}
+}
+
+/// Creates new `Processed` which reads the storage of `contract` at `position`, e.g. of `contracts::storage::home_withdraw`.
+pub fn processed<T: Transport>(transport: T, timer: Timer, request_timeout: Duration, retry: &Retry, contract: Address, position: U256) -> Processed<T> {
+	let flag = api::storage(&transport, contract, position);
+
+	Processed {
+		future: retry_call(transport, timer, request_timeout, retry, flag),
+	}
+}
+
+/// Checks on chain whether the relay has already been processed, e.g. in `HomeBridge.withdraws`.
+///
+/// The flags are read from the storage of the contract, the contracts have no getters for them.
+/// Resolves to `true` if the flag is set.
+pub struct Processed<T: Transport> {
+	future: RetryCall<T, H256>,
+}
+
+impl<T: Transport> Future for Processed<T> {
+	type Item = bool;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let value = try_ready!(self.future.poll());
+		let processed = !value.is_zero();
+		Ok(Async::Ready(processed))
+	}
+}
+
+/// Token of `ForeignBridge` and the number of tokens owned by the contract.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TokenReserve {
+	/// Zero if the token address has not been set yet.
+	pub token: Address,
+	/// Tokens which the contract can release to recipients of deposits.
+	pub balance: U256,
+}
+
+enum TokenReserveState<T: Transport> {
+	/// Fetching the token address of `ForeignBridge`.
+	FetchToken(RetryCall<T, Bytes>),
+	/// Fetching the token balance of `ForeignBridge`.
+	FetchBalance {
+		token: Address,
+		future: RetryCall<T, Bytes>,
+	},
+}
+
+/// Creates new `FetchTokenReserve` of `ForeignBridge` at `contract`.
+pub fn token_reserve<T: Transport + Clone>(transport: T, timer: Timer, request_timeout: Duration, retry: &Retry, contract: Address) -> FetchTokenReserve<T> {
+	let payload = foreign::ForeignBridge::default().functions().erc20token().input();
+	let call = api::call(&transport, contract, payload.into());
+	let state = TokenReserveState::FetchToken(retry_call(transport.clone(), timer.clone(), request_timeout, retry, call));
+
+	FetchTokenReserve {
+		transport,
+		timer,
+		request_timeout,
+		retry: retry.clone(),
+		contract,
+		state,
+	}
+}
+
+/// Reads `erc20token` of `ForeignBridge` and the `balanceOf` the contract in the token.
+///
+/// The balance of a contract without token is zero.
+pub struct FetchTokenReserve<T: Transport> {
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	retry: Retry,
+	contract: Address,
+	state: TokenReserveState<T>,
+}
+
+impl<T: Transport + Clone> Future for FetchTokenReserve<T> {
+	type Item = TokenReserve;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				TokenReserveState::FetchToken(ref mut future) => {
+					let output = try_ready!(future.poll());
+					let token = foreign::ForeignBridge::default().functions().erc20token().output(&output.0)?;
+					if token.is_zero() {
+						return Ok(Async::Ready(TokenReserve {
+							token: token.0.into(),
+							balance: U256::zero(),
+						}));
+					}
+
+					let payload = erc20::ERC20::default().functions().balance_of().input(self.contract.0);
+					let call = api::call(&self.transport, token.0.into(), payload.into());
+					TokenReserveState::FetchBalance {
+						token: token.0.into(),
+						future: retry_call(self.transport.clone(), self.timer.clone(), self.request_timeout, &self.retry, call),
+					}
+				},
+				TokenReserveState::FetchBalance { token, ref mut future } => {
+					let output = try_ready!(future.poll());
+					let balance = erc20::ERC20::default().functions().balance_of().output(&output.0)?;
+					return Ok(Async::Ready(TokenReserve {
+						token,
+						balance,
+					}));
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+/// Creates new `DiagnoseDeposits` of reverting deposits of `values` relayed by `authority` to `ForeignBridge` at `contract`.
+pub fn diagnose_deposits<T: Transport + Clone>(
+	transport: T,
+	timer: Timer,
+	request_timeout: Duration,
+	retry: &Retry,
+	contract: Address,
+	authority: Address,
+	values: Vec<ethabi::Uint>
+) -> DiagnoseDeposits<T> {
+	let is_authority = processed(transport.clone(), timer.clone(), request_timeout, retry, contract, storage::foreign_authority(authority));
+	DiagnoseDeposits {
+		future: is_authority.join(token_reserve(transport, timer, request_timeout, retry, contract)),
+		values,
+	}
+}
+
+/// Finds out why deposits relayed to `ForeignBridge` revert, assuming the account hasn't relayed them yet.
+///
+/// `ForeignBridge.deposit` then reverts only if the account is not an authority, the token is not set
+/// or the contract can't transfer the tokens. Resolves to the reason of every deposit.
+pub struct DiagnoseDeposits<T: Transport> {
+	future: Join<Processed<T>, FetchTokenReserve<T>>,
+	values: Vec<ethabi::Uint>,
+}
+
+impl<T: Transport + Clone> Future for DiagnoseDeposits<T> {
+	type Item = Vec<Revert>;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		let (is_authority, reserve) = try_ready!(self.future.poll());
+		let reverts = self.values.iter()
+			.map(|value| diagnose_deposit(is_authority, &reserve, *value))
+			.collect();
+		Ok(Async::Ready(reverts))
+	}
+}
+
+/// Reason why a deposit of `value` reverts.
+fn diagnose_deposit(is_authority: bool, reserve: &TokenReserve, value: ethabi::Uint) -> Revert {
+	if !is_authority {
+		Revert::NotAuthority
+	} else if reserve.token.is_zero() {
+		Revert::TokenNotSet
+	} else if reserve.balance < value {
+		Revert::InsufficientTokenBalance
+	} else {
+		Revert::Unknown
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use rpc;
+	use web3;
+	use error::{Error, ErrorKind};
+	use super::{Revert, TokenReserve, is_rejected, exceeds_gas, diagnose_deposit};
+
+	fn rpc_error(code: i64, message: &str, data: Option<rpc::Value>) -> Error {
+		let err = rpc::Error {
+			code: rpc::ErrorCode::ServerError(code),
+			message: message.into(),
+			data,
+		};
+		ErrorKind::Web3(web3::error::ErrorKind::Rpc(err).into()).into()
+	}
+
+	#[test]
+	fn test_is_rejected() {
+		assert!(is_rejected(&rpc_error(-32000, "execution reverted", None)));
+		assert!(is_rejected(&rpc_error(3, "execution reverted: not an authority", None)));
+		assert!(is_rejected(&rpc_error(-32000, "gas required exceeds allowance or always failing transaction", None)));
+		assert!(is_rejected(&rpc_error(-32015, "Transaction execution error.", Some(rpc::Value::String("Reverted".into())))));
+		assert!(is_rejected(&rpc_error(-32000, "gas required exceeds allowance (253)", None)));
+		assert!(is_rejected(&rpc_error(-32000, "Requires higher than upper limit of 253", None)));
+		assert!(is_rejected(&rpc_error(-32015, "Transaction execution error.", Some(rpc::Value::String("OutOfGas".into())))));
+
+		assert!(!is_rejected(&rpc_error(-32015, "Transaction execution error.", Some(rpc::Value::String("BadInstruction".into())))));
+		assert!(!is_rejected(&rpc_error(-32000, "header not found", None)));
+		let invalid = web3::error::ErrorKind::Rpc(rpc::Error::invalid_params("invalid address")).into();
+		assert!(!is_rejected(&ErrorKind::Web3(invalid).into()));
+		let internal = web3::error::ErrorKind::Rpc(rpc::Error::internal_error()).into();
+		assert!(!is_rejected(&ErrorKind::Web3(internal).into()));
+		let lost = web3::error::ErrorKind::Transport("connection reset".into()).into();
+		assert!(!is_rejected(&ErrorKind::Web3(lost).into()));
+		assert!(!is_rejected(&Error::from(ErrorKind::Timeout("eth_estimateGas"))));
+	}
+
+	#[test]
+	fn test_exceeds_gas() {
+		assert_eq!(None, exceeds_gas(0x5208.into(), None));
+		assert_eq!(None, exceeds_gas(0x5208.into(), Some(0.into())));
+		assert_eq!(None, exceeds_gas(0x5208.into(), Some(0x5208.into())));
+		assert!(exceeds_gas(0x5208.into(), Some(0xfd.into())).is_some());
+	}
+
+	#[test]
+	fn test_diagnose_deposit() {
+		let reserve = TokenReserve {
+			token: 0xaa.into(),
+			balance: 0x1000.into(),
+		};
+		let unset = TokenReserve {
+			token: 0.into(),
+			balance: 0.into(),
+		};
+
+		assert_eq!(Revert::NotAuthority, diagnose_deposit(false, &reserve, 0xf0.into()));
+		assert_eq!(Revert::TokenNotSet, diagnose_deposit(true, &unset, 0xf0.into()));
+		assert_eq!(Revert::InsufficientTokenBalance, diagnose_deposit(true, &reserve, 0x1001.into()));
+		assert_eq!(Revert::Unknown, diagnose_deposit(true, &reserve, 0x1000.into()));
+	}
+}
@@ -0,0 +1,128 @@
+/// Subscriptions to new blocks pushed by nodes connected over websockets.
+
+use std::time::Duration;
+use futures::{Future, Stream, Poll, Async};
+use serde_json;
+use tokio_timer::{Timer, Timeout};
+use web3::{DuplexTransport, Transport};
+use web3::transports::ws::WebSocket;
+use web3::api::SubscriptionId;
+use api::{ApiCall, BlockHeader, subscribe_new_heads};
+use error::{Error, ErrorKind, ResultExt};
+use failover::FailoverTransport;
+
+/// Numbers of new blocks of the chain.
+pub type NewHeads = Box<Stream<Item = u64, Error = Error>>;
+
+/// Transports of nodes which can push new blocks to the bridge, if their current endpoint is connected over websockets.
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions {
+	pub home: Option<FailoverTransport>,
+	pub foreign: Option<FailoverTransport>,
+}
+
+enum NewHeadsState {
+	/// Subscribing over the current websocket once polled.
+	Idle,
+	/// Waiting for the node to confirm the subscription.
+	Subscribe {
+		websocket: WebSocket,
+		future: Timeout<ApiCall<String, <WebSocket as Transport>::Out>>,
+	},
+	/// Receiving notifications.
+	Notifications {
+		websocket: WebSocket,
+		id: SubscriptionId,
+		stream: <WebSocket as DuplexTransport>::NotificationStream,
+	},
+}
+
+/// Creates a stream of numbers of new blocks pushed by the current endpoint of `transport` with `eth_subscribe("newHeads")`.
+///
+/// The websocket is resolved every time the stream subscribes. Once the subscription fails or is closed,
+/// e.g. after a reconnect or a failover, the stream returns an error and subscribes again when it's polled next time.
+pub fn new_heads(transport: FailoverTransport, timer: Timer, request_timeout: Duration) -> NewHeads {
+	Box::new(NewHeadsStream {
+		transport,
+		timer,
+		request_timeout,
+		state: NewHeadsState::Idle,
+	})
+}
+
+struct NewHeadsStream {
+	transport: FailoverTransport,
+	timer: Timer,
+	request_timeout: Duration,
+	state: NewHeadsState,
+}
+
+impl NewHeadsStream {
+	fn poll_subscription(&mut self) -> Poll<Option<u64>, Error> {
+		loop {
+			let next_state = match self.state {
+				NewHeadsState::Idle => {
+					let websocket = self.transport.websocket().ok_or("current endpoint is not connected over websockets")?;
+					NewHeadsState::Subscribe {
+						future: self.timer.timeout(subscribe_new_heads(&websocket), self.request_timeout),
+						websocket,
+					}
+				},
+				NewHeadsState::Subscribe { ref mut future, ref websocket } => {
+					let id: SubscriptionId = try_ready!(future.poll()).into();
+					info!("subscribed to new heads with subscription {:?}", id);
+					NewHeadsState::Notifications {
+						stream: websocket.subscribe(&id),
+						websocket: websocket.clone(),
+						id,
+					}
+				},
+				NewHeadsState::Notifications { ref mut stream, .. } => {
+					let notification = match try_ready!(stream.poll().map_err(ErrorKind::Web3)) {
+						Some(notification) => notification,
+						None => return Ok(None.into()),
+					};
+					let header: BlockHeader = serde_json::from_value(notification)
+						.chain_err(|| "Invalid newHeads notification")?;
+					let number = header.number.ok_or("newHeads notification without block number")?;
+					return Ok(Some(number.low_u64()).into());
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+
+	/// Drops the subscription, so the stream subscribes again when it's polled next time.
+	fn reset(&mut self) {
+		if let NewHeadsState::Notifications { ref websocket, ref id, .. } = self.state {
+			websocket.unsubscribe(id);
+		}
+		self.state = NewHeadsState::Idle;
+	}
+}
+
+impl Stream for NewHeadsStream {
+	type Item = u64;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+		match self.poll_subscription() {
+			Ok(Async::Ready(None)) => {
+				self.reset();
+				Err("subscription has been closed".into())
+			},
+			Err(err) => {
+				self.reset();
+				Err(err)
+			},
+			result => result,
+		}
+	}
+}
+
+impl Drop for NewHeadsStream {
+	fn drop(&mut self) {
+		self.reset();
+	}
+}
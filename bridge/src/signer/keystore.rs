@@ -0,0 +1,328 @@
+/// Local signing with keys decrypted from V3 JSON keystore files.
+/// https://github.com/ethereum/wiki/wiki/Web3-Secret-Storage-Definition
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use crypto::aes::{ctr, KeySize};
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::scrypt::{scrypt, ScryptParams};
+use crypto::sha2::Sha256;
+use futures::future;
+use rlp::RlpStream;
+use rustc_hex::{FromHex, ToHex};
+use secp256k1::{self, Secp256k1, SecretKey, PublicKey, Message};
+use serde_json;
+use tiny_keccak::keccak256;
+use web3::Transport;
+use web3::types::{Address, Bytes, H520, U256, TransactionRequest};
+use error::{Error, ResultExt};
+use super::{Signer, SignMessage, SignTransaction, SignedTransaction};
+
+#[derive(Debug, Deserialize)]
+struct KeyFile {
+	address: Option<String>,
+	crypto: Crypto,
+}
+
+#[derive(Debug, Deserialize)]
+struct Crypto {
+	cipher: String,
+	cipherparams: CipherParams,
+	ciphertext: String,
+	kdfparams: KdfParams,
+	mac: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CipherParams {
+	iv: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KdfParams {
+	Scrypt {
+		dklen: usize,
+		n: u32,
+		p: u32,
+		r: u32,
+		salt: String,
+	},
+	Pbkdf2 {
+		c: u32,
+		dklen: usize,
+		prf: String,
+		salt: String,
+	},
+}
+
+/// Signs messages and transactions of a single account with the key loaded from the keystore.
+pub struct KeystoreSigner {
+	secp: Secp256k1<secp256k1::All>,
+	secret: SecretKey,
+	address: Address,
+	chain_id: u64,
+}
+
+impl KeystoreSigner {
+	/// Loads the key of `account` from `keystore` directory, decrypting it with the password stored in `password` file.
+	pub fn from_keystore<P: AsRef<Path>, Q: AsRef<Path>>(keystore: P, account: Address, password: Q, chain_id: u64) -> Result<Self, Error> {
+		let mut password_file = fs::File::open(password.as_ref()).chain_err(|| "Cannot open password file")?;
+		let mut password = String::new();
+		password_file.read_to_string(&mut password)?;
+		// password files usually end with a newline
+		let password = password.trim_right_matches(|c| c == '\r' || c == '\n');
+
+		let secret = load_secret(keystore, account, password)?;
+		let signer = Self::from_secret(&secret, chain_id)?;
+		if signer.address != account {
+			bail!("Key file of account {:?} contains key of account {:?}", account, signer.address);
+		}
+		Ok(signer)
+	}
+
+	pub fn from_secret(secret: &[u8], chain_id: u64) -> Result<Self, Error> {
+		let secp = Secp256k1::new();
+		let secret = SecretKey::from_slice(&secp, secret).map_err(|err| format!("Invalid secret key: {}", err))?;
+		let public = PublicKey::from_secret_key(&secp, &secret);
+		// address is the last 20 bytes of the hash of the public key without its prefix
+		let hash = keccak256(&public.serialize_uncompressed()[1..]);
+		Ok(KeystoreSigner {
+			secp,
+			secret,
+			address: Address::from(&hash[12..]),
+			chain_id,
+		})
+	}
+
+	pub fn address(&self) -> Address {
+		self.address
+	}
+
+	/// Returns `r`, `s` and recovery id of the signature of `hash`.
+	fn sign_hash(&self, hash: &[u8; 32]) -> Result<([u8; 64], u8), Error> {
+		let message = Message::from_slice(hash).map_err(|err| format!("Invalid message: {}", err))?;
+		let signature = self.secp.sign_recoverable(&message, &self.secret);
+		let (recovery_id, rs) = signature.serialize_compact(&self.secp);
+		Ok((rs, recovery_id.to_i32() as u8))
+	}
+
+	/// Signs the message the same way `eth_sign` does.
+	fn sign_raw_message(&self, message: &[u8]) -> Result<H520, Error> {
+		let mut prefixed = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+		prefixed.extend_from_slice(message);
+		let (rs, recovery_id) = self.sign_hash(&keccak256(&prefixed))?;
+
+		let mut signature = [0u8; 65];
+		signature[0..64].copy_from_slice(&rs);
+		signature[64] = 27 + recovery_id;
+		Ok(signature.into())
+	}
+
+	/// Signs the transaction with EIP-155 replay protection and returns it encoded for `eth_sendRawTransaction`.
+	///
+	/// Nonce, gas and gas price of the transaction must be set.
+	fn sign_raw_transaction(&self, request: &TransactionRequest) -> Result<Bytes, Error> {
+		let nonce = request.nonce.ok_or("Transaction nonce must be set to sign it locally")?;
+		let gas = request.gas.ok_or("Transaction gas must be set to sign it locally")?;
+		let gas_price = request.gas_price.ok_or("Transaction gas price must be set to sign it locally")?;
+
+		let append_unsigned = |stream: &mut RlpStream| {
+			stream.append(&int_bytes(nonce));
+			stream.append(&int_bytes(gas_price));
+			stream.append(&int_bytes(gas));
+			stream.append(&request.to.map(|to| to.0.to_vec()).unwrap_or_default());
+			stream.append(&int_bytes(request.value.unwrap_or_default()));
+			stream.append(&request.data.clone().map(|data| data.0).unwrap_or_default());
+		};
+
+		let mut unsigned = RlpStream::new_list(9);
+		append_unsigned(&mut unsigned);
+		unsigned.append(&int_bytes(self.chain_id.into()));
+		unsigned.append(&Vec::<u8>::new());
+		unsigned.append(&Vec::<u8>::new());
+		let (rs, recovery_id) = self.sign_hash(&keccak256(&unsigned.out()))?;
+
+		let mut signed = RlpStream::new_list(9);
+		append_unsigned(&mut signed);
+		signed.append(&int_bytes((recovery_id as u64 + 35 + self.chain_id * 2).into()));
+		signed.append(&trim_leading_zeros(&rs[0..32]));
+		signed.append(&trim_leading_zeros(&rs[32..64]));
+		Ok(signed.out().into())
+	}
+}
+
+impl<T: Transport> Signer<T> for KeystoreSigner {
+	fn sign_message(&self, _transport: &T, message: Vec<u8>) -> SignMessage<T> {
+		SignMessage::Ready(future::result(self.sign_raw_message(&message)))
+	}
+
+	fn sign_transaction(&self, request: TransactionRequest) -> SignTransaction {
+		let signed = self.sign_raw_transaction(&request).map(SignedTransaction::Raw);
+		SignTransaction::Ready(future::result(signed))
+	}
+}
+
+/// Big-endian representation of the integer without leading zeros, as required by RLP.
+fn int_bytes(value: U256) -> Vec<u8> {
+	let mut bytes = [0u8; 32];
+	value.to_big_endian(&mut bytes);
+	trim_leading_zeros(&bytes)
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> Vec<u8> {
+	let leading_zeros = bytes.iter().take_while(|b| **b == 0).count();
+	bytes[leading_zeros..].to_vec()
+}
+
+/// Finds the key file of `account` in `keystore` directory and returns its decrypted secret.
+fn load_secret<P: AsRef<Path>>(keystore: P, account: Address, password: &str) -> Result<Vec<u8>, Error> {
+	let address = account.0[..].to_hex();
+	let entries = fs::read_dir(keystore.as_ref()).chain_err(|| "Cannot read keystore")?;
+	for entry in entries {
+		let path = entry?.path();
+		if !path.is_file() {
+			continue;
+		}
+
+		// keystore may contain other files, skip everything that's not a key file
+		let key_file: KeyFile = match fs::File::open(&path).map(serde_json::from_reader) {
+			Ok(Ok(key_file)) => key_file,
+			_ => continue,
+		};
+
+		let matches = key_file.address.as_ref()
+			.map(|a| a.trim_left_matches("0x").to_lowercase() == address)
+			.unwrap_or(false);
+		if matches {
+			return decrypt(&key_file.crypto, password)
+				.chain_err(|| format!("Cannot decrypt key file {}", path.display()));
+		}
+	}
+
+	bail!("Key file of account {:?} not found in {}", account, keystore.as_ref().display())
+}
+
+fn from_hex(value: &str) -> Result<Vec<u8>, Error> {
+	Ok(value.trim_left_matches("0x").from_hex()?)
+}
+
+fn decrypt(crypto: &Crypto, password: &str) -> Result<Vec<u8>, Error> {
+	let derived_key = match crypto.kdfparams {
+		KdfParams::Scrypt { dklen, n, p, r, ref salt } => {
+			if !n.is_power_of_two() || n < 2 {
+				bail!("Invalid scrypt parameter n: {}", n);
+			}
+			let mut derived_key = vec![0u8; dklen];
+			let params = ScryptParams::new(n.trailing_zeros() as u8, r, p);
+			scrypt(password.as_bytes(), &from_hex(salt)?, &params, &mut derived_key);
+			derived_key
+		},
+		KdfParams::Pbkdf2 { c, dklen, ref prf, ref salt } => {
+			if prf != "hmac-sha256" {
+				bail!("Unsupported pbkdf2 prf: {}", prf);
+			}
+			let mut derived_key = vec![0u8; dklen];
+			let mut mac = Hmac::new(Sha256::new(), password.as_bytes());
+			pbkdf2(&mut mac, &from_hex(salt)?, c, &mut derived_key);
+			derived_key
+		},
+	};
+
+	if derived_key.len() < 32 {
+		bail!("Derived key must be at least 32 bytes long");
+	}
+
+	let ciphertext = from_hex(&crypto.ciphertext)?;
+	let mut mac_input = derived_key[16..32].to_vec();
+	mac_input.extend_from_slice(&ciphertext);
+	if keccak256(&mac_input)[..] != from_hex(&crypto.mac)?[..] {
+		bail!("Invalid password");
+	}
+
+	if crypto.cipher != "aes-128-ctr" {
+		bail!("Unsupported cipher: {}", crypto.cipher);
+	}
+
+	let mut secret = vec![0u8; ciphertext.len()];
+	ctr(KeySize::KeySize128, &derived_key[0..16], &from_hex(&crypto.cipherparams.iv)?)
+		.process(&ciphertext, &mut secret);
+	Ok(secret)
+}
+
+#[cfg(test)]
+mod tests {
+	use rustc_hex::FromHex;
+	use serde_json;
+	use web3::types::{Address, Bytes, TransactionRequest};
+	use super::{KeyFile, KeystoreSigner, decrypt};
+
+	#[test]
+	fn test_decrypt_pbkdf2_key_file() {
+		// test vector from the Web3 Secret Storage Definition
+		let key_file: KeyFile = serde_json::from_str(r#"{
+			"crypto": {
+				"cipher": "aes-128-ctr",
+				"cipherparams": { "iv": "6087dab2f9fdbbfaddc31a909735c1e6" },
+				"ciphertext": "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+				"kdf": "pbkdf2",
+				"kdfparams": {
+					"c": 262144,
+					"dklen": 32,
+					"prf": "hmac-sha256",
+					"salt": "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+				},
+				"mac": "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9a51a8f4a81"
+			},
+			"id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+			"version": 3
+		}"#).unwrap();
+
+		let expected = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d".from_hex().unwrap();
+		assert_eq!(expected, decrypt(&key_file.crypto, "testpassword").unwrap());
+		assert!(decrypt(&key_file.crypto, "wrongpassword").is_err());
+	}
+
+	#[test]
+	fn test_sign_transaction_eip155() {
+		// example from EIP-155
+		let secret = "4646464646464646464646464646464646464646464646464646464646464646".from_hex().unwrap();
+		let signer = KeystoreSigner::from_secret(&secret, 1).unwrap();
+		let expected_address: Address = "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f".parse().unwrap();
+		assert_eq!(expected_address, signer.address);
+
+		let request = TransactionRequest {
+			from: signer.address,
+			to: Some("3535353535353535353535353535353535353535".parse().unwrap()),
+			gas: Some(21000.into()),
+			gas_price: Some(20_000_000_000u64.into()),
+			value: Some(1_000_000_000_000_000_000u64.into()),
+			data: None,
+			nonce: Some(9.into()),
+			condition: None,
+		};
+
+		let expected: Bytes = "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83".from_hex().unwrap().into();
+		assert_eq!(expected, signer.sign_raw_transaction(&request).unwrap());
+	}
+
+	#[test]
+	fn test_sign_transaction_requires_nonce() {
+		let secret = "4646464646464646464646464646464646464646464646464646464646464646".from_hex().unwrap();
+		let signer = KeystoreSigner::from_secret(&secret, 1).unwrap();
+		let request = TransactionRequest {
+			from: signer.address,
+			to: None,
+			gas: Some(21000.into()),
+			gas_price: Some(1.into()),
+			value: None,
+			data: None,
+			nonce: None,
+			condition: None,
+		};
+
+		assert!(signer.sign_raw_transaction(&request).is_err());
+	}
+}
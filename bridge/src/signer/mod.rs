@@ -0,0 +1,153 @@
+/// Signing of messages and transactions of authority accounts.
+///
+/// Accounts can be unlocked on the node, loaded from the keystore
+/// or managed by a remote JSON-RPC signer.
+
+mod keystore;
+mod node;
+mod remote;
+
+use std::sync::Arc;
+use futures::{Future, Poll, Async};
+use futures::future::FutureResult;
+use tokio_core::reactor::Handle;
+use tokio_timer::{Timer, Timeout};
+use web3::Transport;
+use web3::transports::http::Http;
+use web3::types::{Bytes, H520, TransactionRequest};
+use api::{ApiCall, SignedTransactionResponse};
+use config::{Config, Node, SignerConfig};
+use error::{Error, ResultExt};
+
+pub use self::keystore::KeystoreSigner;
+pub use self::node::NodeSigner;
+pub use self::remote::RemoteSigner;
+
+/// Signs messages and transactions of a single account.
+pub trait Signer<T: Transport> {
+	/// Signs the message the same way `eth_sign` does.
+	fn sign_message(&self, transport: &T, message: Vec<u8>) -> SignMessage<T>;
+
+	/// Signs the transaction. Nonce, gas and gas price of the request must be set.
+	fn sign_transaction(&self, request: TransactionRequest) -> SignTransaction;
+}
+
+/// Transaction ready to be sent.
+#[derive(Debug)]
+pub enum SignedTransaction {
+	/// Signed and encoded transaction, sent with `eth_sendRawTransaction`.
+	Raw(Bytes),
+	/// Transaction signed by the node when it's sent with `eth_sendTransaction`.
+	Unsigned(TransactionRequest),
+}
+
+/// Future resolving to the signature of a message.
+///
+/// `T` is the transport of the node, `S` the transport of the remote signer.
+pub enum SignMessage<T: Transport, S: Transport = Http> {
+	Node(Timeout<ApiCall<H520, T::Out>>),
+	Remote(Timeout<ApiCall<H520, S::Out>>),
+	Ready(FutureResult<H520, Error>),
+}
+
+impl<T: Transport, S: Transport> Future for SignMessage<T, S> {
+	type Item = H520;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match *self {
+			SignMessage::Node(ref mut future) => future.poll(),
+			SignMessage::Remote(ref mut future) => future.poll(),
+			SignMessage::Ready(ref mut future) => future.poll(),
+		}
+	}
+}
+
+/// Future resolving to the transaction ready to be sent.
+///
+/// `S` is the transport of the remote signer.
+pub enum SignTransaction<S: Transport = Http> {
+	Remote(Timeout<ApiCall<SignedTransactionResponse, S::Out>>),
+	Ready(FutureResult<SignedTransaction, Error>),
+}
+
+impl<S: Transport> Future for SignTransaction<S> {
+	type Item = SignedTransaction;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		match *self {
+			SignTransaction::Remote(ref mut future) => {
+				let result = try_ready!(future.poll());
+				Ok(Async::Ready(SignedTransaction::Raw(result.raw)))
+			},
+			SignTransaction::Ready(ref mut future) => future.poll(),
+		}
+	}
+}
+
+/// Signer of an account selected in the config.
+pub enum AccountSigner {
+	Node(NodeSigner),
+	Keystore(KeystoreSigner),
+	Remote(RemoteSigner),
+}
+
+impl AccountSigner {
+	fn from_config(config: &Config, node: &Node, handle: &Handle, timer: &Timer) -> Result<Self, Error> {
+		let signer = match node.signer {
+			SignerConfig::Node => AccountSigner::Node(NodeSigner::new(node.account, timer.clone(), node.request_timeout)),
+			SignerConfig::Keystore => {
+				let chain_id = node.chain_id.ok_or("chain_id is required to sign transactions locally")?;
+				AccountSigner::Keystore(KeystoreSigner::from_keystore(&config.keystore, node.account, &node.password, chain_id)?)
+			},
+			SignerConfig::Remote { ref url } => {
+				AccountSigner::Remote(RemoteSigner::new(url, handle, node.account, timer.clone(), node.request_timeout)?)
+			},
+		};
+		Ok(signer)
+	}
+}
+
+impl<T: Transport> Signer<T> for AccountSigner {
+	fn sign_message(&self, transport: &T, message: Vec<u8>) -> SignMessage<T> {
+		match *self {
+			AccountSigner::Node(ref signer) => signer.sign_message(transport, message),
+			AccountSigner::Keystore(ref signer) => signer.sign_message(transport, message),
+			AccountSigner::Remote(ref signer) => signer.sign_message(transport, message),
+		}
+	}
+
+	fn sign_transaction(&self, request: TransactionRequest) -> SignTransaction {
+		match *self {
+			AccountSigner::Node(ref signer) => Signer::<T>::sign_transaction(signer, request),
+			AccountSigner::Keystore(ref signer) => Signer::<T>::sign_transaction(signer, request),
+			AccountSigner::Remote(ref signer) => Signer::<T>::sign_transaction(signer, request),
+		}
+	}
+}
+
+/// Signers of accounts used on both sides of the bridge.
+#[derive(Clone)]
+pub struct Signers {
+	pub home: Arc<AccountSigner>,
+	pub foreign: Arc<AccountSigner>,
+}
+
+impl Signers {
+	pub fn from_config(config: &Config, handle: &Handle, timer: &Timer) -> Result<Self, Error> {
+		let signers = Signers {
+			home: Arc::new(AccountSigner::from_config(config, &config.home, handle, timer).chain_err(|| "Cannot create home account signer")?),
+			foreign: Arc::new(AccountSigner::from_config(config, &config.foreign, handle, timer).chain_err(|| "Cannot create foreign account signer")?),
+		};
+		Ok(signers)
+	}
+
+	/// Signers of accounts unlocked on the nodes.
+	pub fn node(config: &Config, timer: &Timer) -> Self {
+		Signers {
+			home: Arc::new(AccountSigner::Node(NodeSigner::new(config.home.account, timer.clone(), config.home.request_timeout))),
+			foreign: Arc::new(AccountSigner::Node(NodeSigner::new(config.foreign.account, timer.clone(), config.foreign.request_timeout))),
+		}
+	}
+}
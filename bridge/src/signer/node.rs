@@ -0,0 +1,35 @@
+use std::time::Duration;
+use futures::future;
+use tokio_timer::Timer;
+use web3::Transport;
+use web3::types::{Address, Bytes, TransactionRequest};
+use api;
+use super::{Signer, SignMessage, SignTransaction, SignedTransaction};
+
+/// Account unlocked on the node. Messages are signed with `eth_sign`
+/// and transactions are signed by the node when they are sent.
+pub struct NodeSigner {
+	account: Address,
+	timer: Timer,
+	request_timeout: Duration,
+}
+
+impl NodeSigner {
+	pub fn new(account: Address, timer: Timer, request_timeout: Duration) -> Self {
+		NodeSigner {
+			account,
+			timer,
+			request_timeout,
+		}
+	}
+}
+
+impl<T: Transport> Signer<T> for NodeSigner {
+	fn sign_message(&self, transport: &T, message: Vec<u8>) -> SignMessage<T> {
+		SignMessage::Node(self.timer.timeout(api::sign(transport, self.account, Bytes(message)), self.request_timeout))
+	}
+
+	fn sign_transaction(&self, request: TransactionRequest) -> SignTransaction {
+		SignTransaction::Ready(future::ok(SignedTransaction::Unsigned(request)))
+	}
+}
@@ -0,0 +1,65 @@
+use std::time::Duration;
+use tokio_core::reactor::Handle;
+use tokio_timer::Timer;
+use web3::Transport;
+use web3::transports::http::Http;
+use web3::types::{Address, Bytes, TransactionRequest};
+use api;
+use error::{Error, ErrorKind, ResultExt};
+use super::{Signer, SignMessage, SignTransaction};
+
+/// Content type of `account_signData` requests signed the same way `eth_sign` does.
+const TEXT_PLAIN: &str = "text/plain";
+
+/// Account managed by a remote JSON-RPC signer with clef compatible api.
+pub struct RemoteSigner<S = Http> {
+	transport: S,
+	account: Address,
+	timer: Timer,
+	request_timeout: Duration,
+}
+
+impl RemoteSigner {
+	pub fn new(url: &str, handle: &Handle, account: Address, timer: Timer, request_timeout: Duration) -> Result<Self, Error> {
+		let transport = Http::with_event_loop(url, handle, 1)
+			.map_err(ErrorKind::Web3)
+			.map_err(Error::from)
+			.chain_err(|| format!("Cannot connect to remote signer {}", url))?;
+
+		Ok(RemoteSigner::with_transport(transport, account, timer, request_timeout))
+	}
+}
+
+impl<S: Transport> RemoteSigner<S> {
+	/// Creates signer of the `account` managed by the signer at the other end of the `transport`.
+	pub fn with_transport(transport: S, account: Address, timer: Timer, request_timeout: Duration) -> Self {
+		RemoteSigner {
+			transport,
+			account,
+			timer,
+			request_timeout,
+		}
+	}
+
+	/// Signs the message with `account_signData`.
+	pub fn sign_data<T: Transport>(&self, message: Vec<u8>) -> SignMessage<T, S> {
+		let call = api::account_sign_data(&self.transport, TEXT_PLAIN, self.account, Bytes(message));
+		SignMessage::Remote(self.timer.timeout(call, self.request_timeout))
+	}
+
+	/// Signs the transaction with `account_signTransaction`.
+	pub fn sign_raw_transaction(&self, request: TransactionRequest) -> SignTransaction<S> {
+		let call = api::account_sign_transaction(&self.transport, &request);
+		SignTransaction::Remote(self.timer.timeout(call, self.request_timeout))
+	}
+}
+
+impl<T: Transport> Signer<T> for RemoteSigner {
+	fn sign_message(&self, _transport: &T, message: Vec<u8>) -> SignMessage<T> {
+		self.sign_data(message)
+	}
+
+	fn sign_transaction(&self, request: TransactionRequest) -> SignTransaction {
+		self.sign_raw_transaction(request)
+	}
+}
@@ -0,0 +1,340 @@
+/// Health and status of the running bridge and the http server exposing them.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use futures::future::{self, FutureResult};
+use hyper::{self, Method, StatusCode};
+use hyper::header::{ContentLength, ContentType};
+use hyper::server::{Request, Response, Service};
+use serde_json;
+use tokio_core::reactor::Handle;
+use config::{Config, Node};
+use database::Database;
+use error::Error;
+use http;
+use journal::RelayKind;
+
+/// Connectivity of a single chain.
+#[derive(Debug, Default, Clone)]
+struct Chain {
+	/// Number of the latest block reported by the node.
+	head: Option<u64>,
+	/// When the node has responded with the latest block number for the last time.
+	last_seen: Option<SystemTime>,
+	/// Node is considered disconnected if it hasn't responded for longer than this.
+	stale_after: Duration,
+}
+
+impl Chain {
+	fn new(node: &Node) -> Self {
+		Chain {
+			head: None,
+			last_seen: None,
+			// the transport asks for the block number once per poll interval, even while no bridge component polls the chain
+			stale_after: (node.poll_interval + node.request_timeout) * 3,
+		}
+	}
+
+	fn is_connected(&self, now: SystemTime) -> bool {
+		self.last_seen
+			.and_then(|last_seen| now.duration_since(last_seen).ok())
+			.map_or(false, |elapsed| elapsed <= self.stale_after)
+	}
+
+	fn report(&self, now: SystemTime) -> ChainReport {
+		ChainReport {
+			connected: self.is_connected(now),
+			head: self.head,
+			last_seen: self.last_seen.map(unix_time),
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+struct State {
+	home: Chain,
+	foreign: Chain,
+	database: Option<Database>,
+	streams: BTreeMap<&'static str, &'static str>,
+	last_error: Option<ErrorReport>,
+}
+
+impl State {
+	fn chain(&mut self, chain: &str) -> &mut Chain {
+		match chain {
+			"home" => &mut self.home,
+			_ => &mut self.foreign,
+		}
+	}
+}
+
+/// Connectivity of a chain as reported by the status api.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ChainReport {
+	pub connected: bool,
+	pub head: Option<u64>,
+	/// Unix time of the last response of the node.
+	pub last_seen: Option<u64>,
+}
+
+/// Error as reported by the status api.
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct ErrorReport {
+	pub message: String,
+	/// Unix time of the error.
+	pub at: u64,
+}
+
+/// Health of the bridge, served at `/health`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HealthReport {
+	pub healthy: bool,
+	pub home: bool,
+	pub foreign: bool,
+}
+
+/// Status of the bridge, served at `/status`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct StatusReport {
+	pub home: ChainReport,
+	pub foreign: ChainReport,
+	/// Database with the latest checkpoints, `None` until the bridge is started.
+	pub database: Option<Database>,
+	/// Current state of each bridge component.
+	pub streams: BTreeMap<&'static str, &'static str>,
+	pub last_error: Option<ErrorReport>,
+}
+
+/// Health and status of the running bridge, shared by all bridge components.
+#[derive(Debug, Default)]
+pub struct Status {
+	state: Mutex<State>,
+}
+
+lazy_static! {
+	/// Status of the running bridge, receives errors recorded by components without access to it.
+	static ref INSTALLED: Mutex<Weak<Status>> = Mutex::new(Weak::new());
+}
+
+/// Records an error which has been retried or skipped in the installed status, the bridge keeps running.
+pub fn record_error(message: String) {
+	let installed = INSTALLED.lock().expect("status lock is never poisoned; qed").upgrade();
+	if let Some(status) = installed {
+		status.record_error(message);
+	}
+}
+
+fn unix_time(time: SystemTime) -> u64 {
+	time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+impl Status {
+	pub fn new(config: &Config) -> Self {
+		Status {
+			state: Mutex::new(State {
+				home: Chain::new(&config.home),
+				foreign: Chain::new(&config.foreign),
+				..Default::default()
+			}),
+		}
+	}
+
+	/// Makes `status` receive errors recorded by `record_error`.
+	pub fn install(status: &Arc<Status>) {
+		*INSTALLED.lock().expect("status lock is never poisoned; qed") = Arc::downgrade(status);
+	}
+
+	/// Records the latest block of the chain scanned by the bridge component.
+	pub fn head(&self, kind: RelayKind, block: u64) {
+		self.chain_head(kind.chain(), block);
+	}
+
+	/// Records the latest block number returned by the node of `chain`.
+	pub fn chain_head(&self, chain: &str, block: u64) {
+		let mut state = self.state.lock().expect("status lock is never poisoned; qed");
+		let chain = state.chain(chain);
+		chain.head = Some(block);
+		chain.last_seen = Some(SystemTime::now());
+	}
+
+	/// Records the database the bridge has been started with.
+	pub fn database(&self, database: &Database) {
+		let mut state = self.state.lock().expect("status lock is never poisoned; qed");
+		state.database = Some(database.clone());
+	}
+
+	/// Records the last block checked by the bridge component.
+	pub fn checked(&self, kind: RelayKind, block: u64) {
+		let mut state = self.state.lock().expect("status lock is never poisoned; qed");
+		if let Some(ref mut database) = state.database {
+			match kind {
+				RelayKind::DepositRelay => database.checked_deposit_relay = block,
+				RelayKind::WithdrawRelay => database.checked_withdraw_relay = block,
+				RelayKind::WithdrawConfirm => database.checked_withdraw_confirm = block,
+			}
+		}
+	}
+
+	/// Records current state of the bridge component.
+	pub fn stream(&self, kind: RelayKind, state: &'static str) {
+		let mut status = self.state.lock().expect("status lock is never poisoned; qed");
+		status.streams.insert(kind.as_str(), state);
+	}
+
+	/// Records the error which stopped the bridge.
+	pub fn error(&self, error: &Error) {
+		self.record_error(error.iter().map(|e| e.to_string()).collect::<Vec<_>>().join(": "));
+	}
+
+	/// Records an error which has been retried or skipped, the bridge keeps running.
+	pub fn record_error(&self, message: String) {
+		let mut state = self.state.lock().expect("status lock is never poisoned; qed");
+		state.last_error = Some(ErrorReport {
+			message,
+			at: unix_time(SystemTime::now()),
+		});
+	}
+
+	/// Bridge is healthy if both nodes have responded recently.
+	pub fn health(&self) -> HealthReport {
+		let state = self.state.lock().expect("status lock is never poisoned; qed");
+		let now = SystemTime::now();
+		let home = state.home.is_connected(now);
+		let foreign = state.foreign.is_connected(now);
+		HealthReport {
+			healthy: home && foreign,
+			home,
+			foreign,
+		}
+	}
+
+	pub fn report(&self) -> StatusReport {
+		let state = self.state.lock().expect("status lock is never poisoned; qed");
+		let now = SystemTime::now();
+		StatusReport {
+			home: state.home.report(now),
+			foreign: state.foreign.report(now),
+			database: state.database.clone(),
+			streams: state.streams.clone(),
+			last_error: state.last_error.clone(),
+		}
+	}
+}
+
+struct StatusService {
+	status: Arc<Status>,
+}
+
+fn json_response<T: ::serde::Serialize>(value: &T) -> Response {
+	let body = serde_json::to_vec(value).expect("serialization can't fail; qed");
+	Response::new()
+		.with_header(ContentType::json())
+		.with_header(ContentLength(body.len() as u64))
+		.with_body(body)
+}
+
+impl Service for StatusService {
+	type Request = Request;
+	type Response = Response;
+	type Error = hyper::Error;
+	type Future = FutureResult<Response, hyper::Error>;
+
+	fn call(&self, request: Request) -> Self::Future {
+		let response = match (request.method(), request.path()) {
+			(&Method::Get, "/health") => {
+				let health = self.status.health();
+				let code = if health.healthy { StatusCode::Ok } else { StatusCode::ServiceUnavailable };
+				json_response(&health).with_status(code)
+			},
+			(&Method::Get, "/status") => json_response(&self.status.report()),
+			_ => Response::new().with_status(StatusCode::NotFound),
+		};
+		future::ok(response)
+	}
+}
+
+/// Starts serving `http://<address>/health` and `http://<address>/status` on the event loop.
+pub fn serve(address: &SocketAddr, handle: &Handle, status: Arc<Status>) -> Result<(), Error> {
+	http::serve("status", address, handle, move || StatusService { status: status.clone() })?;
+	info!("serving status at http://{}/status", address);
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+	use std::time::{Duration, SystemTime};
+	use database::Database;
+	use error::{Error, ResultExt};
+	use journal::RelayKind;
+	use super::{Status, Chain, HealthReport, record_error};
+
+	#[test]
+	fn test_status_health() {
+		let status = Status::default();
+		assert_eq!(HealthReport { healthy: false, home: false, foreign: false }, status.health());
+
+		{
+			let mut state = status.state.lock().unwrap();
+			state.home.stale_after = Duration::from_secs(60);
+			state.foreign.stale_after = Duration::from_secs(60);
+		}
+
+		status.head(RelayKind::DepositRelay, 10);
+		assert_eq!(HealthReport { healthy: false, home: true, foreign: false }, status.health());
+		status.head(RelayKind::WithdrawConfirm, 20);
+		assert_eq!(HealthReport { healthy: true, home: true, foreign: true }, status.health());
+	}
+
+	#[test]
+	fn test_chain_is_stale() {
+		let now = SystemTime::now();
+		let chain = Chain {
+			head: Some(10),
+			last_seen: Some(now - Duration::from_secs(61)),
+			stale_after: Duration::from_secs(60),
+		};
+		assert!(!chain.is_connected(now));
+		assert!(chain.is_connected(now - Duration::from_secs(1)));
+	}
+
+	#[test]
+	fn test_status_report() {
+		let status = Status::default();
+		status.checked(RelayKind::DepositRelay, 5);
+		assert_eq!(None, status.report().database);
+
+		status.database(&Database::default());
+		status.checked(RelayKind::DepositRelay, 5);
+		status.checked(RelayKind::WithdrawConfirm, 7);
+		status.stream(RelayKind::DepositRelay, "relay_deposits");
+		status.head(RelayKind::WithdrawRelay, 30);
+		let error: Result<(), Error> = Err(Error::from("connection refused")).chain_err(|| "Cannot fetch logs");
+		status.error(&error.unwrap_err());
+
+		let report = status.report();
+		let database = report.database.unwrap();
+		assert_eq!(5, database.checked_deposit_relay);
+		assert_eq!(0, database.checked_withdraw_relay);
+		assert_eq!(7, database.checked_withdraw_confirm);
+		assert_eq!(Some(&"relay_deposits"), report.streams.get("deposit_relay"));
+		assert_eq!(None, report.home.head);
+		assert_eq!(Some(30), report.foreign.head);
+		assert_eq!("Cannot fetch logs: connection refused", report.last_error.unwrap().message);
+	}
+
+	#[test]
+	fn test_status_record_error() {
+		let status = Arc::new(Status::default());
+		record_error("eth_getLogs failed: timeout".into());
+		assert_eq!(None, status.report().last_error);
+
+		Status::install(&status);
+		record_error("eth_getLogs failed: timeout".into());
+		assert_eq!("eth_getLogs failed: timeout", status.report().last_error.unwrap().message);
+		status.chain_head("home", 40);
+		assert_eq!(Some(40), status.report().home.head);
+	}
+}
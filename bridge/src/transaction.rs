@@ -0,0 +1,293 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{Future, Poll, Async};
+use futures::future::{JoinAll, join_all};
+use tokio_timer::{Timer, Timeout, Sleep};
+use web3::Transport;
+use web3::types::{H256, U256, Transaction, TransactionRequest};
+use api::{self, ApiCall, RetryCall, Receipt, retry_call};
+use config::{Resubmission, Retry};
+use error::{Error, ErrorKind};
+use signer::{AccountSigner, Signer, SignTransaction, SignedTransaction};
+
+/// Used for `PendingTransaction` initialization.
+pub struct PendingTransactionInit {
+	/// Hash of the transaction which has been sent.
+	pub hash: H256,
+	/// Request which has been used to send the transaction.
+	pub request: TransactionRequest,
+	pub request_timeout: Duration,
+	pub poll_interval: Duration,
+	pub confirmations: usize,
+	/// If set, transaction is resubmitted with a higher gas price when it's not mined in time.
+	pub resubmission: Option<Resubmission>,
+	/// Signs replacements of the transaction.
+	pub signer: Arc<AccountSigner>,
+	/// Policies of retrying requests for receipts, transactions and block numbers.
+	pub retry: Retry,
+}
+
+/// Final state of a pending transaction.
+#[derive(Debug)]
+pub enum Outcome {
+	/// Transaction has been mined and has enough confirmations.
+	Mined(Receipt),
+	/// Transaction has been mined, but its execution failed.
+	Failed(H256),
+	/// Transaction has been dropped from the transaction pool.
+	Dropped(H256),
+}
+
+impl Outcome {
+	/// Returns receipt of the mined transaction, failing if the transaction failed or has been dropped.
+	pub fn receipt(self) -> Result<Receipt, Error> {
+		match self {
+			Outcome::Mined(receipt) => Ok(receipt),
+			Outcome::Failed(hash) => Err(ErrorKind::TransactionFailed(hash).into()),
+			Outcome::Dropped(hash) => Err(ErrorKind::TransactionDropped(hash).into()),
+		}
+	}
+}
+
+/// Number of consecutive polls for which the node must not know the latest replacement
+/// before the transaction is reported as dropped. A failover endpoint may not have seen it yet.
+const DROPPED_AFTER_POLLS: usize = 2;
+
+/// Pending transaction state.
+enum PendingTransactionState<T: Transport> {
+	/// Waiting for timer to poll.
+	Wait(Sleep),
+	/// Fetching receipts of the transaction and all its replacements.
+	FetchReceipts(JoinAll<Vec<RetryCall<T, Option<Receipt>>>>),
+	/// Transaction is not mined yet. Checking if the node still knows about the latest replacement.
+	FetchTransaction(RetryCall<T, Option<Transaction>>),
+	/// Transaction is not mined in time. Signing replacement with the same nonce and higher gas price.
+	SignReplacement {
+		gas_price: U256,
+		future: SignTransaction,
+	},
+	/// Sending signed replacement.
+	Resubmit {
+		gas_price: U256,
+		future: Timeout<ApiCall<H256, T::Out>>,
+	},
+	/// Transaction is mined. Checking if it has enough confirmations.
+	FetchBlockNumber {
+		receipt: Option<Receipt>,
+		future: RetryCall<T, U256>,
+	},
+}
+
+/// Creates new `PendingTransaction`.
+pub fn pending_transaction<T: Transport + Clone>(transport: T, timer: Timer, init: PendingTransactionInit) -> PendingTransaction<T> {
+	let receipt = api::transaction_receipt(&transport, init.hash);
+	let state = PendingTransactionState::FetchReceipts(join_all(vec![
+		retry_call(transport.clone(), timer.clone(), init.request_timeout, &init.retry, receipt)
+	]));
+
+	PendingTransaction {
+		transport,
+		timer,
+		hashes: vec![init.hash],
+		request: init.request,
+		sent_at: Instant::now(),
+		missing: 0,
+		request_timeout: init.request_timeout,
+		poll_interval: init.poll_interval,
+		confirmations: init.confirmations,
+		resubmission: init.resubmission,
+		signer: init.signer,
+		retry: init.retry,
+		state,
+	}
+}
+
+/// Resolves to the transaction receipt once the transaction is mined and has `confirmations`.
+///
+/// Transaction which has been mined but whose execution failed, or which has been dropped
+/// from the transaction pool, resolves to the corresponding `Outcome` and is left to the caller.
+/// The transaction is dropped only if the node didn't know its latest replacement on `DROPPED_AFTER_POLLS`
+/// consecutive polls and none of the replacement chain has been mined afterwards, since the latest
+/// replacement also disappears from the pool once an earlier one is mined.
+pub struct PendingTransaction<T: Transport> {
+	transport: T,
+	timer: Timer,
+	/// Hashes of the original transaction and all its replacements, oldest first.
+	hashes: Vec<H256>,
+	/// Request used to send the latest replacement.
+	request: TransactionRequest,
+	/// When the latest replacement has been sent.
+	sent_at: Instant,
+	/// Number of consecutive polls for which the node didn't know the latest replacement.
+	missing: usize,
+	request_timeout: Duration,
+	poll_interval: Duration,
+	confirmations: usize,
+	resubmission: Option<Resubmission>,
+	signer: Arc<AccountSigner>,
+	retry: Retry,
+	state: PendingTransactionState<T>,
+}
+
+impl<T: Transport + Clone> PendingTransaction<T> {
+	/// Hash of the latest replacement of the tracked transaction.
+	pub fn hash(&self) -> H256 {
+		*self.hashes.last().expect("there is always at least one hash; qed")
+	}
+
+	/// Hashes of the original transaction and all its replacements, oldest first.
+	pub fn hashes(&self) -> &[H256] {
+		&self.hashes
+	}
+
+	/// Sends `call`, retrying it if it fails with a transient error.
+	fn retry_call<R>(&self, call: ApiCall<R, T::Out>) -> RetryCall<T, R> {
+		retry_call(self.transport.clone(), self.timer.clone(), self.request_timeout, &self.retry, call)
+	}
+
+	fn wait(&self) -> PendingTransactionState<T> {
+		PendingTransactionState::Wait(self.timer.sleep(self.poll_interval))
+	}
+
+	fn fetch_receipts(&self) -> PendingTransactionState<T> {
+		let receipts = self.hashes.iter()
+			.map(|hash| self.retry_call(api::transaction_receipt(&self.transport, *hash)))
+			.collect();
+		PendingTransactionState::FetchReceipts(join_all(receipts))
+	}
+
+	fn fetch_transaction(&self) -> PendingTransactionState<T> {
+		PendingTransactionState::FetchTransaction(self.retry_call(api::transaction(&self.transport, self.hash())))
+	}
+
+	/// Returns `SignReplacement` state if the latest replacement is not mined in time
+	/// and its gas price can still be increased.
+	fn resubmit(&self, transaction: &Transaction) -> Option<PendingTransactionState<T>> {
+		let resubmission = self.resubmission.as_ref()?;
+		if self.sent_at.elapsed() < resubmission.timeout {
+			return None;
+		}
+
+		let gas_price = resubmission.next_gas_price(transaction.gas_price)?;
+		let request = TransactionRequest {
+			nonce: Some(transaction.nonce),
+			gas_price: Some(gas_price),
+			..self.request.clone()
+		};
+
+		info!("transaction {:?} is not mined after {:?}, resubmitting with gas price {}", self.hash(), resubmission.timeout, gas_price);
+		Some(PendingTransactionState::SignReplacement {
+			gas_price,
+			future: Signer::<T>::sign_transaction(&*self.signer, request),
+		})
+	}
+
+	fn send_replacement(&self, gas_price: U256, transaction: SignedTransaction) -> PendingTransactionState<T> {
+		let future = match transaction {
+			SignedTransaction::Raw(raw) => api::send_raw_transaction(&self.transport, raw),
+			SignedTransaction::Unsigned(request) => api::send_transaction(&self.transport, request),
+		};
+		PendingTransactionState::Resubmit {
+			gas_price,
+			future: self.timer.timeout(future, self.request_timeout),
+		}
+	}
+}
+
+impl<T: Transport + Clone> Future for PendingTransaction<T> {
+	type Item = Outcome;
+	type Error = Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				PendingTransactionState::Wait(ref mut future) => {
+					try_ready!(future.poll());
+					self.fetch_receipts()
+				},
+				PendingTransactionState::FetchReceipts(ref mut future) => {
+					// any transaction from the replacement chain may be mined
+					match try_ready!(future.poll()).into_iter().filter_map(|receipt| receipt).next() {
+						Some(receipt) => {
+							if receipt.status == Some(U256::zero()) {
+								return Ok(Async::Ready(Outcome::Failed(receipt.transaction_hash)));
+							}
+
+							match receipt.block_number {
+								// parity returns receipts of pending transactions without block number
+								None => self.fetch_transaction(),
+								Some(_) if self.confirmations == 0 => return Ok(Async::Ready(Outcome::Mined(receipt))),
+								Some(_) => PendingTransactionState::FetchBlockNumber {
+									receipt: Some(receipt),
+									future: self.retry_call(api::block_number(&self.transport)),
+								},
+							}
+						},
+						None if self.missing >= DROPPED_AFTER_POLLS => return Ok(Async::Ready(Outcome::Dropped(self.hash()))),
+						None => self.fetch_transaction(),
+					}
+				},
+				PendingTransactionState::FetchTransaction(ref mut future) => match try_ready!(future.poll()) {
+					Some(transaction) => {
+						self.missing = 0;
+						self.resubmit(&transaction).unwrap_or_else(|| self.wait())
+					},
+					None => {
+						self.missing += 1;
+						warn!("transaction {:?} is unknown to the node", self.hash());
+						if self.missing >= DROPPED_AFTER_POLLS {
+							// check the whole replacement chain once more, an earlier transaction might have been mined
+							self.fetch_receipts()
+						} else {
+							self.wait()
+						}
+					},
+				},
+				PendingTransactionState::SignReplacement { ref mut future, gas_price } => match future.poll() {
+					Ok(Async::NotReady) => return Ok(Async::NotReady),
+					Ok(Async::Ready(transaction)) => self.send_replacement(gas_price, transaction),
+					Err(err) => {
+						warn!("failed to sign replacement of transaction {:?}: {}", self.hash(), err);
+						self.wait()
+					},
+				},
+				PendingTransactionState::Resubmit { ref mut future, gas_price } => {
+					match future.poll() {
+						Ok(Async::NotReady) => return Ok(Async::NotReady),
+						Ok(Async::Ready(hash)) => {
+							self.hashes.push(hash);
+							self.request.gas_price = Some(gas_price);
+							self.sent_at = Instant::now();
+							self.missing = 0;
+							info!("transaction replaced, replacement chain: {:?}", self.hashes);
+						},
+						// previous transaction might have been mined in the meantime,
+						// keep tracking the existing replacement chain
+						Err(err) => {
+							let err: Error = err.into();
+							warn!("failed to resubmit transaction {:?}: {}", self.hash(), err);
+						},
+					}
+					self.wait()
+				},
+				PendingTransactionState::FetchBlockNumber { ref mut future, ref mut receipt } => {
+					let last_block = try_ready!(future.poll()).low_u64();
+					let mined_at = receipt.as_ref()
+						.and_then(|receipt| receipt.block_number)
+						.expect("FetchBlockNumber state is entered only for mined receipts; qed")
+						.low_u64();
+					if mined_at + self.confirmations as u64 <= last_block {
+						let receipt = receipt.take().expect("receipt is taken only once; qed");
+						if self.hashes.len() > 1 {
+							info!("transaction {:?} mined, replacement chain: {:?}", receipt.transaction_hash, self.hashes);
+						}
+						return Ok(Async::Ready(Outcome::Mined(receipt)));
+					}
+					self.wait()
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
@@ -0,0 +1,322 @@
+/// Transport chosen at runtime from the config, reconnecting to the node once the connection is lost.
+
+use std::{cmp, fmt};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::Duration;
+use futures::{Future, Poll};
+use rpc;
+use tokio_core::reactor::Handle;
+use tokio_timer::{Timer, Sleep};
+use web3::{self, RequestId, Transport};
+use web3::helpers;
+use web3::transports::http::Http;
+use web3::transports::ipc::Ipc;
+use web3::transports::ws::WebSocket;
+use config::TransportConfig;
+use error::{Error, ErrorKind, ResultExt};
+
+/// Any of the transports supported by the bridge.
+#[derive(Debug, Clone)]
+pub enum AnyTransport {
+	Ipc(Ipc),
+	Http(Http),
+	Ws(WebSocket),
+}
+
+impl AnyTransport {
+	/// Connects to the node. `name` of the chain is used only in error messages.
+	pub fn new(handle: &Handle, config: &TransportConfig, name: &str) -> Result<Self, Error> {
+		let result = match *config {
+			TransportConfig::Ipc(ref path) => Ipc::with_event_loop(path, handle)
+				.map(AnyTransport::Ipc)
+				.map_err(ErrorKind::Web3)
+				.map_err(Error::from)
+				.chain_err(|| format!("Cannot connect to {} node ipc", name))?,
+			TransportConfig::Http(ref url) => Http::with_event_loop(url, handle, 1)
+				.map(AnyTransport::Http)
+				.map_err(ErrorKind::Web3)
+				.map_err(Error::from)
+				.chain_err(|| format!("Cannot connect to {} node rpc", name))?,
+			TransportConfig::Ws(ref url) => WebSocket::with_event_loop(url, handle)
+				.map(AnyTransport::Ws)
+				.map_err(ErrorKind::Web3)
+				.map_err(Error::from)
+				.chain_err(|| format!("Cannot connect to {} node websocket", name))?,
+		};
+
+		Ok(result)
+	}
+
+	/// Websocket connection which supports subscriptions, if any.
+	pub fn websocket(&self) -> Option<WebSocket> {
+		match *self {
+			AnyTransport::Ws(ref transport) => Some(transport.clone()),
+			AnyTransport::Ipc(_) | AnyTransport::Http(_) => None,
+		}
+	}
+}
+
+impl Transport for AnyTransport {
+	type Out = web3::Result<rpc::Value>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		match *self {
+			AnyTransport::Ipc(ref transport) => transport.prepare(method, params),
+			AnyTransport::Http(ref transport) => transport.prepare(method, params),
+			AnyTransport::Ws(ref transport) => transport.prepare(method, params),
+		}
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		match *self {
+			AnyTransport::Ipc(ref transport) => Box::new(transport.send(id, request)),
+			AnyTransport::Http(ref transport) => Box::new(transport.send(id, request)),
+			AnyTransport::Ws(ref transport) => Box::new(transport.send(id, request)),
+		}
+	}
+}
+
+/// Delay before the first reconnection attempt. Doubled after every failed attempt.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+/// Maximum delay between reconnection attempts.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Returns true if the request failed because the connection to the node has been lost.
+pub fn is_connection_error(err: &web3::Error) -> bool {
+	match *err.kind() {
+		web3::error::ErrorKind::Io(_) | web3::error::ErrorKind::Transport(_) | web3::error::ErrorKind::Unreachable => true,
+		_ => false,
+	}
+}
+
+/// Returns false for requests which must not be sent again after the connection has been lost,
+/// because the node might have received them. `SendTransaction` checks the transaction count of the account instead.
+fn is_replayable(request: &rpc::Call) -> bool {
+	match *request {
+		rpc::Call::MethodCall(ref call) => match call.method.as_str() {
+			"eth_sendRawTransaction" | "eth_sendTransaction" => false,
+			_ => true,
+		},
+		_ => true,
+	}
+}
+
+/// Delay before the reconnection attempt following `failures` failed requests or attempts.
+fn reconnect_delay(failures: u32) -> Duration {
+	let multiplier = 1u32.checked_shl(failures.saturating_sub(1)).unwrap_or(u32::max_value());
+	cmp::min(RECONNECT_MIN_DELAY.checked_mul(multiplier).unwrap_or(RECONNECT_MAX_DELAY), RECONNECT_MAX_DELAY)
+}
+
+#[derive(Debug, Default)]
+struct Connection {
+	transport: Option<AnyTransport>,
+	/// Incremented with every new connection, so requests sent over a connection
+	/// which has already been replaced don't drop the new one.
+	generation: u64,
+	/// Number of failures since the last successful request.
+	failures: u32,
+}
+
+/// Transport which re-establishes lost connections with exponential backoff.
+///
+/// Requests which failed because the connection has been lost are sent again once the bridge reconnects.
+/// They are still bounded by the `request_timeout` of the caller. Transactions are never sent again,
+/// their error is returned instead.
+#[derive(Clone)]
+pub struct ReconnectingTransport {
+	config: TransportConfig,
+	name: &'static str,
+	handle: Handle,
+	timer: Timer,
+	connection: Rc<RefCell<Connection>>,
+	ids: Rc<Cell<RequestId>>,
+}
+
+impl fmt::Debug for ReconnectingTransport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("ReconnectingTransport")
+			.field("config", &self.config)
+			.field("name", &self.name)
+			.field("connection", &self.connection)
+			.finish()
+	}
+}
+
+impl ReconnectingTransport {
+	/// Connects to the node. `name` of the chain is used only in logs and error messages.
+	pub fn new(handle: &Handle, timer: &Timer, config: &TransportConfig, name: &'static str) -> Result<Self, Error> {
+		let transport = AnyTransport::new(handle, config, name)?;
+		let result = ReconnectingTransport {
+			config: config.clone(),
+			name,
+			handle: handle.clone(),
+			timer: timer.clone(),
+			connection: Rc::new(RefCell::new(Connection {
+				transport: Some(transport),
+				generation: 0,
+				failures: 0,
+			})),
+			ids: Default::default(),
+		};
+		Ok(result)
+	}
+
+	/// Websocket connection which supports subscriptions, if currently connected over websockets.
+	pub fn websocket(&self) -> Option<WebSocket> {
+		self.connection.borrow().transport.as_ref().and_then(AnyTransport::websocket)
+	}
+
+	/// Returns false if the connection has been lost and hasn't been re-established yet.
+	pub fn is_connected(&self) -> bool {
+		self.connection.borrow().transport.is_some()
+	}
+
+	/// Current connection, reconnects if it has been lost.
+	fn connect(&self) -> Result<(u64, AnyTransport), Error> {
+		let mut connection = self.connection.borrow_mut();
+		if let Some(ref transport) = connection.transport {
+			return Ok((connection.generation, transport.clone()));
+		}
+
+		let transport = AnyTransport::new(&self.handle, &self.config, self.name)?;
+		info!("reconnected to {} node", self.name);
+		connection.generation += 1;
+		connection.transport = Some(transport.clone());
+		Ok((connection.generation, transport))
+	}
+
+	/// Drops the connection, unless it has already been replaced.
+	fn disconnect(&self, generation: u64) {
+		let mut connection = self.connection.borrow_mut();
+		if connection.generation == generation && connection.transport.take().is_some() {
+			warn!("connection to {} node has been lost", self.name);
+		}
+	}
+
+	/// Waits before the next reconnection attempt.
+	fn backoff(&self) -> Sleep {
+		let mut connection = self.connection.borrow_mut();
+		connection.failures += 1;
+		let delay = reconnect_delay(connection.failures);
+		info!("reconnecting to {} node in {:?}", self.name, delay);
+		self.timer.sleep(delay)
+	}
+
+	fn succeeded(&self) {
+		self.connection.borrow_mut().failures = 0;
+	}
+}
+
+impl Transport for ReconnectingTransport {
+	type Out = web3::Result<rpc::Value>;
+
+	fn prepare(&self, method: &str, params: Vec<rpc::Value>) -> (RequestId, rpc::Call) {
+		let id = self.ids.get() + 1;
+		self.ids.set(id);
+		(id, helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, request: rpc::Call) -> Self::Out {
+		Box::new(ReconnectingRequest {
+			transport: self.clone(),
+			id,
+			replayable: is_replayable(&request),
+			request,
+			state: ReconnectingRequestState::Connect,
+		})
+	}
+}
+
+enum ReconnectingRequestState {
+	/// Connecting to the node, if the connection has been lost.
+	Connect,
+	/// Waiting for the response.
+	Send {
+		generation: u64,
+		future: web3::Result<rpc::Value>,
+	},
+	/// Waiting before the next reconnection attempt.
+	Backoff(Sleep),
+}
+
+/// Request which is sent again once the connection to the node is re-established.
+struct ReconnectingRequest {
+	transport: ReconnectingTransport,
+	id: RequestId,
+	request: rpc::Call,
+	/// False if the request must not be sent again after a connection error.
+	replayable: bool,
+	state: ReconnectingRequestState,
+}
+
+impl Future for ReconnectingRequest {
+	type Item = rpc::Value;
+	type Error = web3::Error;
+
+	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+		loop {
+			let next_state = match self.state {
+				ReconnectingRequestState::Connect => match self.transport.connect() {
+					Ok((generation, transport)) => ReconnectingRequestState::Send {
+						generation,
+						future: transport.send(self.id, self.request.clone()),
+					},
+					Err(err) => {
+						warn!("{}", err);
+						ReconnectingRequestState::Backoff(self.transport.backoff())
+					},
+				},
+				ReconnectingRequestState::Send { generation, ref mut future } => match future.poll() {
+					Err(ref err) if is_connection_error(err) && self.replayable => {
+						self.transport.disconnect(generation);
+						ReconnectingRequestState::Backoff(self.transport.backoff())
+					},
+					Err(err) => {
+						if is_connection_error(&err) {
+							// the next request reconnects
+							self.transport.disconnect(generation);
+						}
+						return Err(err);
+					},
+					Ok(result) => {
+						if result.is_ready() {
+							self.transport.succeeded();
+						}
+						return Ok(result);
+					},
+				},
+				ReconnectingRequestState::Backoff(ref mut sleep) => {
+					try_ready!(sleep.poll().map_err(|err| web3::Error::from(web3::error::ErrorKind::Transport(err.to_string()))));
+					ReconnectingRequestState::Connect
+				},
+			};
+
+			self.state = next_state;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+	use web3::helpers;
+	use super::{reconnect_delay, is_replayable};
+
+	#[test]
+	fn test_reconnect_delay() {
+		assert_eq!(Duration::from_secs(1), reconnect_delay(1));
+		assert_eq!(Duration::from_secs(2), reconnect_delay(2));
+		assert_eq!(Duration::from_secs(32), reconnect_delay(6));
+		assert_eq!(Duration::from_secs(60), reconnect_delay(7));
+		assert_eq!(Duration::from_secs(60), reconnect_delay(100));
+	}
+
+	#[test]
+	fn test_is_replayable() {
+		assert!(is_replayable(&helpers::build_request(1, "eth_getLogs", vec![])));
+		assert!(is_replayable(&helpers::build_request(1, "eth_getTransactionReceipt", vec![])));
+		assert!(!is_replayable(&helpers::build_request(1, "eth_sendRawTransaction", vec![])));
+		assert!(!is_replayable(&helpers::build_request(1, "eth_sendTransaction", vec![])));
+	}
+}
@@ -10,19 +10,20 @@ extern crate env_logger;
 extern crate bridge;
 extern crate ctrlc;
 
-use std::{env, fs, io};
+use std::{env, io};
 use std::sync::Arc;
 use std::path::PathBuf;
 use docopt::Docopt;
-use futures::{Stream, future};
+use futures::{Future, Stream, future};
 use tokio_core::reactor::Core;
 
 use bridge::app::App;
-use bridge::bridge::{create_bridge, create_deploy, Deployed};
+use bridge::bridge::{create_bridge, create_sync_nonces, create_deploy, create_deploy_dry_run, create_init, create_load_gas_limits, create_load_gas_prices, create_verify, create_set_token_address, create_load_token_address_votes, create_token_address_events};
 use bridge::config::Config;
+use bridge::database::Database;
 use bridge::error::{Error, ErrorKind};
 use bridge::web3;
-use web3::Transport;
+use bridge::web3::types::Address;
 
 const ERR_UNKNOWN: i32 = 1;
 const ERR_IO_ERROR: i32 = 2;
@@ -65,17 +66,33 @@ Ethereum-Kovan bridge.
     Copyright 2017 Parity Technologies (UK) Limited
 
 Usage:
-    bridge --config <config> --database <database>
+    bridge run --config <config> --database <database>
+    bridge deploy --config <config> --database <database> [--dry-run]
+    bridge init --config <config> --database <database> --home-contract <home> --foreign-contract <foreign>
+    bridge set-token-address --config <config> --database <database> --token <token> [--wait]
+    bridge token-address-votes --config <config> --database <database> --token <token>
     bridge -h | --help
 
 Options:
     -h, --help           Display help message and exit.
+    --dry-run            Print the deployment transactions without sending them.
+    --wait               Wait until the token address is set by enough authorities.
 "#;
 
 #[derive(Debug, Deserialize)]
 pub struct Args {
+	cmd_run: bool,
+	cmd_deploy: bool,
+	cmd_init: bool,
+	cmd_set_token_address: bool,
+	cmd_token_address_votes: bool,
+	flag_dry_run: bool,
+	flag_wait: bool,
 	arg_config: PathBuf,
 	arg_database: PathBuf,
+	arg_home: Option<String>,
+	arg_foreign: Option<String>,
+	arg_token: Option<String>,
 }
 
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -107,6 +124,12 @@ fn print_err(err: Error) {
 	println!("{}", message);
 }
 
+fn parse_address(address: Option<String>, chain: &str) -> Result<Address, UserFacingError> {
+	let address = address.unwrap_or_default();
+	address.trim_left_matches("0x").parse()
+		.map_err(|_| format!("Invalid address of the {} contract: {}", chain, address).into())
+}
+
 fn execute<S, I>(command: I, running: Arc<AtomicBool>) -> Result<String, UserFacingError> where I: IntoIterator<Item=S>, S: AsRef<str> {
 	info!(target: "bridge", "Parsing cli arguments");
 	let args: Args = Docopt::new(USAGE)
@@ -118,119 +141,118 @@ fn execute<S, I>(command: I, running: Arc<AtomicBool>) -> Result<String, UserFac
 	info!(target: "bridge", "Starting event loop");
 	let mut event_loop = Core::new().unwrap();
 
-	info!(target: "bridge", "Home IPC file stem {:?}", config.clone().home.ipc.file_stem());
-
-	info!(target: "bridge", "Home rpc host {}", config.clone().home.rpc_host);
-
-/*
-	// FIXME [edwardmack], figure out how to make this work.
-	// I thought that since App<T> where T: Transport, and since Ipc and Http are Transport why doesn't this work
-	let app = match config.clone().home.ipc.file_stem() {
-		Some(_) =>
-			{
-				info!(target:"bridge", "USE IPC");
-				match App::new_ipc(config.clone(), &args.arg_database, &event_loop.handle(), running) {
-					Ok(app) => app,
-					Err(e) => {
-						warn!("Can't establish an IPC connection: {:?}", e);
-						return Err((ERR_CANNOT_CONNECT, e).into());
-					},
-				}
-			},
-		None =>
-			{
-				info!(target: "bridge", "USE RPC");
-				match App::new_http(config.clone(), &args.arg_database, &event_loop.handle(), running) {
-					Ok(app) => app,
-					Err(e) => {
-						warn!("Can't establish an IPC connection: {:?}", e);
-						return Err((ERR_CANNOT_CONNECT, e).into());
-					},
-				}
-			},
-
-	};
-*/
+	// other commands, including the dry run, must not bind the metrics port or create the journal
+	let persistent = args.cmd_run || args.cmd_init || (args.cmd_deploy && !args.flag_dry_run);
 
-	info!(target: "bridge", "Establishing connection:");
+	if persistent {
+		if let Some(ref metrics) = config.metrics {
+			bridge::metrics::serve(&metrics.address, &event_loop.handle())?;
+		}
+	}
 
-	info!(target: "bridge", "  using IPC connection");
-	let app_ipc = match App::new_ipc(config.clone(), &args.arg_database, &event_loop.handle(), running.clone()) {
+	info!(target: "bridge", "Establishing connection to home node endpoints {:?}", config.home.endpoints);
+	info!(target: "bridge", "Establishing connection to foreign node endpoints {:?}", config.foreign.endpoints);
+	let mut app = match App::from_config(config.clone(), &args.arg_database, &event_loop.handle(), running) {
 		Ok(app) => app,
 		Err(e) => {
-			warn!("Can't establish an IPC connection: {:?}", e);
+			warn!("Can't establish a connection: {:?}", e);
 			return Err((ERR_CANNOT_CONNECT, e).into());
 		},
 	};
 
+	if args.cmd_init && app.database_path.exists() {
+		return Err(format!("Database {:?} already exists", app.database_path).into());
+	}
 
-	info!(target:"bridge", "  using RPC connection");
-	let app_rpc = match App::new_http(config.clone(), &args.arg_database, &event_loop.handle(), running.clone()) {
-		Ok(app) => app,
-		Err(e) => {
-			warn!("Can't establish an RPC connection: {:?}", e);
-			return Err((ERR_CANNOT_CONNECT, e).into());
+	if args.cmd_deploy && !args.flag_dry_run && app.database_path.exists() {
+		return Err(format!("Database {:?} already exists, contracts have already been deployed", app.database_path).into());
+	}
+
+	if persistent {
+		app.open_journal()?;
+	}
+
+	let app_ref = Arc::new(app);
+
+	if args.cmd_init {
+		let home_contract = parse_address(args.arg_home, "home")?;
+		let foreign_contract = parse_address(args.arg_foreign, "foreign")?;
+
+		info!(target: "bridge", "Reading deployment of contracts {:?} and {:?}", home_contract, foreign_contract);
+		let database = event_loop.run(create_init(app_ref.clone(), home_contract, foreign_contract))?;
+		info!(target: "bridge", "\n\n{}\n", database);
+		database.store(&app_ref.database_path)?;
+		app_ref.journal.reset(&database)?;
+		return Ok(format!("Initialized database {:?}", app_ref.database_path));
+	}
+
+	info!(target: "bridge", "Fetching gas prices");
+	event_loop.run(create_load_gas_prices(app_ref.clone()))?;
+
+	if args.cmd_deploy {
+		if args.flag_dry_run {
+			info!(target: "bridge", "Estimating deployment of contracts");
+			let plan = event_loop.run(create_deploy_dry_run(app_ref.clone()))?;
+			return Ok(format!("{}\n\nDry run, no transactions have been sent", plan));
+		}
+
+		info!(target: "bridge", "Deploying contracts");
+		let database = event_loop.run(create_deploy(app_ref.clone()))?;
+		info!(target: "bridge", "Deployed new bridge contracts");
+		info!(target: "bridge", "\n\n{}\n", database);
+		database.store(&app_ref.database_path)?;
+		app_ref.journal.reset(&database)?;
+		return Ok(format!("Deployed bridge contracts, stored database {:?}", app_ref.database_path));
+	}
+
+	let database = match Database::load(&app_ref.database_path) {
+		Ok(database) => database,
+		Err(Error(ErrorKind::MissingFile(_), _)) => {
+			return Err(format!("Database {:?} doesn't exist, create it with `bridge deploy` or `bridge init`", app_ref.database_path).into());
 		},
+		Err(err) => return Err(err.into()),
 	};
+	info!(target: "bridge", "Loaded database");
 
+	if args.cmd_token_address_votes {
+		let token = parse_address(args.arg_token, "token")?;
+		let votes = event_loop.run(create_load_token_address_votes(app_ref.clone(), &database, token))?;
+		return Ok(votes.to_string());
+	}
 
-/*
-	let app_ref = match config.clone().home.ipc.file_stem() {
-		Some(_) =>
-			{
-				info!(target:"bridge", "USE IPC");
-				app_rpc.as_ref()
-			},
-		None =>
-			{
-				info!(target: "bridge", "USE RPC");
-				app_ipc.as_ref()
-			},
+	if args.cmd_set_token_address {
+		let token = parse_address(args.arg_token, "token")?;
+		info!(target: "bridge", "Voting for token {:?}", token);
+		let votes = event_loop.run(create_set_token_address(app_ref.clone(), &database, token))?;
+		if args.flag_wait && votes.current != token {
+			info!(target: "bridge", "Waiting for TokenAddress event of token {:?}", token);
+			let events = create_token_address_events(app_ref.clone(), &database)
+				.filter(|event| *event == token)
+				.into_future()
+				.map_err(|(err, _)| err);
+			event_loop.run(events)?;
+			return Ok(format!("{}\n\ntoken address has been set to {:?}", votes, token));
+		}
+		return Ok(votes.to_string());
+	}
 
-	};
-*/
-	let app_test;
+	let database = app_ref.journal.restore(&database)?;
 
-	if let Some(_) = config.clone().home.ipc.file_stem() {
-		app_test = match App::new_ipc(config.clone(), &args.arg_database, &event_loop.handle(), running.clone()) {
-			Ok(app) => app,
-			Err(e) => {
-				warn!("Can't establish an IPC connection: {:?}", e);
-				return Err((ERR_CANNOT_CONNECT, e).into());
-			},
-		};
-		println!("has file");
-	} else {
-		app_test = match App::new_http(config.clone(), &args.arg_database, &event_loop.handle(), running.clone()) {
-			Ok(app) => app,
-			Err(e) => {
-				warn!("Can't establish an RPC connection: {:?}", e);
-				return Err((ERR_CANNOT_CONNECT, e).into());
-			},
-		};
+	if let Some(ref status) = config.status {
+		bridge::status::serve(&status.address, &event_loop.handle(), app_ref.status.clone())?;
 	}
 
-	//let app_ref = create_arc(app_rpc);
-	let app_ref = Arc::new(app_rpc.as_ref());
+	info!(target: "bridge", "Verifying contracts");
+	event_loop.run(create_verify(app_ref.clone(), &database))?;
 
-	info!(target: "bridge", "Deploying contracts (if needed)");
-	let deployed = event_loop.run(create_deploy(app_ref.clone()))?;
+	info!(target: "bridge", "Loading gas limits");
+	let gas_limits = event_loop.run(create_load_gas_limits(app_ref.clone(), &database))?;
 
-	let database = match deployed {
-		Deployed::New(database) => {
-			info!(target: "bridge", "Deployed new bridge contracts");
-			info!(target: "bridge", "\n\n{}\n", database);
-			database.save(fs::File::create(&app_ref.database_path)?)?;
-			database
-		},
-		Deployed::Existing(database) => {
-			info!(target: "bridge", "Loaded database");
-			database
-		},
-	};
+	info!(target: "bridge", "Synchronising nonces");
+	event_loop.run(create_sync_nonces(app_ref.clone()))?;
 
 	info!(target: "bridge", "Starting listening to events");
-	let bridge = create_bridge(app_ref.clone(), &database).and_then(|_| future::ok(true)).collect();
+	let bridge = create_bridge(app_ref.clone(), &database, &gas_limits).and_then(|_| future::ok(true)).collect();
 	let result = event_loop.run(bridge);
 	match result {
 			Err(Error(ErrorKind::Web3(web3::error::Error(web3::error::ErrorKind::Io(e), _)), _)) => {
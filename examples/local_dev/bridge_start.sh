@@ -4,4 +4,8 @@ BRIDGE='../../target/debug/bridge'
 CONFIG='--config config/bridge_config.toml'
 DATABASE='--database tmp/bridge1_db.txt'
 
-RUST_LOG=info $BRIDGE $CONFIG $DATABASE
+if [ ! -f tmp/bridge1_db.txt ]; then
+	RUST_LOG=info $BRIDGE deploy $CONFIG $DATABASE || exit 1
+fi
+
+RUST_LOG=info $BRIDGE run $CONFIG $DATABASE
@@ -157,17 +157,30 @@ fn test_basic_deposit_then_withdraw() {
 	// give nodes time to start up
 	thread::sleep(Duration::from_millis(10000));
 
+	// deploy the contracts
+	assert!(Command::new("env")
+		.arg("RUST_BACKTRACE=1")
+		.arg("../target/debug/bridge")
+		.env("RUST_LOG", "info")
+		.arg("deploy")
+		.arg("--config").arg("bridge_config.toml")
+		.arg("--database").arg("tmp/bridge1_db.txt")
+		.status()
+		.expect("failed to deploy bridge contracts")
+		.success());
+
 	// start bridge authority 1
 	let mut bridge1 = Command::new("env")
 		.arg("RUST_BACKTRACE=1")
 		.arg("../target/debug/bridge")
 		.env("RUST_LOG", "info")
+		.arg("run")
 		.arg("--config").arg("bridge_config.toml")
 		.arg("--database").arg("tmp/bridge1_db.txt")
 		.spawn()
 		.expect("failed to spawn bridge process");
 
-	// give the bridge time to start up and deploy the contracts
+	// give the bridge time to start up
 	thread::sleep(Duration::from_millis(10000));
 
 	let home_contract_address = "0xebd3944af37ccc6b67ff61239ac4fef229c8f69f";
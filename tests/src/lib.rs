@@ -46,8 +46,15 @@ impl Transport for MockedTransport {
 
 	fn send(&self, _id: usize, _request: rpc::Call) -> web3::Result<rpc::Value> {
 		let response = self.mocked_responses.iter().nth(self.requests.get() - 1).expect("missing response");
-		let f = futures::finished(response.clone());
-		Box::new(f)
+		// response with an `error` member is an error reported by the node
+		let result = match response.get("error") {
+			Some(error) => {
+				let error: rpc::Error = serde_json::from_value(error.clone()).expect("invalid mocked error");
+				Err(web3::ErrorKind::Rpc(error).into())
+			},
+			None => Ok(response.clone()),
+		};
+		Box::new(futures::future::result(result))
 	}
 }
 
@@ -80,8 +87,8 @@ macro_rules! test_app_stream {
 	(
 		name => $name: ident,
 		database => $db: expr,
-		home => account => $home_acc: expr, confirmations => $home_conf: expr;
-		foreign => account => $foreign_acc: expr, confirmations => $foreign_conf: expr;
+		home => account => $home_acc: expr, confirmations => $home_conf: expr, reorg_depth => $home_reorg: expr;
+		foreign => account => $foreign_acc: expr, confirmations => $foreign_conf: expr, reorg_depth => $foreign_reorg: expr;
 		authorities => accounts => $authorities_accs: expr, signatures => $signatures: expr;
 		txs => $txs: expr,
 		init => $init_stream: expr,
@@ -93,11 +100,16 @@ macro_rules! test_app_stream {
 		#[allow(unused_imports)]
 		fn $name() {
 			use self::std::sync::Arc;
+			use self::std::sync::atomic::AtomicBool;
 			use self::std::time::Duration;
 			use self::futures::{Future, Stream};
 			use self::bridge::app::{App, Connections};
 			use self::bridge::contracts::{foreign, home};
-			use self::bridge::config::{Config, Authorities, Node, ContractConfig, Transactions, TransactionConfig};
+			use self::bridge::config::{Config, Authorities, Node, ContractConfig, Transactions, TransactionConfig, SignerConfig, TransportConfig};
+			use self::bridge::signer::Signers;
+			use self::bridge::status::Status;
+			use self::bridge::gas_limits::GasLimits;
+			use self::bridge::gas_price::GasPrices;
 			use self::bridge::database::Database;
 
 			let home = $crate::MockedTransport {
@@ -116,29 +128,39 @@ macro_rules! test_app_stream {
 				txs: $txs,
 				home: Node {
 					account: $home_acc.parse().unwrap(),
-					ipc: "".into(),
+					endpoints: vec![TransportConfig::Ipc("".into())],
+					quorum: 1,
+					max_lag: 5,
 					contract: ContractConfig {
 						bin: Default::default(),
 					},
 					poll_interval: Duration::from_secs(0),
 					request_timeout: Duration::from_secs(5),
 					required_confirmations: $home_conf,
-					rpc_host: "".into(),
-					rpc_port: 8545,
+					reorg_depth: $home_reorg,
+					max_block_range: None,
 					password: "".into(),
+					chain_id: None,
+					signer: SignerConfig::Node,
+					gas_price: Default::default(),
 				},
 				foreign: Node {
 					account: $foreign_acc.parse().unwrap(),
-					ipc: "".into(),
+					endpoints: vec![TransportConfig::Ipc("".into())],
+					quorum: 1,
+					max_lag: 5,
 					contract: ContractConfig {
 						bin: Default::default(),
 					},
 					poll_interval: Duration::from_secs(0),
 					request_timeout: Duration::from_secs(5),
 					required_confirmations: $foreign_conf,
-					rpc_host: "".into(),
-					rpc_port: 8545,
+					reorg_depth: $foreign_reorg,
+					max_block_range: None,
 					password: "".into(),
+					chain_id: None,
+					signer: SignerConfig::Node,
+					gas_price: Default::default(),
 				},
 				authorities: Authorities {
 					accounts: $authorities_accs.iter().map(|a: &&str| a.parse().unwrap()).collect(),
@@ -146,8 +168,16 @@ macro_rules! test_app_stream {
 				},
 				estimated_gas_cost_of_withdraw: 100_000,
 				keystore: "".into(),
+				metrics: None,
+				status: None,
+				retry: Default::default(),
 			};
 
+			let timer = Default::default();
+			let signers = Signers::node(&config, &timer);
+			let status = Arc::new(Status::new(&config));
+			let gas_limits = Arc::new(GasLimits::new(&config.txs));
+			let gas_prices = Arc::new(GasPrices::new(&config));
 			let app = App {
 				config,
 				database_path: "".into(),
@@ -157,7 +187,15 @@ macro_rules! test_app_stream {
 				},
 				home_bridge: home::HomeBridge::default(),
 				foreign_bridge: foreign::ForeignBridge::default(),
-				timer: Default::default(),
+				timer,
+				running: Arc::new(AtomicBool::new(true)),
+				nonces: Default::default(),
+				signers,
+				journal: Default::default(),
+				status,
+				subscriptions: Default::default(),
+				gas_limits,
+				gas_prices,
 			};
 
 			let app = Arc::new(app);
@@ -182,6 +220,31 @@ macro_rules! test_app_stream {
 				foreign.requests.get()
 			);
 		}
+	};
+	(
+		name => $name: ident,
+		database => $db: expr,
+		home => account => $home_acc: expr, confirmations => $home_conf: expr;
+		foreign => account => $foreign_acc: expr, confirmations => $foreign_conf: expr;
+		authorities => accounts => $authorities_accs: expr, signatures => $signatures: expr;
+		txs => $txs: expr,
+		init => $init_stream: expr,
+		expected => $expected: expr,
+		home_transport => [$($home_method: expr => req => $home_req: expr, res => $home_res: expr ;)*],
+		foreign_transport => [$($foreign_method: expr => req => $foreign_req: expr, res => $foreign_res: expr ;)*]
+	) => {
+		test_app_stream! {
+			name => $name,
+			database => $db,
+			home => account => $home_acc, confirmations => $home_conf, reorg_depth => 0;
+			foreign => account => $foreign_acc, confirmations => $foreign_conf, reorg_depth => 0;
+			authorities => accounts => $authorities_accs, signatures => $signatures;
+			txs => $txs,
+			init => $init_stream,
+			expected => $expected,
+			home_transport => [$($home_method => req => $home_req, res => $home_res ;)*],
+			foreign_transport => [$($foreign_method => req => $foreign_req, res => $foreign_res ;)*]
+		}
 	}
 }
 
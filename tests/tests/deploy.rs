@@ -0,0 +1,84 @@
+/// test interactions of deployment dry run with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+extern crate rustc_hex;
+extern crate ethereum_types;
+
+use rustc_hex::ToHex;
+use bridge::bridge::{create_deploy_dry_run, DeployPlan, PlannedDeployment};
+use bridge::contracts;
+
+fn authorities() -> Vec<ethereum_types::Address> {
+	vec![
+		"0000000000000000000000000000000000000001".parse().unwrap(),
+		"0000000000000000000000000000000000000002".parse().unwrap(),
+	]
+}
+
+fn home_data() -> Vec<u8> {
+	contracts::home::HomeBridge::default().constructor(vec![], 1, authorities(), 100_000)
+}
+
+fn foreign_data() -> Vec<u8> {
+	contracts::foreign::ForeignBridge::default().constructor(vec![], 1, authorities(), 100_000)
+}
+
+test_app_stream! {
+	name => deploy_dry_run,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000002",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, _db| create_deploy_dry_run(app).into_stream(),
+	expected => vec![DeployPlan {
+		authorities: authorities(),
+		required_signatures: 1,
+		home: PlannedDeployment {
+			contract: "HomeBridge",
+			from: "0000000000000000000000000000000000000001".parse().unwrap(),
+			gas: 0u64.into(),
+			gas_price: 0u64.into(),
+			estimated_gas: 0x1e8480u64.into(),
+			data: home_data(),
+		},
+		foreign: PlannedDeployment {
+			contract: "ForeignBridge",
+			from: "0000000000000000000000000000000000000002".parse().unwrap(),
+			gas: 0u64.into(),
+			gas_price: 0u64.into(),
+			estimated_gas: 0x2dc6c0u64.into(),
+			data: foreign_data(),
+		},
+	}],
+	home_transport => [
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": format!("0x{}", home_data().to_hex()),
+				"from": "0x0000000000000000000000000000000000000001"
+			}]),
+			res => json!("0x1e8480");
+	],
+	foreign_transport => [
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": format!("0x{}", foreign_data().to_hex()),
+				"from": "0x0000000000000000000000000000000000000002"
+			}]),
+			res => json!("0x2dc6c0");
+	]
+}
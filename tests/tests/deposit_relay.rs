@@ -4,10 +4,45 @@ extern crate serde_json;
 extern crate bridge;
 #[macro_use]
 extern crate tests;
+extern crate ethereum_types;
+extern crate rustc_hex;
 
+use std::sync::Arc;
+use ethereum_types::{Address, H256, U256};
+use rustc_hex::ToHex;
+use bridge::app::App;
 use bridge::bridge::create_deposit_relay;
+use bridge::contracts;
+use bridge::journal::{JournalEntry, Relay, RelayKind, RelayStatus};
+use tests::MockedTransport;
 
 const DEPOSIT_TOPIC: &str = "0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c";
+const NOT_SIGNED: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+const TOKEN: &str = "0x00000000000000000000000000000000000000aa";
+const TOKEN_OUTPUT: &str = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+const RESERVE: &str = "0x0000000000000000000000000000000000000000000000000000000000001000";
+
+/// Payload of `ForeignBridge.erc20token`.
+fn token_payload() -> String {
+	format!("0x{}", contracts::foreign::ForeignBridge::default().functions().erc20token().input().to_hex())
+}
+
+/// Payload of `ERC20.balanceOf` the `ForeignBridge` used by all tests.
+fn reserve_payload() -> String {
+	let payload = contracts::erc20::ERC20::default().functions().balance_of().input("0000000000000000000000000000000000000000".parse::<Address>().unwrap());
+	format!("0x{}", payload.to_hex())
+}
+
+/// Storage position of the `ForeignBridge.deposits_signed` flag of the deposit of 0xf0 used by all tests.
+fn deposit_signed_position(authority: &str, transaction_hash: &str) -> U256 {
+	contracts::storage::foreign_deposit_signed(
+		authority.parse::<Address>().unwrap(),
+		"aff3454fce5edbc8cca8697c15331677e6ebcccc".parse::<Address>().unwrap(),
+		U256::from(0xf0),
+		transaction_hash.parse::<H256>().unwrap(),
+	)
+}
 
 test_app_stream! {
 	name => deposit_relay_basic,
@@ -110,20 +145,58 @@ test_app_stream! {
 			res => json!([]);
 	],
 	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x0",
+				"nonce": "0x0",
 				"to": "0x0000000000000000000000000000000000000000"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
 	]
 }
 
 test_app_stream! {
-	name => deposit_relay_check_gas,
+	name => deposit_relay_already_relayed,
 	database => Database {
 		checked_deposit_relay: 5,
 		..Default::default()
@@ -140,13 +213,69 @@ test_app_stream! {
 			"0000000000000000000000000000000000000002",
 		],
 		signatures => 1;
-	txs => Transactions {
-		deposit_relay: TransactionConfig {
-			gas: 0xfd,
-			gas_price: 0xa0,
-		},
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x6",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [DEPOSIT_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000001");
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!({
+				"error": {
+					"code": -32000,
+					"message": "execution reverted"
+				}
+			});
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_insufficient_reserve,
+	database => Database {
+		checked_deposit_relay: 5,
 		..Default::default()
 	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
 	init => |app, db| create_deposit_relay(app, db).take(1),
 	expected => vec![0x1005],
 	home_transport => [
@@ -158,33 +287,98 @@ test_app_stream! {
 				"address": ["0x0000000000000000000000000000000000000000"],
 				"fromBlock": "0x6",
 				"limit": null,
-				"toBlock": "0x1005",
+				"toBlock":"0x1005",
 				"topics": [[DEPOSIT_TOPIC], null, null, null]
 			}]),
 			res => json!([{
 				"address": "0x0000000000000000000000000000000000000000",
 				"topics": [DEPOSIT_TOPIC],
-				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
 			}]);
 	],
 	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!("0x00000000000000000000000000000000000000000000000000000000000000ef");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
 				"from": "0x0000000000000000000000000000000000000001",
-				"gas": "0xfd",
-				"gasPrice": "0xa0",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
 				"to": "0x0000000000000000000000000000000000000000"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
 	]
 }
 
+// relay of the deposit would revert because the account is no longer an authority on chain,
+// even though it's still listed in the config. the deposit is skipped.
 test_app_stream! {
-	name => deposit_relay_contract_address,
+	name => deposit_relay_not_authority_skipped,
 	database => Database {
-		home_contract_address: "0000000000000000000000000000000000000cc1".into(),
-		foreign_contract_address: "0000000000000000000000000000000000000dd1".into(),
+		checked_deposit_relay: 5,
 		..Default::default()
 	},
 	home =>
@@ -208,45 +402,84 @@ test_app_stream! {
 			res => json!("0x1011");
 		"eth_getLogs" =>
 			req => json!([{
-				"address": ["0x0000000000000000000000000000000000000cc1"],
-				"fromBlock": "0x1",
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x6",
 				"limit": null,
-				"toBlock": "0x1005",
+				"toBlock":"0x1005",
 				"topics": [[DEPOSIT_TOPIC], null, null, null]
 			}]),
 			res => json!([{
-				"address": "0x0000000000000000000000000000000000000cc1",
-				"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [DEPOSIT_TOPIC],
 				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
 				"type": "",
 				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
 			}]);
 	],
 	foreign_transport => [
-		"eth_sendTransaction" =>
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
 			req => json!([{
 				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x0",
-				"to": "0x0000000000000000000000000000000000000dd1"
+				"to": "0x0000000000000000000000000000000000000000"
 			}]),
-			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+			res => json!({
+				"error": {
+					"code": -32000,
+					"message": "execution reverted"
+				}
+			});
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		// `ForeignBridge.authorities`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x0000000000000000000000000000000000000000",
+				contracts::storage::foreign_authority("0000000000000000000000000000000000000001".parse::<Address>().unwrap()),
+				"latest"
+			]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000000");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
 	]
 }
 
 test_app_stream! {
-	name => deposit_relay_accounts,
+	name => deposit_relay_failed,
 	database => Database {
-		home_contract_address: "0000000000000000000000000000000000000cc1".into(),
-		foreign_contract_address: "0000000000000000000000000000000000000dd1".into(),
+		checked_deposit_relay: 5,
 		..Default::default()
 	},
 	home =>
-		account => "00000000000000000000000000000000000000ff",
+		account => "0000000000000000000000000000000000000001",
 		confirmations => 12;
 	foreign =>
-		account => "00000000000000000000000000000000000000ee",
+		account => "0000000000000000000000000000000000000001",
 		confirmations => 12;
 	authorities =>
 		accounts => [
@@ -263,14 +496,14 @@ test_app_stream! {
 			res => json!("0x1011");
 		"eth_getLogs" =>
 			req => json!([{
-				"address": ["0x0000000000000000000000000000000000000cc1"],
-				"fromBlock": "0x1",
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x6",
 				"limit": null,
-				"toBlock": "0x1005",
+				"toBlock":"0x1005",
 				"topics": [[DEPOSIT_TOPIC], null, null, null]
 			}]),
 			res => json!([{
-				"address": "0x0000000000000000000000000000000000000cc1",
+				"address": "0x0000000000000000000000000000000000000000",
 				"topics": [DEPOSIT_TOPIC],
 				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
 				"type": "",
@@ -278,21 +511,59 @@ test_app_stream! {
 			}]);
 	],
 	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
-				"from": "0x00000000000000000000000000000000000000ee",
+				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x0",
-				"to":"0x0000000000000000000000000000000000000dd1"
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x0"
+			});
 	]
 }
 
 test_app_stream! {
-	name => deposit_relay_multiple_logs,
-	database => Database::default(),
+	name => deposit_relay_dropped,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
 	home =>
 		account => "0000000000000000000000000000000000000001",
 		confirmations => 12;
@@ -315,46 +586,959 @@ test_app_stream! {
 		"eth_getLogs" =>
 			req => json!([{
 				"address": ["0x0000000000000000000000000000000000000000"],
-				"fromBlock": "0x1",
+				"fromBlock": "0x6",
 				"limit": null,
-				"toBlock": "0x1005",
+				"toBlock":"0x1005",
 				"topics": [[DEPOSIT_TOPIC], null, null, null]
 			}]),
-			res => json!([
-				{
-					"address": "0x0000000000000000000000000000000000000000",
-					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
-					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
-					"type": "",
-					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
-				},
-				{
-					"address":"0x0000000000000000000000000000000000000000",
-					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
-					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
-					"type": "",
-					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"
-				}
-			]);
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [DEPOSIT_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
 	],
 	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x0",
+				"nonce": "0x0",
 				"to": "0x0000000000000000000000000000000000000000"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!(null);
+		"eth_getTransactionByHash" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!(null);
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!(null);
+		"eth_getTransactionByHash" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!(null);
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!(null);
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		// nonce of the dropped transaction is handed out again
 		"eth_sendTransaction" =>
 			req => json!([{
-				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x0",
+				"nonce": "0x0",
 				"to": "0x0000000000000000000000000000000000000000"
 			}]),
-			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+			res => json!("0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_restored_failed,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app: Arc<App<&MockedTransport>>, db| {
+		// relay has been sent before restart
+		app.journal.record(vec![JournalEntry::Relay(Relay {
+			kind: RelayKind::DepositRelay,
+			source: "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse().unwrap(),
+			destination: "1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b".parse().unwrap(),
+			status: RelayStatus::Sent,
+			block: 0x1005,
+		})]).unwrap();
+		create_deposit_relay(app, db).take(1)
+	},
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x6",
+				"limit": null,
+				"toBlock":"0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [DEPOSIT_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	],
+	foreign_transport => [
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x0"
+			});
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x2db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_check_gas,
+	database => Database {
+		checked_deposit_relay: 5,
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig {
+			gas: 0xfd,
+			gas_price: 0xa0,
+		},
+		..Default::default()
+	},
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x6",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [DEPOSIT_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0","type":"","transactionHash":"0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0xfd",
+				"gasPrice": "0xa0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0xfd");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0xfd",
+				"gasPrice": "0xa0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_contract_address,
+	database => Database {
+		home_contract_address: "0000000000000000000000000000000000000cc1".into(),
+		foreign_contract_address: "0000000000000000000000000000000000000dd1".into(),
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000cc1"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000cc1",
+				"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000dd1", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000dd1"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000dd1"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_accounts,
+	database => Database {
+		home_contract_address: "0000000000000000000000000000000000000cc1".into(),
+		foreign_contract_address: "0000000000000000000000000000000000000dd1".into(),
+		..Default::default()
+	},
+	home =>
+		account => "00000000000000000000000000000000000000ff",
+		confirmations => 12;
+	foreign =>
+		account => "00000000000000000000000000000000000000ee",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000cc1"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000cc1",
+				"topics": [DEPOSIT_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000dd1", deposit_signed_position("00000000000000000000000000000000000000ee", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x00000000000000000000000000000000000000ee",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to":"0x0000000000000000000000000000000000000dd1"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x00000000000000000000000000000000000000ee", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x00000000000000000000000000000000000000ee",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to":"0x0000000000000000000000000000000000000dd1"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+test_app_stream! {
+	name => deposit_relay_multiple_logs,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([
+				{
+					"address": "0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+				},
+				{
+					"address":"0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"
+				}
+			]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x1",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+// the reserve covers only the first of two deposits. it's relayed right away,
+// the second one is queued until the reserve is topped up.
+test_app_stream! {
+	name => deposit_relay_partial_reserve,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([
+				{
+					"address": "0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+				},
+				{
+					"address":"0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"
+				}
+			]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000100");
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x1",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	]
+}
+
+// the node rejects the relay of the second of two deposits. the first one is confirmed,
+// the second one is queued and relayed again after the poll interval.
+test_app_stream! {
+	name => deposit_relay_one_send_failed,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_deposit_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[DEPOSIT_TOPIC], null, null, null]
+			}]),
+			res => json!([
+				{
+					"address": "0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+				},
+				{
+					"address":"0x0000000000000000000000000000000000000000",
+					"topics": ["0xe1fffcc4923d04b559f4d29a8bfc6cda04eb5b0d3c460751c2402c5c5cc9109c"],
+					"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+					"type": "",
+					"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"
+				}
+			]);
+	],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x1",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!({"error": {"code": -32000, "message": "insufficient funds for gas * price + value"}});
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", deposit_signed_position("0000000000000000000000000000000000000001", "884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f"), "latest"]),
+			res => json!(NOT_SIGNED);
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x5208");
+		"eth_call" =>
+			req => json!([{
+				"data": token_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(TOKEN_OUTPUT);
+		"eth_call" =>
+			req => json!([{
+				"data": reserve_payload(),
+				"to": TOKEN
+			}, "latest"]),
+			res => json!(RESERVE);
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": "0x26b3293f000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a942436f",
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x0",
+				"nonce": "0x1",
+				"to": "0x0000000000000000000000000000000000000000"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
 	]
 }
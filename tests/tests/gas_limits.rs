@@ -0,0 +1,175 @@
+/// test interactions of gas limits updates with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+extern crate rustc_hex;
+
+use rustc_hex::ToHex;
+use bridge::bridge::{create_foreign_gas_limits_updates, GasLimitsLoaded};
+use bridge::contracts::foreign::ForeignBridge;
+
+const GAS_LIMITS_TOPIC: &str = "0x3b49a33ec45179ab3408f6f29a2b208c909ab1460e94af7558808ea22854c106";
+
+/// Payload of `ForeignBridge.gasLimitDepositRelay`.
+fn deposit_relay_payload() -> String {
+	format!("0x{}", ForeignBridge::default().functions().gas_limit_deposit_relay().input().to_hex())
+}
+
+/// Payload of `ForeignBridge.gasLimitWithdrawConfirm`.
+fn withdraw_confirm_payload() -> String {
+	format!("0x{}", ForeignBridge::default().functions().gas_limit_withdraw_confirm().input().to_hex())
+}
+
+test_app_stream! {
+	name => foreign_gas_limits_update,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig { gas: 50_000, gas_price: 0 },
+		withdraw_confirm: TransactionConfig { gas: 50_000, gas_price: 0 },
+		..Default::default()
+	},
+	init => |app: Arc<App<_>>, db| {
+		let loaded = GasLimitsLoaded { home: 0, foreign: 0x1000 };
+		let gas_limits = app.gas_limits.clone();
+		create_foreign_gas_limits_updates(app, db, &loaded)
+			.take(1)
+			.map(move |block| (block, gas_limits.deposit_relay().low_u64(), gas_limits.withdraw_confirm().low_u64()))
+	},
+	expected => vec![(0x1005, 100_000, 150_000)],
+	home_transport => [],
+	foreign_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1001",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[GAS_LIMITS_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [GAS_LIMITS_TOPIC],
+				"data": "0x00000000000000000000000000000000000000000000000000000000000186a000000000000000000000000000000000000000000000000000000000000249f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	]
+}
+
+test_app_stream! {
+	name => foreign_gas_limits_update_reorganized,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12,
+		reorg_depth => 10;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12,
+		reorg_depth => 10;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions {
+		deposit_relay: TransactionConfig { gas: 50_000, gas_price: 0 },
+		withdraw_confirm: TransactionConfig { gas: 50_000, gas_price: 0 },
+		..Default::default()
+	},
+	init => |app: Arc<App<_>>, db| {
+		let loaded = GasLimitsLoaded { home: 0, foreign: 0x1000 };
+		let gas_limits = app.gas_limits.clone();
+		create_foreign_gas_limits_updates(app, db, &loaded)
+			.take(2)
+			.map(move |block| (block, gas_limits.deposit_relay().low_u64(), gas_limits.withdraw_confirm().low_u64()))
+	},
+	// the update is orphaned, so the limits are reloaded from the block before it
+	expected => vec![(0x1005, 100_000, 150_000), (0x1006, 60_000, 70_000)],
+	home_transport => [],
+	foreign_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getBlockByNumber" =>
+			req => json!(["0x1005", false]),
+			res => json!({
+				"number": "0x1005",
+				"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+			});
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1001",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[GAS_LIMITS_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [GAS_LIMITS_TOPIC],
+				"data": "0x00000000000000000000000000000000000000000000000000000000000186a000000000000000000000000000000000000000000000000000000000000249f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1012");
+		"eth_getBlockByNumber" =>
+			req => json!(["0x1005", false]),
+			res => json!({
+				"number": "0x1005",
+				"hash": "0x2222222222222222222222222222222222222222222222222222222222222222"
+			});
+		"eth_call" =>
+			req => json!([{
+				"data": deposit_relay_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "0x1000"]),
+			res => json!("0x000000000000000000000000000000000000000000000000000000000000ea60");
+		"eth_call" =>
+			req => json!([{
+				"data": withdraw_confirm_payload(),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "0x1000"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000011170");
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1012");
+		"eth_getBlockByNumber" =>
+			req => json!(["0x1006", false]),
+			res => json!({
+				"number": "0x1006",
+				"hash": "0x3333333333333333333333333333333333333333333333333333333333333333"
+			});
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1001",
+				"limit": null,
+				"toBlock": "0x1006",
+				"topics": [[GAS_LIMITS_TOPIC], null, null, null]
+			}]),
+			res => json!([]);
+	]
+}
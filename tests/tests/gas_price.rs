@@ -0,0 +1,53 @@
+/// test interactions of gas price strategies with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate web3;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+
+use std::time::Duration;
+use web3::types::U256;
+use bridge::config::{GasPriceConfig, GasPriceStrategy};
+use bridge::gas_price::fetch_gas_price;
+
+test_transport_stream! {
+	name => gas_price_node,
+	init => |transport| {
+		let config = GasPriceConfig {
+			strategy: GasPriceStrategy::Node,
+			..GasPriceConfig::default()
+		};
+		fetch_gas_price(transport, Default::default(), Duration::from_secs(5), &Default::default(), &config, None).into_stream()
+	},
+	expected => vec![Some(U256::from(0x4a817c800u64))],
+	"eth_gasPrice" =>
+		req => json!([]),
+		res => json!("0x4a817c800");
+}
+
+test_transport_stream! {
+	name => gas_price_percentile_of_missing_blocks,
+	init => |transport| {
+		let config = GasPriceConfig {
+			strategy: GasPriceStrategy::Percentile {
+				percentile: 60,
+				blocks: 2,
+			},
+			..GasPriceConfig::default()
+		};
+		fetch_gas_price(transport, Default::default(), Duration::from_secs(5), &Default::default(), &config, None).into_stream()
+	},
+	expected => vec![None],
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0x1011");
+	"eth_getBlockByNumber" =>
+		req => json!(["0x1010", true]),
+		res => json!(null);
+	"eth_getBlockByNumber" =>
+		req => json!(["0x1011", true]),
+		res => json!(null);
+}
@@ -8,7 +8,8 @@ extern crate tests;
 
 use std::time::Duration;
 use web3::types::{FilterBuilder, H160, H256, Log};
-use bridge::api::{LogStreamInit, log_stream, LogStreamItem};
+use bridge::api::{LogStreamInit, log_stream, LogStreamItem, LogStreamEvent};
+use bridge::error::Error;
 
 test_transport_stream! {
 	name => log_stream_basic,
@@ -19,19 +20,21 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(2)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0x1006,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x1007,
 		to: 0x1007,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x1010");
@@ -61,6 +64,53 @@ test_transport_stream! {
 		res => json!([]);
 }
 
+test_transport_stream! {
+	name => log_stream_chunked,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: Some(0x800),
+		};
+
+		log_stream(transport, Default::default(), init).take(2)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0x80a,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0x80b,
+		to: 0x1006,
+		logs: vec![],
+	})],
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0x1010");
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0x80a",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0x80b",
+			"limit": null,
+			"toBlock": "0x1006",
+			"topics": null
+		}]),
+		res => json!([]);
+}
+
 test_transport_stream! {
 	name => log_stream_rollback,
 	init => |transport| {
@@ -70,19 +120,21 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(2)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0xd,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0xe,
 		to: 0xf,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x17");
@@ -124,15 +176,17 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(1)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0xd,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x13");
@@ -162,23 +216,25 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 0,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(3)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0x13,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x14,
 		to: 0x14,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x15,
 		to: 0x17,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x13");
@@ -229,19 +285,21 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 0,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(2)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xc,
 		to: 0x13,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x14,
 		to: 0x14,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x13");
@@ -277,19 +335,21 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 0,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(2)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xc,
 		to: 0x13,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x14,
 		to: 0x14,
 		logs: vec![],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x13");
@@ -325,11 +385,13 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(1)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0x1006,
 		logs: vec![Log {
@@ -339,7 +401,7 @@ test_transport_stream! {
 			log_type: "".into(),
 			..Default::default()
 		}],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x1010");
@@ -368,11 +430,13 @@ test_transport_stream! {
 			poll_interval: Duration::from_secs(0),
 			request_timeout: Duration::from_secs(5),
 			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
 		};
 
 		log_stream(transport, Default::default(), init).take(3)
 	},
-	expected => vec![LogStreamItem {
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
 		from: 0xb,
 		to: 0x1006,
 		logs: vec![Log {
@@ -382,11 +446,11 @@ test_transport_stream! {
 			log_type: "".into(),
 			..Default::default()
 		}],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x1007,
 		to: 0x1007,
 		logs: vec![],
-	}, LogStreamItem {
+	}), LogStreamEvent::Logs(LogStreamItem {
 		from: 0x1008,
 		to: 0x1008,
 		logs: vec![Log {
@@ -402,7 +466,7 @@ test_transport_stream! {
 			log_type: "".into(),
 			..Default::default()
 		}],
-	}],
+	})],
 	"eth_blockNumber" =>
 		req => json!([]),
 		res => json!("0x1010");
@@ -458,3 +522,332 @@ test_transport_stream! {
 			}
 		]);
 }
+
+test_transport_stream! {
+	name => log_stream_reorg_of_whole_history,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 0,
+			reorg_depth: 10,
+			max_block_range: None,
+		};
+
+		log_stream(transport, Default::default(), init).take(2)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0xd,
+		logs: vec![],
+	}), LogStreamEvent::Reorg {
+		from: 0xb,
+		to: 0xd,
+	}],
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xd");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xd", false]),
+		res => json!({
+			"number": "0xd",
+			"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+		});
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0xd",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xf");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xd", false]),
+		res => json!({
+			"number": "0xd",
+			"hash": "0x2222222222222222222222222222222222222222222222222222222222222222"
+		});
+}
+
+test_transport_stream! {
+	name => log_stream_reorg_rescans_orphaned_range,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 0,
+			reorg_depth: 10,
+			max_block_range: None,
+		};
+
+		log_stream(transport, Default::default(), init).take(4)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0xc,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0xd,
+		to: 0xe,
+		logs: vec![],
+	}), LogStreamEvent::Reorg {
+		from: 0xd,
+		to: 0xe,
+	}, LogStreamEvent::Logs(LogStreamItem {
+		from: 0xd,
+		to: 0xf,
+		logs: vec![],
+	})],
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xc");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xc", false]),
+		res => json!({
+			"number": "0xc",
+			"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+		});
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0xc",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xe");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xc", false]),
+		res => json!({
+			"number": "0xc",
+			"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+		});
+	"eth_getBlockByNumber" =>
+		req => json!(["0xe", false]),
+		res => json!({
+			"number": "0xe",
+			"hash": "0x2222222222222222222222222222222222222222222222222222222222222222"
+		});
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xd",
+			"limit": null,
+			"toBlock": "0xe",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xf");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xe", false]),
+		res => json!({
+			"number": "0xe",
+			"hash": "0x3333333333333333333333333333333333333333333333333333333333333333"
+		});
+	"eth_getBlockByNumber" =>
+		req => json!(["0xc", false]),
+		res => json!({
+			"number": "0xc",
+			"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+		});
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0xf");
+	"eth_getBlockByNumber" =>
+		req => json!(["0xc", false]),
+		res => json!({
+			"number": "0xc",
+			"hash": "0x1111111111111111111111111111111111111111111111111111111111111111"
+		});
+	"eth_getBlockByNumber" =>
+		req => json!(["0xf", false]),
+		res => json!({
+			"number": "0xf",
+			"hash": "0x4444444444444444444444444444444444444444444444444444444444444444"
+		});
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xd",
+			"limit": null,
+			"toBlock": "0xf",
+			"topics": null
+		}]),
+		res => json!([]);
+}
+
+test_transport_stream! {
+	name => log_stream_new_heads,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
+		};
+
+		// new heads which don't confirm any new blocks are skipped without requests
+		let new_heads = futures::stream::iter_ok::<_, Error>(vec![0x1010u64, 0x1010, 0x1011]);
+		log_stream(transport, Default::default(), init)
+			.with_new_heads(Some(Box::new(new_heads)))
+			.take(2)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0x1007,
+		to: 0x1007,
+		logs: vec![],
+	})],
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0x1006",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0x1007",
+			"limit": null,
+			"toBlock": "0x1007",
+			"topics": null
+		}]),
+		res => json!([]);
+}
+
+test_transport_stream! {
+	name => log_stream_new_heads_fallback,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
+		};
+
+		// subscription fails after the first head, next block number is polled
+		let new_heads = futures::stream::iter_result::<_, u64, Error>(vec![Ok(0x1010), Err("subscription dropped".into())]);
+		log_stream(transport, Default::default(), init)
+			.with_new_heads(Some(Box::new(new_heads)))
+			.take(2)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0x1007,
+		to: 0x1007,
+		logs: vec![],
+	})],
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0x1006",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0x1011");
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0x1007",
+			"limit": null,
+			"toBlock": "0x1007",
+			"topics": null
+		}]),
+		res => json!([]);
+}
+
+test_transport_stream! {
+	name => log_stream_new_heads_resubscribed,
+	init => |transport| {
+		let init = LogStreamInit {
+			after: 10,
+			filter: FilterBuilder::default(),
+			poll_interval: Duration::from_secs(0),
+			request_timeout: Duration::from_secs(5),
+			confirmations: 10,
+			reorg_depth: 0,
+			max_block_range: None,
+		};
+
+		// subscription fails after the first head, next block number is polled and the stream subscribes again
+		let new_heads = futures::stream::iter_result::<_, u64, Error>(vec![Ok(0x1010), Err("subscription dropped".into()), Ok(0x1012)]);
+		log_stream(transport, Default::default(), init)
+			.with_new_heads(Some(Box::new(new_heads)))
+			.take(3)
+	},
+	expected => vec![LogStreamEvent::Logs(LogStreamItem {
+		from: 0xb,
+		to: 0x1006,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0x1007,
+		to: 0x1007,
+		logs: vec![],
+	}), LogStreamEvent::Logs(LogStreamItem {
+		from: 0x1008,
+		to: 0x1008,
+		logs: vec![],
+	})],
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0xb",
+			"limit": null,
+			"toBlock": "0x1006",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_blockNumber" =>
+		req => json!([]),
+		res => json!("0x1011");
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0x1007",
+			"limit": null,
+			"toBlock": "0x1007",
+			"topics": null
+		}]),
+		res => json!([]);
+	"eth_getLogs" =>
+		req => json!([{
+			"address": null,
+			"fromBlock": "0x1008",
+			"limit": null,
+			"toBlock": "0x1008",
+			"topics": null
+		}]),
+		res => json!([]);
+}
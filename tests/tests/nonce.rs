@@ -0,0 +1,107 @@
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate web3;
+extern crate bridge;
+extern crate tests;
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::Future;
+use web3::types::TransactionRequest;
+use bridge::nonce::{NonceManager, send_transaction};
+use bridge::signer::{AccountSigner, NodeSigner};
+use tests::MockedTransport;
+
+const HASH: &str = "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b";
+
+fn request() -> TransactionRequest {
+	TransactionRequest {
+		from: "0000000000000000000000000000000000000001".parse().unwrap(),
+		to: Some("0000000000000000000000000000000000000002".parse().unwrap()),
+		gas: Some(0x5208.into()),
+		gas_price: Some(0x3e8.into()),
+		value: None,
+		data: None,
+		nonce: None,
+		condition: None,
+	}
+}
+
+fn signer() -> Arc<AccountSigner> {
+	Arc::new(AccountSigner::Node(NodeSigner::new(
+		"0000000000000000000000000000000000000001".parse().unwrap(),
+		Default::default(),
+		Duration::from_secs(5),
+	)))
+}
+
+#[test]
+fn failed_send_releases_nonce() {
+	let sent = json!([{
+		"from": "0x0000000000000000000000000000000000000001",
+		"to": "0x0000000000000000000000000000000000000002",
+		"gas": "0x5208",
+		"gasPrice": "0x3e8",
+		"nonce": "0x7"
+	}]);
+	let requests = vec![
+		("eth_getTransactionCount", json!(["0x0000000000000000000000000000000000000001", "pending"]), json!("0x7")),
+		// the node rejects the transaction
+		("eth_sendTransaction", sent.clone(), json!({"error": {"code": -32000, "message": "insufficient funds for gas * price + value"}})),
+		// released nonce is handed out again without synchronising the manager
+		("eth_sendTransaction", sent, json!(HASH)),
+	];
+	let transport = MockedTransport {
+		requests: Default::default(),
+		expected_requests: requests.iter().map(|&(method, ref req, _)| (method, req.clone()).into()).collect(),
+		mocked_responses: requests.into_iter().map(|(_, _, res)| res).collect(),
+	};
+	let nonces = Arc::new(NonceManager::new());
+
+	let first = send_transaction(&transport, Default::default(), nonces.clone(), signer(), request(), Duration::from_secs(5));
+	assert!(first.wait().is_err());
+
+	let second = send_transaction(&transport, Default::default(), nonces.clone(), signer(), request(), Duration::from_secs(5));
+	assert_eq!((HASH.parse::<web3::types::H256>().unwrap(), 0x7.into()), second.wait().unwrap());
+	assert_eq!(Some(0x8.into()), nonces.next());
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
+
+fn unanswered_send(transaction_count: &str) -> Arc<NonceManager> {
+	let sent = json!([{
+		"from": "0x0000000000000000000000000000000000000001",
+		"to": "0x0000000000000000000000000000000000000002",
+		"gas": "0x5208",
+		"gasPrice": "0x3e8",
+		"nonce": "0x7"
+	}]);
+	let requests = vec![
+		("eth_getTransactionCount", json!(["0x0000000000000000000000000000000000000001", "pending"]), json!("0x7")),
+		// response which can't be decoded to a hash, the node may have accepted the transaction
+		("eth_sendTransaction", sent, json!(null)),
+		("eth_getTransactionCount", json!(["0x0000000000000000000000000000000000000001", "pending"]), json!(transaction_count)),
+	];
+	let transport = MockedTransport {
+		requests: Default::default(),
+		expected_requests: requests.iter().map(|&(method, ref req, _)| (method, req.clone()).into()).collect(),
+		mocked_responses: requests.into_iter().map(|(_, _, res)| res).collect(),
+	};
+	let nonces = Arc::new(NonceManager::new());
+	let send = send_transaction(&transport, Default::default(), nonces.clone(), signer(), request(), Duration::from_secs(5));
+	assert!(send.wait().is_err());
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+	nonces
+}
+
+#[test]
+fn unanswered_send_keeps_used_nonce() {
+	let nonces = unanswered_send("0x8");
+	assert_eq!(Some(0x8.into()), nonces.next());
+}
+
+#[test]
+fn unanswered_send_releases_unused_nonce() {
+	let nonces = unanswered_send("0x7");
+	assert_eq!(Some(0x7.into()), nonces.next());
+}
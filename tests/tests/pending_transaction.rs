@@ -0,0 +1,201 @@
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate web3;
+extern crate bridge;
+extern crate tests;
+
+use std::sync::Arc;
+use std::time::Duration;
+use futures::Future;
+use web3::types::TransactionRequest;
+use bridge::config::Resubmission;
+use bridge::signer::{AccountSigner, NodeSigner};
+use bridge::transaction::{Outcome, PendingTransactionInit, pending_transaction};
+use tests::MockedTransport;
+
+const HASH: &str = "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b";
+const REPLACEMENT_HASH: &str = "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364";
+
+fn init(confirmations: usize) -> PendingTransactionInit {
+	PendingTransactionInit {
+		hash: HASH.parse().unwrap(),
+		request: TransactionRequest {
+			from: "0000000000000000000000000000000000000001".parse().unwrap(),
+			to: Some("0000000000000000000000000000000000000002".parse().unwrap()),
+			gas: Some(0x5208.into()),
+			gas_price: Some(0x3e8.into()),
+			value: None,
+			data: None,
+			nonce: None,
+			condition: None,
+		},
+		request_timeout: Duration::from_secs(5),
+		poll_interval: Duration::from_secs(0),
+		confirmations,
+		resubmission: None,
+		signer: Arc::new(AccountSigner::Node(NodeSigner::new(
+			"0000000000000000000000000000000000000001".parse().unwrap(),
+			Default::default(),
+			Duration::from_secs(5),
+		))),
+		retry: Default::default(),
+	}
+}
+
+fn transport(requests: Vec<(&'static str, serde_json::Value, serde_json::Value)>) -> MockedTransport {
+	MockedTransport {
+		requests: Default::default(),
+		expected_requests: requests.iter().map(|&(method, ref req, _)| (method, req.clone()).into()).collect(),
+		mocked_responses: requests.into_iter().map(|(_, _, res)| res).collect(),
+	}
+}
+
+fn transaction_json() -> serde_json::Value {
+	json!({
+		"hash": HASH,
+		"nonce": "0x7",
+		"blockHash": null,
+		"blockNumber": null,
+		"transactionIndex": null,
+		"from": "0x0000000000000000000000000000000000000001",
+		"to": "0x0000000000000000000000000000000000000002",
+		"value": "0x0",
+		"gasPrice": "0x3e8",
+		"gas": "0x5208",
+		"input": "0x"
+	})
+}
+
+#[test]
+fn pending_transaction_waits_for_confirmations() {
+	let transport = transport(vec![
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([HASH]), transaction_json()),
+		("eth_getTransactionReceipt", json!([HASH]), json!({
+			"transactionHash": HASH,
+			"blockNumber": "0x10",
+			"status": "0x1"
+		})),
+		("eth_blockNumber", json!([]), json!("0x11")),
+		("eth_getTransactionReceipt", json!([HASH]), json!({
+			"transactionHash": HASH,
+			"blockNumber": "0x10",
+			"status": "0x1"
+		})),
+		("eth_blockNumber", json!([]), json!("0x12")),
+	]);
+
+	let receipt = pending_transaction(&transport, Default::default(), init(2)).wait().unwrap().receipt().unwrap();
+	assert_eq!(HASH.parse::<web3::types::H256>().unwrap(), receipt.transaction_hash);
+	assert_eq!(Some(0x10.into()), receipt.block_number);
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
+
+#[test]
+fn pending_transaction_failed() {
+	let transport = transport(vec![
+		("eth_getTransactionReceipt", json!([HASH]), json!({
+			"transactionHash": HASH,
+			"blockNumber": "0x10",
+			"status": "0x0"
+		})),
+	]);
+
+	match pending_transaction(&transport, Default::default(), init(0)).wait().unwrap() {
+		Outcome::Failed(hash) => assert_eq!(HASH.parse::<web3::types::H256>().unwrap(), hash),
+		other => panic!("unexpected outcome {:?}", other),
+	}
+}
+
+#[test]
+fn pending_transaction_dropped() {
+	let transport = transport(vec![
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+	]);
+
+	match pending_transaction(&transport, Default::default(), init(0)).wait().unwrap() {
+		Outcome::Dropped(hash) => assert_eq!(HASH.parse::<web3::types::H256>().unwrap(), hash),
+		other => panic!("unexpected outcome {:?}", other),
+	}
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
+
+#[test]
+fn pending_transaction_resubmitted_with_higher_gas_price() {
+	let transport = transport(vec![
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([HASH]), transaction_json()),
+		("eth_sendTransaction", json!([{
+			"from": "0x0000000000000000000000000000000000000001",
+			"to": "0x0000000000000000000000000000000000000002",
+			"gas": "0x5208",
+			"gasPrice": "0x5dc",
+			"nonce": "0x7"
+		}]), json!(REPLACEMENT_HASH)),
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([REPLACEMENT_HASH]), json!({
+			"transactionHash": REPLACEMENT_HASH,
+			"blockNumber": "0x10",
+			"status": "0x1"
+		})),
+	]);
+
+	let init = PendingTransactionInit {
+		resubmission: Some(Resubmission {
+			timeout: Duration::from_secs(0),
+			gas_price_multiplier: 1.5,
+			max_gas_price: 10_000,
+		}),
+		..init(0)
+	};
+
+	let receipt = pending_transaction(&transport, Default::default(), init).wait().unwrap().receipt().unwrap();
+	assert_eq!(REPLACEMENT_HASH.parse::<web3::types::H256>().unwrap(), receipt.transaction_hash);
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
+
+// the original transaction is mined, so the node forgets its replacement
+#[test]
+fn pending_transaction_earlier_hash_mined() {
+	let transport = transport(vec![
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([HASH]), transaction_json()),
+		("eth_sendTransaction", json!([{
+			"from": "0x0000000000000000000000000000000000000001",
+			"to": "0x0000000000000000000000000000000000000002",
+			"gas": "0x5208",
+			"gasPrice": "0x5dc",
+			"nonce": "0x7"
+		}]), json!(REPLACEMENT_HASH)),
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([REPLACEMENT_HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([REPLACEMENT_HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([REPLACEMENT_HASH]), json!(null)),
+		("eth_getTransactionByHash", json!([REPLACEMENT_HASH]), json!(null)),
+		("eth_getTransactionReceipt", json!([HASH]), json!({
+			"transactionHash": HASH,
+			"blockNumber": "0x10",
+			"status": "0x1"
+		})),
+		("eth_getTransactionReceipt", json!([REPLACEMENT_HASH]), json!(null)),
+	]);
+
+	let init = PendingTransactionInit {
+		resubmission: Some(Resubmission {
+			timeout: Duration::from_secs(0),
+			gas_price_multiplier: 1.5,
+			max_gas_price: 10_000,
+		}),
+		..init(0)
+	};
+
+	let receipt = pending_transaction(&transport, Default::default(), init).wait().unwrap().receipt().unwrap();
+	assert_eq!(HASH.parse::<web3::types::H256>().unwrap(), receipt.transaction_hash);
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
@@ -0,0 +1,149 @@
+/// test interactions of signers with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate web3;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+extern crate ethereum_types;
+extern crate rlp;
+extern crate rustc_hex;
+
+use std::time::Duration;
+use futures::Future;
+use ethereum_types::{Address, H520, U256};
+use rlp::UntrustedRlp;
+use rustc_hex::FromHex;
+use web3::types::{Bytes, TransactionRequest};
+use bridge::signer::{NodeSigner, RemoteSigner, Signer, SignedTransaction};
+use tests::MockedTransport;
+
+const SIGNATURE: &str = "0x8697e7e3b8e86dbe8a4c0ad6a1fc2dbf50f4f0b4c8a8a0cd0bcc0b5d1fa2de4e3e3ca0a7c8e3d43c9d2f4e5bbd9e33bd3eb0fb7bc11c8a2b3e5fa80bd5d7e23b1c";
+
+/// Transaction of the EIP-155 example, signed with chain id `1`.
+const RAW_TRANSACTION: &str = "0xf86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83";
+
+fn request() -> TransactionRequest {
+	TransactionRequest {
+		from: "0000000000000000000000000000000000000001".parse().unwrap(),
+		to: Some("3535353535353535353535353535353535353535".parse().unwrap()),
+		gas: Some(0x5208.into()),
+		gas_price: Some(20_000_000_000u64.into()),
+		value: Some(1_000_000_000_000_000_000u64.into()),
+		data: None,
+		nonce: Some(9.into()),
+		condition: None,
+	}
+}
+
+fn raw(signed: SignedTransaction) -> Bytes {
+	match signed {
+		SignedTransaction::Raw(raw) => raw,
+		SignedTransaction::Unsigned(request) => panic!("expected signed transaction, got {:?}", request),
+	}
+}
+
+test_transport_stream! {
+	name => node_sign_message,
+	init => |transport: &MockedTransport| {
+		let signer = NodeSigner::new("0000000000000000000000000000000000000001".parse().unwrap(), Default::default(), Duration::from_secs(5));
+		signer.sign_message(transport, vec![0x12, 0x34]).into_stream()
+	},
+	expected => vec![SIGNATURE.parse::<H520>().unwrap()],
+	"eth_sign" =>
+		req => json!(["0x0000000000000000000000000000000000000001", "0x1234"]),
+		res => json!(SIGNATURE);
+}
+
+test_transport_stream! {
+	name => node_sign_transaction,
+	init => |_transport: &MockedTransport| {
+		let signer = NodeSigner::new("0000000000000000000000000000000000000001".parse().unwrap(), Default::default(), Duration::from_secs(5));
+		// transactions are signed by the node when they are sent
+		Signer::<MockedTransport>::sign_transaction(&signer, request())
+			.map(|signed| match signed {
+				SignedTransaction::Unsigned(request) => request.nonce,
+				SignedTransaction::Raw(raw) => panic!("expected unsigned transaction, got {:?}", raw),
+			})
+			.into_stream()
+	},
+	expected => vec![Some(U256::from(9))],
+}
+
+test_transport_stream! {
+	name => remote_sign_message,
+	init => |transport: &MockedTransport| {
+		let signer = RemoteSigner::with_transport(transport, "0000000000000000000000000000000000000001".parse().unwrap(), Default::default(), Duration::from_secs(5));
+		signer.sign_data::<MockedTransport>(vec![0x12, 0x34]).into_stream()
+	},
+	expected => vec![SIGNATURE.parse::<H520>().unwrap()],
+	"account_signData" =>
+		req => json!(["text/plain", "0x0000000000000000000000000000000000000001", "0x1234"]),
+		res => json!(SIGNATURE);
+}
+
+test_transport_stream! {
+	name => remote_sign_transaction,
+	init => |transport: &MockedTransport| {
+		let signer = RemoteSigner::with_transport(transport, "0000000000000000000000000000000000000001".parse().unwrap(), Default::default(), Duration::from_secs(5));
+		signer.sign_raw_transaction(request()).map(raw).into_stream()
+	},
+	expected => vec![Bytes(RAW_TRANSACTION[2..].from_hex().unwrap())],
+	"account_signTransaction" =>
+		req => json!([{
+			"from": "0x0000000000000000000000000000000000000001",
+			"to": "0x3535353535353535353535353535353535353535",
+			"gas": "0x5208",
+			"gasPrice": "0x4a817c800",
+			"value": "0xde0b6b3a7640000",
+			"nonce": "0x9"
+		}]),
+		// clef returns the decoded transaction next to the raw one
+		res => json!({
+			"raw": RAW_TRANSACTION,
+			"tx": {
+				"nonce": "0x9",
+				"gasPrice": "0x4a817c800",
+				"gas": "0x5208",
+				"to": "0x3535353535353535353535353535353535353535",
+				"value": "0xde0b6b3a7640000",
+				"input": "0x",
+				"v": "0x25",
+				"r": "0x28ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276",
+				"s": "0x67cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+			}
+		});
+}
+
+#[test]
+fn remote_signed_transaction_is_decodable() {
+	let transport = MockedTransport {
+		requests: Default::default(),
+		expected_requests: vec![("account_signTransaction", json!([{
+			"from": "0x0000000000000000000000000000000000000001",
+			"to": "0x3535353535353535353535353535353535353535",
+			"gas": "0x5208",
+			"gasPrice": "0x4a817c800",
+			"value": "0xde0b6b3a7640000",
+			"nonce": "0x9"
+		}])).into()],
+		mocked_responses: vec![json!({ "raw": RAW_TRANSACTION })],
+	};
+	let signer = RemoteSigner::with_transport(&transport, "0000000000000000000000000000000000000001".parse().unwrap(), Default::default(), Duration::from_secs(5));
+	let signed = raw(signer.sign_raw_transaction(request()).wait().unwrap());
+
+	// the raw transaction is sent as is, so it has to match the request
+	let rlp = UntrustedRlp::new(&signed.0);
+	assert_eq!(9, rlp.item_count().unwrap());
+	assert_eq!(U256::from(9), rlp.val_at::<U256>(0).unwrap());
+	assert_eq!(U256::from(20_000_000_000u64), rlp.val_at::<U256>(1).unwrap());
+	assert_eq!(U256::from(0x5208), rlp.val_at::<U256>(2).unwrap());
+	assert_eq!("3535353535353535353535353535353535353535".parse::<Address>().unwrap(), rlp.val_at::<Address>(3).unwrap());
+	assert_eq!(U256::from(1_000_000_000_000_000_000u64), rlp.val_at::<U256>(4).unwrap());
+	assert_eq!(Vec::<u8>::new(), rlp.val_at::<Vec<u8>>(5).unwrap());
+	// v of chain id `1`
+	assert_eq!(37u64, rlp.val_at::<u64>(6).unwrap());
+	assert_eq!(transport.expected_requests.len(), transport.requests.get());
+}
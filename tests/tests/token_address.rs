@@ -0,0 +1,126 @@
+/// test interactions of token address governance with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+extern crate rustc_hex;
+extern crate ethereum_types;
+
+use rustc_hex::ToHex;
+use bridge::bridge::{create_set_token_address, create_token_address_events, TokenAddressVotes};
+use bridge::contracts::foreign::ForeignBridge;
+use bridge::contracts::storage;
+
+const TOKEN_ADDRESS_TOPIC: &str = "0x9687492a9531bc3914d553389cdcbe0e12eec84d28f59a91175ad3f563fb11be";
+
+fn address(address: &str) -> ethereum_types::Address {
+	address.parse().unwrap()
+}
+
+/// Hex encoded payload of the `eth_call`.
+fn payload(payload: Vec<u8>) -> String {
+	format!("0x{}", payload.to_hex())
+}
+
+test_app_stream! {
+	name => set_token_address_already_voted,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_set_token_address(app, db, "00000000000000000000000000000000000000aa".parse().unwrap()).into_stream(),
+	expected => vec![TokenAddressVotes {
+		token: "00000000000000000000000000000000000000aa".parse().unwrap(),
+		votes: 1u64.into(),
+		required_signatures: 2u64.into(),
+		voted: true,
+		current: "0000000000000000000000000000000000000000".parse().unwrap(),
+	}],
+	home_transport => [],
+	foreign_transport => [
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", storage::foreign_token_address_voted(
+				address("0000000000000000000000000000000000000001"),
+				address("00000000000000000000000000000000000000aa")
+			), "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000001");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", storage::foreign_token_address_votes(address("00000000000000000000000000000000000000aa")), "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000001");
+		"eth_call" =>
+			req => json!([{
+				"data": payload(ForeignBridge::default().functions().required_signatures().input()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000002");
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", storage::foreign_token_address_voted(
+				address("0000000000000000000000000000000000000001"),
+				address("00000000000000000000000000000000000000aa")
+			), "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000001");
+		"eth_call" =>
+			req => json!([{
+				"data": payload(ForeignBridge::default().functions().erc20token().input()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000000");
+	]
+}
+
+test_app_stream! {
+	name => token_address_events,
+	database => Database {
+		foreign_deploy: 0x1000,
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_token_address_events(app, db).take(1),
+	expected => vec![address("00000000000000000000000000000000000000aa")],
+	home_transport => [],
+	foreign_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x0000000000000000000000000000000000000000"],
+				"fromBlock": "0x1001",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[TOKEN_ADDRESS_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x0000000000000000000000000000000000000000",
+				"topics": [TOKEN_ADDRESS_TOPIC],
+				"data": "0x00000000000000000000000000000000000000000000000000000000000000aa",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+	]
+}
@@ -0,0 +1,108 @@
+/// test interactions of contract verification with RPC
+
+extern crate futures;
+#[macro_use]
+extern crate serde_json;
+extern crate bridge;
+#[macro_use]
+extern crate tests;
+extern crate rustc_hex;
+extern crate ethereum_types;
+
+use rustc_hex::ToHex;
+use bridge::bridge::create_verify;
+use bridge::contracts;
+
+fn authority(address: &str) -> ethereum_types::Address {
+	address.parse().unwrap()
+}
+
+/// Payload of `HomeBridge.authorities`.
+fn authorities_payload(index: u64) -> String {
+	format!("0x{}", contracts::home::HomeBridge::default().functions().authorities().input(ethereum_types::U256::from(index)).to_hex())
+}
+
+/// Hex encoded uint returned by `eth_call` and `eth_getStorageAt`.
+fn uint(value: u64) -> String {
+	format!("0x{:064x}", value)
+}
+
+test_app_stream! {
+	name => verify_contracts,
+	database => Database::default(),
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 1;
+	txs => Transactions::default(),
+	init => |app, db| create_verify(app, db).into_stream(),
+	expected => vec![()],
+	home_transport => [
+		"eth_getCode" =>
+			req => json!(["0x0000000000000000000000000000000000000000", "latest"]),
+			res => json!(format!("0x{}", contracts::HOME_BRIDGE_RUNTIME.trim()));
+		"eth_call" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default().functions().required_signatures().input().to_hex()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(1));
+		"eth_call" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default().functions().estimated_gas_cost_of_withdraw().input().to_hex()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(100_000));
+		"eth_call" =>
+			req => json!([{
+				"data": authorities_payload(0),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(2));
+		"eth_call" =>
+			req => json!([{
+				"data": authorities_payload(1),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(1));
+		"eth_call" =>
+			req => json!([{
+				"data": authorities_payload(2),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			// the getter hits the invalid opcode past the end of the array
+			res => json!({ "error": { "code": -32000, "message": "invalid opcode: opcode 0xfe not defined" } });
+	],
+	foreign_transport => [
+		"eth_getCode" =>
+			req => json!(["0x0000000000000000000000000000000000000000", "latest"]),
+			res => json!(format!("0x{}", contracts::FOREIGN_BRIDGE_RUNTIME.trim()));
+		"eth_call" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::foreign::ForeignBridge::default().functions().required_signatures().input().to_hex()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(1));
+		"eth_call" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::foreign::ForeignBridge::default().functions().estimated_gas_cost_of_withdraw().input().to_hex()),
+				"to": "0x0000000000000000000000000000000000000000"
+			}, "latest"]),
+			res => json!(uint(100_000));
+		// `ForeignBridge.authorities`
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", contracts::storage::foreign_authority(authority("0000000000000000000000000000000000000001")), "latest"]),
+			res => json!(uint(1));
+		"eth_getStorageAt" =>
+			req => json!(["0x0000000000000000000000000000000000000000", contracts::storage::foreign_authority(authority("0000000000000000000000000000000000000002")), "latest"]),
+			res => json!(uint(1));
+	]
+}
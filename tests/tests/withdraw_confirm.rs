@@ -17,6 +17,15 @@ use bridge::contracts;
 use ethabi::{encode, Token};
 
 const WITHDRAW_TOPIC: &str = "0xf279e6a1f5e320cca91135676d9cb6e44ca8a08c0b88342bcdb1144f6511b568";
+const NOT_SIGNED: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Storage position of the `ForeignBridge.messages_signed` flag of the authority used by all tests.
+fn message_signed_position(message: MessageToMainnet) -> ethereum_types::U256 {
+	contracts::storage::foreign_message_signed(
+		"0000000000000000000000000000000000000001".parse::<ethereum_types::Address>().unwrap(),
+		&message.to_bytes(),
+	)
+}
 
 test_app_stream! {
 	name => withdraw_confirm_basic,
@@ -228,6 +237,19 @@ test_app_stream! {
 				"type": "",
 				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
 			}]);
+		// `ForeignBridge.messages_signed`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8",
+				message_signed_position(MessageToMainnet {
+					recipient: [1u8; 20].into(),
+					value: 10000.into(),
+					sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+					mainnet_gas_price: 1000.into(),
+				}),
+				"latest"
+			]),
+			res => json!(NOT_SIGNED);
 		"eth_sign" =>
 			req => json!([
 				"0x0000000000000000000000000000000000000001",
@@ -242,6 +264,9 @@ test_app_stream! {
 			]),
 			res => json!("0x8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677aff3454fce5edbc8cca8697c15331677e6ebccccaff3454fce5edbc8cca8697c15331677e6ebc");
 		// `submitSignature`
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": format!("0x{}", contracts::foreign::ForeignBridge::default()
@@ -260,9 +285,20 @@ test_app_stream! {
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0xfe",
 				"gasPrice": "0xa1",
+				"nonce": "0x0",
 				"to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
 		"eth_blockNumber" =>
 			req => json!([]),
 			res => json!("0x1012");
@@ -340,6 +376,31 @@ test_app_stream! {
 				"type":"",
 				"transactionHash":"0xfffedad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
 			}]);
+		// `ForeignBridge.messages_signed`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8",
+				message_signed_position(MessageToMainnet {
+					recipient: [1u8; 20].into(),
+					value: 10000.into(),
+					sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+					mainnet_gas_price: 1000.into(),
+				}),
+				"latest"
+			]),
+			res => json!(NOT_SIGNED);
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8",
+				message_signed_position(MessageToMainnet {
+					recipient: [2u8; 20].into(),
+					value: 42.into(),
+					sidenet_transaction_hash: "0xfffedad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+					mainnet_gas_price: 100.into(),
+				}),
+				"latest"
+			]),
+			res => json!(NOT_SIGNED);
 		"eth_sign" =>
 			req => json!([
 				"0x0000000000000000000000000000000000000001",
@@ -367,6 +428,9 @@ test_app_stream! {
 			]),
 			res => json!("0x8a3b24c56e46f6fc9fa7ed14795745348059b8ac84d6ee93323e83a429e760ae6e89510834ee4d65eefacd74cddca53df61b5eba1c3007ed88d2eebff2e0e2151b");
 		// `submitSignature`
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": format!("0x{}", contracts::foreign::ForeignBridge::default()
@@ -385,6 +449,7 @@ test_app_stream! {
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0xff",
 				"gasPrice": "0xaa",
+				"nonce": "0x0",
 				"to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
@@ -407,9 +472,30 @@ test_app_stream! {
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0xff",
 				"gasPrice": "0xaa",
+				"nonce": "0x1",
 				"to":"0x49edf201c1e139282643d5e7c6fb0c7219ad1db8"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0c",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
 		"eth_blockNumber" =>
 			req => json!([]),
 			res => json!("0x1012");
@@ -141,7 +141,40 @@ test_app_stream! {
 	init => |app, db| create_withdraw_relay(app, db).take(1),
 	expected => vec![0x1005],
 	home_transport => [
+		// `HomeBridge.withdraws`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x00000000000000000000000000000000000000dd",
+				contracts::storage::home_withdraw("884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse::<H256>().unwrap()),
+				"latest"
+			]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000000");
 		// `HomeBridge.withdraw`
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default()
+					.functions()
+					.withdraw()
+					.input(
+						vec![U256::from(1), U256::from(4)],
+						vec![H256::from(2), H256::from(5)],
+						vec![H256::from(3), H256::from(6)],
+						MessageToMainnet {
+							recipient: [1u8; 20].into(),
+							value: 10000.into(),
+							sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+							mainnet_gas_price: 1000.into(),
+						}.to_bytes()
+					).to_hex()),
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x3e8",
+				"to": "0x00000000000000000000000000000000000000dd"
+			}]),
+			res => json!("0x5208");
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
 		"eth_sendTransaction" =>
 			req => json!([{
 				"data": format!("0x{}", contracts::home::HomeBridge::default()
@@ -161,9 +194,271 @@ test_app_stream! {
 				"from": "0x0000000000000000000000000000000000000001",
 				"gas": "0x0",
 				"gasPrice": "0x3e8",
+				"nonce": "0x0",
+				"to": "0x00000000000000000000000000000000000000dd"
+			}]),
+			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	],
+	foreign_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x00000000000000000000000000000000000000ee"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[COLLECTED_SIGNATURES_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x00000000000000000000000000000000000000ee",
+				"topics": [COLLECTED_SIGNATURES_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+		// call to `message`
+		"eth_call" =>
+			req => json!([{
+				"data": "0x490a32c600000000000000000000000000000000000000000000000000000000000000f0",
+				"to": "0x00000000000000000000000000000000000000ee"
+			}, "latest"]),
+			res => json!(format!("0x{}", MessageToMainnet {
+				recipient: [1u8; 20].into(),
+				value: 10000.into(),
+				sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+				mainnet_gas_price: 1000.into(),
+			}.to_payload().to_hex()));
+		// calls to `signature`
+		"eth_call" =>
+			req => json!([{
+				"data": "0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000",
+				"to": "0x00000000000000000000000000000000000000ee"
+			},"latest"]),
+			res => json!(format!("0x{}", Signature { v: 1, r: 2.into(), s: 3.into() }.to_payload().to_hex()));
+		"eth_call" =>
+			req => json!([{
+				"data": "0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001",
+				"to": "0x00000000000000000000000000000000000000ee"
+			},"latest"]),
+			res => json!(format!("0x{}", Signature { v: 4, r: 5.into(), s: 6.into() }.to_payload().to_hex()));
+	]
+}
+
+// 2 signatures required. relay polled twice.
+// single CollectedSignatures log present.
+// message with a non-zero gas price is simulated and relayed at that gas price.
+test_app_stream! {
+	name => withdraw_relay_simulated_at_message_gas_price,
+	database => Database {
+		home_contract_address: "00000000000000000000000000000000000000dd".into(),
+		foreign_contract_address: "00000000000000000000000000000000000000ee".into(),
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "aff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		// `HomeBridge.withdraws`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x00000000000000000000000000000000000000dd",
+				contracts::storage::home_withdraw("884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse::<H256>().unwrap()),
+				"latest"
+			]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000000");
+		// `HomeBridge.withdraw`
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default()
+					.functions()
+					.withdraw()
+					.input(
+						vec![U256::from(1), U256::from(4)],
+						vec![H256::from(2), H256::from(5)],
+						vec![H256::from(3), H256::from(6)],
+						MessageToMainnet {
+							recipient: [1u8; 20].into(),
+							value: 10000.into(),
+							sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+							mainnet_gas_price: 20_000_000_000u64.into(),
+						}.to_bytes()
+					).to_hex()),
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x4a817c800",
+				"to": "0x00000000000000000000000000000000000000dd"
+			}]),
+			res => json!("0x5208");
+		"eth_getTransactionCount" =>
+			req => json!(["0x0000000000000000000000000000000000000001", "pending"]),
+			res => json!("0x0");
+		"eth_sendTransaction" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default()
+					.functions()
+					.withdraw()
+					.input(
+						vec![U256::from(1), U256::from(4)],
+						vec![H256::from(2), H256::from(5)],
+						vec![H256::from(3), H256::from(6)],
+						MessageToMainnet {
+							recipient: [1u8; 20].into(),
+							value: 10000.into(),
+							sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+							mainnet_gas_price: 20_000_000_000u64.into(),
+						}.to_bytes()
+					).to_hex()),
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x4a817c800",
+				"nonce": "0x0",
 				"to": "0x00000000000000000000000000000000000000dd"
 			}]),
 			res => json!("0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b");
+		"eth_getTransactionReceipt" =>
+			req => json!(["0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b"]),
+			res => json!({
+				"transactionHash": "0x1db8f385535c0d178b8f40016048f3a3cffee8f94e68978ea4b277f57b638f0b",
+				"blockNumber": "0x1",
+				"status": "0x1"
+			});
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0xd");
+	],
+	foreign_transport => [
+		"eth_blockNumber" =>
+			req => json!([]),
+			res => json!("0x1011");
+		"eth_getLogs" =>
+			req => json!([{
+				"address": ["0x00000000000000000000000000000000000000ee"],
+				"fromBlock": "0x1",
+				"limit": null,
+				"toBlock": "0x1005",
+				"topics": [[COLLECTED_SIGNATURES_TOPIC], null, null, null]
+			}]),
+			res => json!([{
+				"address": "0x00000000000000000000000000000000000000ee",
+				"topics": [COLLECTED_SIGNATURES_TOPIC],
+				"data": "0x000000000000000000000000aff3454fce5edbc8cca8697c15331677e6ebcccc00000000000000000000000000000000000000000000000000000000000000f0",
+				"type": "",
+				"transactionHash": "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364"
+			}]);
+		// call to `message`
+		"eth_call" =>
+			req => json!([{
+				"data": "0x490a32c600000000000000000000000000000000000000000000000000000000000000f0",
+				"to": "0x00000000000000000000000000000000000000ee"
+			}, "latest"]),
+			res => json!(format!("0x{}", MessageToMainnet {
+				recipient: [1u8; 20].into(),
+				value: 10000.into(),
+				sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+				mainnet_gas_price: 20_000_000_000u64.into(),
+			}.to_payload().to_hex()));
+		// calls to `signature`
+		"eth_call" =>
+			req => json!([{
+				"data": "0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000000",
+				"to": "0x00000000000000000000000000000000000000ee"
+			},"latest"]),
+			res => json!(format!("0x{}", Signature { v: 1, r: 2.into(), s: 3.into() }.to_payload().to_hex()));
+		"eth_call" =>
+			req => json!([{
+				"data": "0x1812d99600000000000000000000000000000000000000000000000000000000000000f00000000000000000000000000000000000000000000000000000000000000001",
+				"to": "0x00000000000000000000000000000000000000ee"
+			},"latest"]),
+			res => json!(format!("0x{}", Signature { v: 4, r: 5.into(), s: 6.into() }.to_payload().to_hex()));
+	]
+}
+
+// 2 signatures required.
+// single CollectedSignatures log present.
+// relay of the message would revert, it's skipped.
+test_app_stream! {
+	name => withdraw_relay_reverting_withdraw_skipped,
+	database => Database {
+		home_contract_address: "00000000000000000000000000000000000000dd".into(),
+		foreign_contract_address: "00000000000000000000000000000000000000ee".into(),
+		..Default::default()
+	},
+	home =>
+		account => "0000000000000000000000000000000000000001",
+		confirmations => 12;
+	foreign =>
+		account => "aff3454fce5edbc8cca8697c15331677e6ebcccc",
+		confirmations => 12;
+	authorities =>
+		accounts => [
+			"0000000000000000000000000000000000000001",
+			"0000000000000000000000000000000000000002",
+		],
+		signatures => 2;
+	txs => Transactions::default(),
+	init => |app, db| create_withdraw_relay(app, db).take(1),
+	expected => vec![0x1005],
+	home_transport => [
+		// `HomeBridge.withdraws`
+		"eth_getStorageAt" =>
+			req => json!([
+				"0x00000000000000000000000000000000000000dd",
+				contracts::storage::home_withdraw("884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".parse::<H256>().unwrap()),
+				"latest"
+			]),
+			res => json!("0x0000000000000000000000000000000000000000000000000000000000000000");
+		// `HomeBridge.withdraw`
+		"eth_estimateGas" =>
+			req => json!([{
+				"data": format!("0x{}", contracts::home::HomeBridge::default()
+					.functions()
+					.withdraw()
+					.input(
+						vec![U256::from(1), U256::from(4)],
+						vec![H256::from(2), H256::from(5)],
+						vec![H256::from(3), H256::from(6)],
+						MessageToMainnet {
+							recipient: [1u8; 20].into(),
+							value: 10000.into(),
+							sidenet_transaction_hash: "0x884edad9ce6fa2440d8a54cc123490eb96d2768479d49ff9c7366125a9424364".into(),
+							mainnet_gas_price: 1000.into(),
+						}.to_bytes()
+					).to_hex()),
+				"from": "0x0000000000000000000000000000000000000001",
+				"gas": "0x0",
+				"gasPrice": "0x3e8",
+				"to": "0x00000000000000000000000000000000000000dd"
+			}]),
+			res => json!({
+				"error": {
+					"code": -32000,
+					"message": "execution reverted"
+				}
+			});
 	],
 	foreign_transport => [
 		"eth_blockNumber" =>
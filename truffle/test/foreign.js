@@ -19,6 +19,50 @@ contract('ForeignBridge', function(accounts) {
     })
   })
 
+  it("should keep authorities in storage read by the bridge", function() {
+    var meta;
+    var authorities = [accounts[0], accounts[1]];
+    return ForeignBridge.new(1, authorities, 0).then(function(instance) {
+      meta = instance;
+      // `authorities` is at slot 6 of `ForeignBridge`
+      return Promise.all([
+        helpers.isStorageSet(meta.address, helpers.mappingPosition(accounts[1], 6)),
+        helpers.isStorageSet(meta.address, helpers.mappingPosition(accounts[2], 6)),
+      ]);
+    }).then(function(result) {
+      assert.deepEqual([true, false], result, "Contract reports invalid authorities");
+    })
+  })
+
+  it("should report votes for the token address", function() {
+    var meta;
+    var authorities = [accounts[0], accounts[1]];
+    var token = accounts[3];
+    return ForeignBridge.new(2, authorities, 0).then(function(instance) {
+      meta = instance;
+      return meta.setTokenAddress(token, { from: authorities[0] });
+    }).then(function(result) {
+      assert.equal(0, result.logs.length, "Token address should not be set by a single vote");
+      // `tokenAddressAprroval_signs` and `num_tokenAddressAprroval_signs` are at slots 14 and 15 of `ForeignBridge`
+      return Promise.all([
+        helpers.getStorageAt(meta.address, helpers.mappingPosition(token, 15)),
+        helpers.isStorageSet(meta.address, helpers.mappingPosition(helpers.senderHash(authorities[0], token), 14)),
+        helpers.isStorageSet(meta.address, helpers.mappingPosition(helpers.senderHash(authorities[1], token), 14)),
+      ]);
+    }).then(function(result) {
+      assert.equal(1, web3.toBigNumber(result[0]), "Contract reports invalid number of votes");
+      assert.deepEqual([true, false], result.slice(1), "Contract reports invalid votes of authorities");
+      return meta.setTokenAddress(token, { from: authorities[1] });
+    }).then(function(result) {
+      assert.equal(1, result.logs.length);
+      assert.equal("TokenAddress", result.logs[0].event);
+      assert.equal(token, result.logs[0].args.token);
+      return helpers.getStorageAt(meta.address, helpers.mappingPosition(token, 15));
+    }).then(function(result) {
+      assert.equal(2, web3.toBigNumber(result), "Contract reports invalid number of votes");
+    })
+  })
+
   it("should fail to deploy contract with not enough required signatures", function() {
     var authorities = [accounts[0], accounts[1]];
     return ForeignBridge.new(0, authorities, 0)
@@ -502,4 +546,69 @@ contract('ForeignBridge', function(accounts) {
         }, helpers.ignoreExpectedError)
     })
   })
+
+  it("should record deposits relayed by authority in storage read by the bridge", function() {
+    var meta;
+    var requiredSignatures = 2;
+    var estimatedGasCostOfWithdraw = 0;
+    var authorities = [accounts[0], accounts[1]];
+    var userAccount = accounts[2];
+    var value = web3.toBigNumber(web3.toWei(1, "ether"));
+    var hash = "0xe55bb43c36cdf79e23b4adc149cdded921f0d482e613c50c6540977c213bc408";
+    var deposit = web3.sha3(helpers.strip0x(userAccount) + helpers.strip0x(helpers.bigNumberToPaddedBytes32(value)) + helpers.strip0x(hash), { encoding: "hex" });
+    // `deposits_signed` is at slot 11 of `ForeignBridge`
+    var signedBy = function(authority) {
+      return helpers.mappingPosition(helpers.senderHash(authority, deposit), 11);
+    };
+    return ForeignBridge.new(requiredSignatures, authorities, estimatedGasCostOfWithdraw).then(function(instance) {
+      meta = instance;
+      // deposits require the token address, the token is never called before the deposit is relayed by both authorities
+      return meta.setTokenAddress(accounts[3], { from: authorities[0] });
+    }).then(function(_) {
+      return meta.setTokenAddress(accounts[3], { from: authorities[1] });
+    }).then(function(_) {
+      return helpers.isStorageSet(meta.address, signedBy(authorities[0]));
+    }).then(function(result) {
+      assert.equal(false, result, "Deposit should not be relayed yet");
+      return meta.deposit(userAccount, value, hash, { from: authorities[0] });
+    }).then(function(_) {
+      return helpers.isStorageSet(meta.address, signedBy(authorities[0]));
+    }).then(function(result) {
+      assert.equal(true, result, "Deposit should be relayed by the authority");
+      return helpers.isStorageSet(meta.address, signedBy(authorities[1]));
+    }).then(function(result) {
+      assert.equal(false, result, "Deposit should not be relayed by other authorities");
+    })
+  })
+
+  it("should record signatures submitted by authority in storage read by the bridge", function() {
+    var meta;
+    var requiredSignatures = 2;
+    var estimatedGasCostOfWithdraw = 0;
+    var authorities = [accounts[0], accounts[1]];
+    var recipientAccount = accounts[2];
+    var transactionHash = "0x1045bfe274b88120a6b1e5d01b5ec00ab5d01098346e90e7c7a3c9b8f0181c80";
+    var homeGasPrice = web3.toBigNumber(web3.toWei(3, "gwei"));
+    var message = helpers.createMessage(recipientAccount, web3.toBigNumber(1000), transactionHash, homeGasPrice);
+    // `messages_signed` is at slot 9 of `ForeignBridge`
+    var signedBy = function(authority) {
+      return helpers.mappingPosition(helpers.senderHash(authority, web3.sha3(message, { encoding: "hex" })), 9);
+    };
+    return ForeignBridge.new(requiredSignatures, authorities, estimatedGasCostOfWithdraw).then(function(instance) {
+      meta = instance;
+      return helpers.isStorageSet(meta.address, signedBy(authorities[0]));
+    }).then(function(result) {
+      assert.equal(false, result, "Message should not be signed yet");
+      return helpers.sign(authorities[0], message);
+    }).then(function(signature) {
+      return meta.submitSignature(signature, message, { from: authorities[0] });
+    }).then(function(_) {
+      return helpers.isStorageSet(meta.address, signedBy(authorities[0]));
+    }).then(function(result) {
+      assert.equal(true, result, "Message should be signed by the authority");
+      return helpers.isStorageSet(meta.address, signedBy(authorities[1]));
+    }).then(function(result) {
+      assert.equal(false, result, "Message should not be signed by other authorities");
+    })
+  })
 })
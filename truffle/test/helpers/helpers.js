@@ -108,6 +108,46 @@ function range(start, end) {
 }
 module.exports.range = range;
 
+// returns the storage position of the value of `key` in the mapping at `slot`.
+// `key` is a hex string of at most 32 bytes, it's padded like solidity pads mapping keys
+function mappingPosition(key, slot) {
+  key = strip0x(key);
+  while (key.length < 64) {
+    key = "0" + key;
+  }
+  return web3.sha3(key + strip0x(bigNumberToPaddedBytes32(web3.toBigNumber(slot))), { encoding: "hex" });
+}
+module.exports.mappingPosition = mappingPosition;
+
+// returns hex string of `keccak256(authority, hash)`, the key of entries signed by `authority`
+function senderHash(authority, hash) {
+  return web3.sha3(strip0x(authority) + strip0x(hash), { encoding: "hex" });
+}
+module.exports.senderHash = senderHash;
+
+// returns a Promise that resolves with the hex string stored by `address` at `position`
+function getStorageAt(address, position) {
+  return new Promise(function(resolve, reject) {
+    web3.eth.getStorageAt(address, position, function(err, result) {
+      if (err !== null) {
+        return reject(err);
+      } else {
+        return resolve(result);
+      }
+    })
+  })
+}
+module.exports.getStorageAt = getStorageAt;
+
+// returns a Promise that resolves with true if the storage of `address`
+// at `position` holds a non-zero value
+function isStorageSet(address, position) {
+  return getStorageAt(address, position).then(function(result) {
+    return web3.toBigNumber(result).greaterThan(0);
+  })
+}
+module.exports.isStorageSet = isStorageSet;
+
 // just used to signal/document that we're explicitely ignoring/expecting an error
 function ignoreExpectedError() {
 }
@@ -23,6 +23,23 @@ contract('HomeBridge', function(accounts) {
     })
   })
 
+  it("should keep authorities in storage read by the bridge", function() {
+    var meta;
+    var authorities = [accounts[0], accounts[1]];
+    // `authorities` is at slot 4 of `HomeBridge`, its elements start at `keccak256(4)`
+    var elements = web3.toBigNumber(web3.sha3(helpers.bigNumberToPaddedBytes32(web3.toBigNumber(4)), { encoding: "hex" }));
+    return HomeBridge.new(1, authorities, 0).then(function(instance) {
+      meta = instance;
+      return Promise.all([
+        helpers.getStorageAt(meta.address, 4),
+        helpers.getStorageAt(meta.address, "0x" + elements.plus(1).toString(16)),
+      ]);
+    }).then(function(result) {
+      assert.equal(2, web3.toBigNumber(result[0]).toNumber(), "Contract stores invalid number of authorities");
+      assert.equal(accounts[1], "0x" + helpers.bigNumberToPaddedBytes32(web3.toBigNumber(result[1])).slice(-40), "Contract stores invalid authorities");
+    })
+  })
+
   it("should fail to deploy contract with not enough required signatures", function() {
     var authorities = [accounts[0], accounts[1]];
     return HomeBridge.new(0, authorities)
@@ -118,6 +135,11 @@ contract('HomeBridge', function(accounts) {
       assert.equal("Withdraw", result.logs[0].event, "Event name should be Withdraw");
       assert.equal(recipientAccount, result.logs[0].args.recipient, "Event recipient should match recipient in message");
       assert(value.equals(result.logs[0].args.value), "Event value should match value in message");
+
+      // `withdraws` is at slot 5 of `HomeBridge`
+      return helpers.isStorageSet(homeBridge.address, helpers.mappingPosition("0x1045bfe274b88120a6b1e5d01b5ec00ab5d01098346e90e7c7a3c9b8f0181c80", 5));
+    }).then(function(result) {
+      assert.equal(true, result, "Withdraw should be marked as executed");
     })
   })
 